@@ -1,4 +1,7 @@
 /// Configuration management for IBKR MCP Server
 pub mod settings;
 
-pub use settings::{IBKRConfig, LoggingConfig, MCPConfig, Settings};
+pub use settings::{
+    BackendKind, ConfirmationConfig, IBKRConfig, LoggingConfig, MCPConfig, ReconnectConfig,
+    RiskConfig, Settings, Transport,
+};
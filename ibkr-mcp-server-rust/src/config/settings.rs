@@ -1,13 +1,20 @@
 use config::{Config, ConfigError, Environment};
 /// Application settings and configuration
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::error::IBKRMCPError;
+use crate::models::SecType;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Settings {
     pub ibkr: IBKRConfig,
     pub mcp: MCPConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
     #[serde(default = "default_environment")]
     pub environment: String,
 }
@@ -32,6 +39,108 @@ pub struct IBKRConfig {
 
     #[serde(default = "default_timeout")]
     pub timeout: u64,
+
+    #[serde(default)]
+    pub backend: BackendKind,
+
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+
+    /// Directory for state that must survive restarts (order ID high-water
+    /// marks); `None` keeps it in memory
+    #[serde(default = "default_state_dir")]
+    pub state_dir: Option<String>,
+}
+
+/// Supervisor settings for the live TWS connection
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReconnectConfig {
+    /// Reconnect automatically after the socket is lost
+    #[serde(default = "default_reconnect_enabled")]
+    pub enabled: bool,
+
+    /// Backoff before the first reconnect attempt, doubled on each failure
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound for the backoff between attempts
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// How often `reqCurrentTime` is sent as a heartbeat
+    #[serde(default = "default_heartbeat_interval_ms")]
+    pub heartbeat_interval_ms: u64,
+
+    /// Unanswered heartbeats before the connection is considered dead
+    #[serde(default = "default_max_missed_heartbeats")]
+    pub max_missed_heartbeats: u32,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_reconnect_enabled(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            heartbeat_interval_ms: default_heartbeat_interval_ms(),
+            max_missed_heartbeats: default_max_missed_heartbeats(),
+        }
+    }
+}
+
+fn default_reconnect_enabled() -> bool {
+    true
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_heartbeat_interval_ms() -> u64 {
+    10_000
+}
+
+fn default_max_missed_heartbeats() -> u32 {
+    3
+}
+
+/// Which broker backend serves IBKR requests
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// TWS / IB Gateway over the socket API
+    #[default]
+    Live,
+    /// Deterministic in-process data, for development and tests
+    Memory,
+}
+
+impl BackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackendKind::Live => "live",
+            BackendKind::Memory => "memory",
+        }
+    }
+}
+
+impl Default for IBKRConfig {
+    fn default() -> Self {
+        Self {
+            host: default_ibkr_host(),
+            port: default_ibkr_port(),
+            client_id: default_client_id(),
+            readonly: false,
+            timeout: default_timeout(),
+            backend: BackendKind::default(),
+            reconnect: ReconnectConfig::default(),
+            state_dir: default_state_dir(),
+        }
+    }
 }
 
 fn default_ibkr_host() -> String {
@@ -50,8 +159,48 @@ fn default_timeout() -> u64 {
     30
 }
 
+fn default_state_dir() -> Option<String> {
+    Some("./state".to_string())
+}
+
+/// How MCP clients reach the server
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// JSON-RPC over HTTP on `mcp.host:mcp.port`
+    #[default]
+    Http,
+    /// Newline-delimited JSON-RPC on stdin/stdout, for clients that launch
+    /// the server as a subprocess
+    Stdio,
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transport::Http => "http",
+            Transport::Stdio => "stdio",
+        }
+    }
+}
+
+impl FromStr for Transport {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(Transport::Http),
+            "stdio" => Ok(Transport::Stdio),
+            _ => Err(IBKRMCPError::Config(format!("Unknown transport: {}", s))),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MCPConfig {
+    #[serde(default)]
+    pub transport: Transport,
+
     #[serde(default = "default_mcp_host")]
     pub host: String,
 
@@ -60,6 +209,69 @@ pub struct MCPConfig {
 
     #[serde(default = "default_max_connections")]
     pub max_connections: usize,
+
+    /// Bearer token the `/admin` routes require; they are refused while
+    /// it is unset
+    #[serde(default)]
+    pub admin_token: Option<String>,
+
+    /// Browser origins allowed to call `/mcp` besides loopback ones; other
+    /// `Origin` headers are refused to guard against DNS rebinding
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+
+    /// How long a `client_order_key` keeps returning the order it placed
+    #[serde(default = "default_order_key_ttl_secs")]
+    pub order_key_ttl_secs: u64,
+
+    /// How long an idle Streamable HTTP session is kept
+    #[serde(default = "default_session_ttl_secs")]
+    pub session_ttl_secs: u64,
+
+    /// How long a POST waits for its response before switching to an SSE
+    /// stream, when the client accepts one
+    #[serde(default = "default_sse_upgrade_after_ms")]
+    pub sse_upgrade_after_ms: u64,
+}
+
+/// Two-phase confirmation of orders placed through MCP tools
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConfirmationConfig {
+    /// When set, `place_order` returns a ticket that must be confirmed
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How long a ticket can be confirmed after it was issued
+    #[serde(default = "default_ticket_ttl_secs")]
+    pub ticket_ttl_secs: u64,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ticket_ttl_secs: default_ticket_ttl_secs(),
+        }
+    }
+}
+
+fn default_ticket_ttl_secs() -> u64 {
+    120
+}
+
+fn default_order_key_ttl_secs() -> u64 {
+    86_400
+}
+
+fn default_session_ttl_secs() -> u64 {
+    3_600
+}
+
+fn default_sse_upgrade_after_ms() -> u64 {
+    1_000
 }
 
 fn default_mcp_host() -> String {
@@ -74,6 +286,44 @@ fn default_max_connections() -> usize {
     100
 }
 
+/// Pre-trade limits checked before any order is transmitted. Every limit
+/// is optional; unset limits are not enforced.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct RiskConfig {
+    /// Largest notional (quantity × price × multiplier) of a single order
+    #[serde(default)]
+    pub max_order_notional: Option<f64>,
+
+    /// Largest quantity of a single order, for symbols without an override
+    #[serde(default)]
+    pub max_order_quantity: Option<f64>,
+
+    /// Per-symbol overrides of `max_order_quantity`
+    #[serde(default)]
+    pub symbol_max_quantity: HashMap<String, f64>,
+
+    /// Largest absolute position in one symbol once the order fills
+    #[serde(default)]
+    pub max_position: Option<f64>,
+
+    /// Total notional of orders submitted per UTC day
+    #[serde(default)]
+    pub max_daily_notional: Option<f64>,
+
+    /// Symbols that may be traded; empty allows all
+    #[serde(default)]
+    pub allowed_symbols: Vec<String>,
+
+    /// Security types that may be traded; empty allows all
+    #[serde(default)]
+    pub allowed_sec_types: Vec<SecType>,
+
+    /// How far, in percent, a limit or stop trigger price may be from the
+    /// last trade price
+    #[serde(default)]
+    pub price_collar_pct: Option<f64>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
@@ -103,9 +353,17 @@ impl Settings {
             .set_default("ibkr.client_id", 1)?
             .set_default("ibkr.readonly", false)?
             .set_default("ibkr.timeout", 30)?
+            .set_default("ibkr.backend", "live")?
+            .set_default("ibkr.state_dir", "./state")?
+            .set_default("mcp.transport", "http")?
             .set_default("mcp.host", "0.0.0.0")?
             .set_default("mcp.port", 8080)?
             .set_default("mcp.max_connections", 100)?
+            .set_default("mcp.confirmation.enabled", false)?
+            .set_default("mcp.confirmation.ticket_ttl_secs", 120)?
+            .set_default("mcp.order_key_ttl_secs", 86_400)?
+            .set_default("mcp.session_ttl_secs", 3_600)?
+            .set_default("mcp.sse_upgrade_after_ms", 1_000)?
             .set_default("logging.level", "info")?
             .set_default("logging.format", "pretty")?
             .set_default("environment", "development")?
@@ -113,7 +371,11 @@ impl Settings {
             .add_source(
                 Environment::with_prefix("IBKR")
                     .separator("__")
-                    .try_parsing(true),
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("mcp.allowed_origins")
+                    .with_list_parse_key("risk.allowed_symbols")
+                    .with_list_parse_key("risk.allowed_sec_types"),
             )
             .build()?;
 
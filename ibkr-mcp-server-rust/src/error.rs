@@ -1,37 +1,59 @@
 /// Custom error types for IBKR MCP Server
 use thiserror::Error;
 
+use crate::risk::RiskViolation;
+
 #[derive(Error, Debug)]
 pub enum IBKRMCPError {
     #[error("IBKR connection error: {0}")]
     Connection(String),
-    
+
     #[error("IBKR order error: {0}")]
     Order(String),
-    
+
     #[error("Market data error: {0}")]
     MarketData(String),
-    
+
     #[error("Configuration error: {0}")]
     Config(String),
-    
+
     #[error("MCP protocol error: {0}")]
     Protocol(String),
-    
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
-    
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
-    
+
     #[error("Timeout error")]
     Timeout,
-    
+
     #[error("Not connected to IBKR")]
     NotConnected,
-    
+
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
+
+    #[error("Read-only mode: {0} is disabled")]
+    ReadOnly(String),
+
+    #[error("Trading halted: {0}")]
+    TradingHalted(String),
+
+    #[error("Order ticket error: {0}")]
+    Ticket(String),
+
+    #[error("Risk check failed: {}", describe_violations(.0))]
+    RiskRejected(Vec<RiskViolation>),
+}
+
+fn describe_violations(violations: &[RiskViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| v.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 pub type Result<T> = std::result::Result<T, IBKRMCPError>;
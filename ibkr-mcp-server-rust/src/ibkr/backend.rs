@@ -0,0 +1,82 @@
+/// Broker backend abstraction
+///
+/// `IBKRClient` forwards every broker operation to a `BrokerBackend`, so the
+/// live TWS session and the deterministic in-memory data set are
+/// interchangeable and always identifiable via [`BrokerBackend::kind`].
+use async_trait::async_trait;
+
+use super::connection::ConnectionState;
+use super::order_book::{OrderRefMatch, TrackedOrder};
+use crate::{
+    config::BackendKind,
+    error::Result,
+    models::{AccountValue, BarData, Contract, MarketSnapshot, Order, OrderPreview, Position},
+};
+
+#[async_trait]
+pub trait BrokerBackend: Send + Sync {
+    /// Which implementation this is, reported alongside every response
+    fn kind(&self) -> BackendKind;
+
+    async fn connect(&self) -> Result<()>;
+
+    async fn disconnect(&self) -> Result<()>;
+
+    async fn is_connected(&self) -> bool;
+
+    /// Drop the session and establish a new one
+    async fn reconnect(&self) -> Result<()> {
+        self.disconnect().await?;
+        self.connect().await
+    }
+
+    /// Connection health for status reporting
+    async fn connection_state(&self) -> ConnectionState {
+        if self.is_connected().await {
+            ConnectionState::Connected
+        } else {
+            ConnectionState::Disconnected
+        }
+    }
+
+    /// Accounts visible to this session
+    async fn managed_accounts(&self) -> Vec<String>;
+
+    /// Next order ID the broker will accept, if known
+    async fn next_valid_id(&self) -> Option<i32>;
+
+    async fn get_account_summary(&self) -> Result<Vec<AccountValue>>;
+
+    async fn get_positions(&self) -> Result<Vec<Position>>;
+
+    async fn place_order(&self, contract: &Contract, order: &Order) -> Result<i32>;
+
+    /// Margin and commission impact of `order`; it is never transmitted
+    async fn preview_order(&self, contract: &Contract, order: &Order) -> Result<OrderPreview>;
+
+    async fn cancel_order(&self, order_id: i32) -> Result<bool>;
+
+    /// Cancel every working order, including those placed by other
+    /// sessions, returning the IDs that were working
+    async fn global_cancel(&self) -> Result<Vec<i32>>;
+
+    /// Orders still working at the broker
+    async fn get_open_orders(&self) -> Result<Vec<TrackedOrder>>;
+
+    /// Lifecycle of one order placed or seen during this session
+    async fn get_order_status(&self, order_id: i32) -> Result<TrackedOrder>;
+
+    /// Orders tagged with `order_ref`: working, completed today or filled,
+    /// including those placed by earlier sessions
+    async fn find_orders_by_ref(&self, order_ref: &str) -> Result<Vec<OrderRefMatch>>;
+
+    async fn get_market_data(&self, contract: &Contract) -> Result<MarketSnapshot>;
+
+    async fn get_historical_data(
+        &self,
+        contract: &Contract,
+        duration: &str,
+        bar_size: &str,
+        what_to_show: &str,
+    ) -> Result<Vec<BarData>>;
+}
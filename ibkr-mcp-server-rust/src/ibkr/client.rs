@@ -1,260 +1,654 @@
+use std::path::Path;
 use std::sync::Arc;
 /// IBKR Client implementation
 ///
 /// Provides async wrapper around IBKR TWS API
-use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+use super::backend::BrokerBackend;
+use super::connection::ConnectionState;
+use super::halt::{HaltReport, HaltSwitch, TradingHalt};
+use super::live::LiveBackend;
+use super::memory::InMemoryBackend;
+use super::order_book::{CancelOutcome, OrderFilter, OrderRefMatch, TrackedOrder};
 use crate::{
-    config::IBKRConfig,
+    config::{BackendKind, IBKRConfig, RiskConfig},
     error::{IBKRMCPError, Result},
-    models::{Contract, Order, Position},
+    models::{
+        AccountValue, BarData, BracketOrder, BracketOrderIds, Contract, MarketSnapshot, OcaType,
+        Order, OrderAction, OrderChanges, OrderPreview, OrderStatus, OrderType, Position,
+    },
+    risk::{RiskContext, RiskEngine, RiskReservation},
 };
 
 pub struct IBKRClient {
     config: IBKRConfig,
-    connected: Arc<RwLock<bool>>,
-    // Note: ibapi client will be added once we integrate the library
-    // client: Arc<RwLock<Option<IB>>>,
+    backend: Arc<dyn BrokerBackend>,
+    risk: RiskEngine,
+    halt: HaltSwitch,
 }
 
 impl IBKRClient {
+    /// Create a client using the backend selected by `config.backend`
     pub fn new(config: IBKRConfig) -> Self {
+        let backend: Arc<dyn BrokerBackend> = match config.backend {
+            BackendKind::Live => Arc::new(LiveBackend::new(config.clone())),
+            BackendKind::Memory => Arc::new(InMemoryBackend::new()),
+        };
+        Self::with_backend(config, backend)
+    }
+
+    /// Create a client around an explicit backend
+    pub fn with_backend(config: IBKRConfig, backend: Arc<dyn BrokerBackend>) -> Self {
+        let halt = HaltSwitch::new(config.state_dir.as_deref().map(Path::new));
         Self {
             config,
-            connected: Arc::new(RwLock::new(false)),
+            backend,
+            risk: RiskEngine::new(RiskConfig::default()),
+            halt,
         }
     }
 
+    /// Enforce `limits` on every order this client transmits
+    pub fn with_risk_limits(mut self, limits: RiskConfig) -> Self {
+        self.risk = RiskEngine::new(limits);
+        self
+    }
+
+    pub fn risk(&self) -> &RiskEngine {
+        &self.risk
+    }
+
+    /// Which backend is serving requests
+    pub fn backend_kind(&self) -> BackendKind {
+        self.backend.kind()
+    }
+
+    pub fn config(&self) -> &IBKRConfig {
+        &self.config
+    }
+
+    /// Whether `IBKRConfig.readonly` blocks order and session changes
+    pub fn is_readonly(&self) -> bool {
+        self.config.readonly
+    }
+
+    /// Refuse `operation` when running read-only
+    fn ensure_writable(&self, operation: &str) -> Result<()> {
+        if self.config.readonly {
+            warn!("Refusing {} in read-only mode", operation);
+            return Err(IBKRMCPError::ReadOnly(operation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// The engaged trading halt, if any
+    pub fn trading_halt(&self) -> Option<TradingHalt> {
+        self.halt.current()
+    }
+
+    /// Refuse `operation` while trading is halted
+    pub fn ensure_trading_allowed(&self, operation: &str) -> Result<()> {
+        match self.halt.current() {
+            Some(halt) => {
+                warn!("Refusing {} while trading is halted", operation);
+                Err(IBKRMCPError::TradingHalted(format!(
+                    "{} refused, halted since {}: {}",
+                    operation,
+                    halt.halted_at.to_rfc3339(),
+                    halt.reason
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Engage the kill switch: block new orders, then optionally cancel
+    /// every working order and close every position at market. The halt
+    /// stays engaged and every step is attempted even if an earlier one
+    /// fails; failures are listed in the report.
+    pub async fn halt_trading(
+        &self,
+        reason: &str,
+        cancel_orders: bool,
+        flatten_positions: bool,
+    ) -> Result<HaltReport> {
+        let mut report = HaltReport {
+            halt: self.halt.engage(reason)?,
+            cancelled_orders: Vec::new(),
+            flatten_orders: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        if cancel_orders {
+            let cancelled = async {
+                self.ensure_writable("cancel_orders")?;
+                self.backend.global_cancel().await
+            };
+            match cancelled.await {
+                Ok(cancelled) => {
+                    info!("Global cancel covered {} orders", cancelled.len());
+                    report.cancelled_orders = cancelled;
+                }
+                Err(e) => {
+                    error!("Global cancel failed: {}", e);
+                    report.errors.push(format!("cancel_orders: {}", e));
+                }
+            }
+        }
+
+        if flatten_positions {
+            if let Err(e) = self.flatten_positions(&mut report).await {
+                error!("Flattening positions failed: {}", e);
+                report.errors.push(format!("flatten_positions: {}", e));
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Send a market order closing each position, recording every leg sent
+    // and every leg that failed
+    async fn flatten_positions(&self, report: &mut HaltReport) -> Result<()> {
+        self.ensure_writable("flatten_positions")?;
+        for position in self.backend.get_positions().await? {
+            if position.position == 0.0 {
+                continue;
+            }
+            let action = if position.position > 0.0 {
+                OrderAction::Sell
+            } else {
+                OrderAction::Buy
+            };
+            let mut contract = position.contract;
+            if contract.exchange.is_empty() {
+                contract.exchange = "SMART".to_string();
+            }
+            // Closing orders bypass the halt and the risk limits
+            let mut order = Order::new(action, position.position.abs(), OrderType::Market);
+            order.account = Some(position.account.clone()).filter(|a| !a.is_empty());
+            info!(
+                "Flattening {} {} ({})",
+                position.position, contract.symbol, position.account
+            );
+            match self.backend.place_order(&contract, &order).await {
+                Ok(order_id) => report.flatten_orders.push(order_id),
+                Err(e) => {
+                    error!("Flattening {} failed: {}", contract.symbol, e);
+                    report.errors.push(format!(
+                        "flatten {} {}: {}",
+                        position.account, contract.symbol, e
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lift the kill switch, returning the halt that was engaged
+    pub fn resume_trading(&self) -> Result<Option<TradingHalt>> {
+        self.halt.release()
+    }
+
     pub async fn connect(&self) -> Result<()> {
         info!(
-            "Connecting to IBKR at {}:{}",
-            self.config.host, self.config.port
+            "Connecting to IBKR at {}:{} ({} backend)",
+            self.config.host,
+            self.config.port,
+            self.backend.kind().as_str()
         );
 
-        // TODO: Implement actual IBKR connection using ibapi crate
-        // For now, we'll simulate connection
-
-        let mut connected = self.connected.write().await;
-        *connected = true;
+        self.backend.connect().await?;
 
-        info!("Successfully connected to IBKR");
+        info!(
+            "Successfully connected to IBKR (accounts {:?})",
+            self.backend.managed_accounts().await
+        );
         Ok(())
     }
 
     pub async fn disconnect(&self) -> Result<()> {
         info!("Disconnecting from IBKR");
 
-        let mut connected = self.connected.write().await;
-        *connected = false;
+        self.backend.disconnect().await?;
 
         info!("Disconnected from IBKR");
         Ok(())
     }
 
     pub async fn is_connected(&self) -> bool {
-        *self.connected.read().await
+        self.backend.is_connected().await
     }
 
     pub async fn reconnect(&self) -> Result<()> {
+        self.ensure_writable("reconnect")?;
         warn!("Attempting to reconnect to IBKR");
+        self.backend.reconnect().await
+    }
 
-        self.disconnect().await?;
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        self.connect().await?;
+    /// Connection health, including reconnects in progress
+    pub async fn connection_state(&self) -> ConnectionState {
+        self.backend.connection_state().await
+    }
 
-        Ok(())
+    /// Accounts reported by the gateway in `managedAccounts`
+    pub async fn managed_accounts(&self) -> Vec<String> {
+        self.backend.managed_accounts().await
     }
 
-    fn ensure_connected(&self) -> Result<()> {
-        // This will be called before any operation
-        // In async context, we'll need to modify this
-        Ok(())
+    /// Order ID reported by the gateway in `nextValidId`
+    pub async fn next_valid_id(&self) -> Option<i32> {
+        self.backend.next_valid_id().await
     }
 
     // Account operations
-    pub async fn get_account_summary(&self) -> Result<Vec<serde_json::Value>> {
+    pub async fn get_account_summary(&self) -> Result<Vec<AccountValue>> {
         info!("Fetching account summary");
-
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
-        }
-
-        // Return mock data
-        Ok(vec![
-            serde_json::json!({
-                "account": "DU123456",
-                "tag": "NetLiquidation",
-                "value": "150000.00",
-                "currency": "USD"
-            }),
-            serde_json::json!({
-                "account": "DU123456",
-                "tag": "TotalCashValue",
-                "value": "50000.00",
-                "currency": "USD"
-            }),
-            serde_json::json!({
-                "account": "DU123456",
-                "tag": "GrossPositionValue",
-                "value": "100000.00",
-                "currency": "USD"
-            }),
-        ])
+        self.backend.get_account_summary().await
     }
 
     pub async fn get_positions(&self) -> Result<Vec<Position>> {
         info!("Fetching positions");
-
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
-        }
-
-        // Return mock positions
-        use crate::models::SecType;
-
-        Ok(vec![
-            Position {
-                account: "DU123456".to_string(),
-                contract: Contract::new("AAPL", SecType::Stock),
-                position: 100.0,
-                avg_cost: 150.25,
-                market_price: Some(175.50),
-                market_value: Some(17550.0),
-                unrealized_pnl: Some(2525.0),
-                realized_pnl: Some(0.0),
-            },
-            Position {
-                account: "DU123456".to_string(),
-                contract: Contract::new("MSFT", SecType::Stock),
-                position: 50.0,
-                avg_cost: 350.00,
-                market_price: Some(375.00),
-                market_value: Some(18750.0),
-                unrealized_pnl: Some(1250.0),
-                realized_pnl: Some(0.0),
-            },
-        ])
+        self.backend.get_positions().await
     }
 
     // Order operations
-    pub async fn place_order(&self, contract: &Contract, _order: &Order) -> Result<i32> {
+    pub async fn place_order(&self, contract: &Contract, order: &Order) -> Result<i32> {
+        self.ensure_writable("place_order")?;
+        self.ensure_trading_allowed("place_order")?;
         info!("Placing order for {}", contract.symbol);
+        order.validate()?;
+        let reservation = self.reserve_risk(contract, order, None).await?;
 
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
-        }
+        let order_id = self.backend.place_order(contract, order).await?;
+        reservation.commit();
+        Ok(order_id)
+    }
 
-        // Return mock order ID
-        use std::sync::atomic::{AtomicI32, Ordering};
-        static ORDER_ID_COUNTER: AtomicI32 = AtomicI32::new(1000);
-        let order_id = ORDER_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
+    /// Check an order against the risk limits without transmitting it or
+    /// holding any of the daily notional, returning its notional
+    pub async fn check_risk(&self, contract: &Contract, order: &Order) -> Result<f64> {
+        Ok(self.reserve_risk(contract, order, None).await?.notional())
+    }
 
-        Ok(order_id)
+    /// Risk check for `order`, which may amend `replaced`, reserving the
+    /// notional it adds to the daily total
+    async fn reserve_risk(
+        &self,
+        contract: &Contract,
+        order: &Order,
+        replaced: Option<&Order>,
+    ) -> Result<RiskReservation<'_>> {
+        let ctx = self.risk_context(contract, order, replaced).await?;
+        self.risk.check(contract, order, &ctx).map_err(|e| {
+            warn!("Order for {} failed risk checks: {}", contract.symbol, e);
+            e
+        })
     }
 
-    pub async fn cancel_order(&self, order_id: i32) -> Result<bool> {
-        info!("Cancelling order {}", order_id);
+    // What the risk checks of `order` need to know about the market and
+    // the account
+    async fn risk_context(
+        &self,
+        contract: &Contract,
+        order: &Order,
+        replaced: Option<&Order>,
+    ) -> Result<RiskContext> {
+        let same_contract = |other: &Contract| {
+            other.symbol.eq_ignore_ascii_case(&contract.symbol)
+                && other.sec_type == contract.sec_type
+        };
+        let mut ctx = RiskContext::default();
+        if self.risk.needs_last_price(order)
+            || replaced.is_some_and(|o| self.risk.needs_last_price(o))
+        {
+            ctx.last_price = self.last_price(contract).await;
+        }
+        if self.risk.needs_position() {
+            ctx.position = self
+                .backend
+                .get_positions()
+                .await?
+                .iter()
+                .filter(|p| same_contract(&p.contract))
+                .map(|p| p.position)
+                .sum();
+            // Working orders on the same side grow the position too; an
+            // amended order is replaced rather than added to
+            let working: f64 = self
+                .backend
+                .get_open_orders()
+                .await?
+                .iter()
+                .filter(|o| {
+                    !o.status.is_terminal()
+                        && o.order.action == order.action
+                        && same_contract(&o.contract)
+                        && Some(o.order_id) != order.order_id
+                })
+                .map(|o| o.remaining)
+                .sum();
+            ctx.working = match order.action {
+                OrderAction::Buy => working,
+                OrderAction::Sell => -working,
+            };
+        }
+        if let Some(replaced) = replaced {
+            ctx.replaced_notional = self
+                .risk
+                .notional(contract, replaced, ctx.last_price)
+                .unwrap_or(0.0);
+        }
+        Ok(ctx)
+    }
 
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
+    // Last trade price for risk checks; unavailable data is left to the
+    // limits that need it to report
+    async fn last_price(&self, contract: &Contract) -> Option<f64> {
+        match self.backend.get_market_data(contract).await {
+            Ok(snapshot) => snapshot.last.filter(|price| *price > 0.0),
+            Err(e) => {
+                warn!(
+                    "No market data for risk checks on {}: {}",
+                    contract.symbol, e
+                );
+                None
+            }
         }
+    }
 
-        // Mock cancellation success
-        Ok(true)
+    /// Margin and commission impact of an order, without transmitting it
+    pub async fn preview_order(&self, contract: &Contract, order: &Order) -> Result<OrderPreview> {
+        info!("Previewing order for {}", contract.symbol);
+        order.validate()?;
+        self.backend.preview_order(contract, order).await
     }
 
-    pub async fn get_open_orders(&self) -> Result<Vec<serde_json::Value>> {
-        info!("Fetching open orders");
+    /// Submit an entry with take-profit and stop-loss exits. If any leg
+    /// fails, the legs already sent are cancelled.
+    pub async fn place_bracket_order(
+        &self,
+        contract: &Contract,
+        bracket: BracketOrder,
+    ) -> Result<BracketOrderIds> {
+        self.ensure_writable("place_bracket_order")?;
+        self.ensure_trading_allowed("place_bracket_order")?;
+        info!("Placing bracket order for {}", contract.symbol);
+        bracket.parent.validate()?;
+        // The exits only ever close the entry, so the entry carries the risk
+        let reservation = self.reserve_risk(contract, &bracket.parent, None).await?;
+
+        let parent_id = self.backend.place_order(contract, &bracket.parent).await?;
+        let bracket = bracket.with_parent_id(parent_id);
+        let mut placed = vec![parent_id];
+
+        let mut ids = Vec::with_capacity(2);
+        for leg in [&bracket.take_profit, &bracket.stop_loss] {
+            match self.backend.place_order(contract, leg).await {
+                Ok(order_id) => {
+                    placed.push(order_id);
+                    ids.push(order_id);
+                }
+                Err(e) => {
+                    self.cancel_quietly(&placed).await;
+                    return Err(e);
+                }
+            }
+        }
 
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
+        // The parent is only acknowledged once the last leg is transmitted
+        if let Ok(parent) = self.backend.get_order_status(parent_id).await {
+            if parent.status == OrderStatus::Rejected {
+                self.cancel_quietly(&placed).await;
+                return Err(IBKRMCPError::Order(format!(
+                    "Bracket parent {} rejected: {}",
+                    parent_id,
+                    parent.last_error.unwrap_or_default()
+                )));
+            }
         }
 
-        // Return mock open orders
-        Ok(vec![serde_json::json!({
-            "order_id": 1001,
-            "symbol": "TSLA",
-            "action": "BUY",
-            "quantity": 10.0,
-            "order_type": "LMT",
-            "limit_price": 180.00,
-            "status": "Submitted",
-            "filled": 0.0,
-            "remaining": 10.0
-        })])
+        // Only a bracket that was sent in full counts towards the day
+        reservation.commit();
+        Ok(BracketOrderIds {
+            parent_order_id: parent_id,
+            take_profit_order_id: ids[0],
+            stop_loss_order_id: ids[1],
+        })
     }
 
-    // Market data operations
-    pub async fn get_market_data(&self, contract: &Contract) -> Result<serde_json::Value> {
-        info!("Fetching market data for {}", contract.symbol);
-
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
+    /// Place several orders in one OCA group: once one fills, TWS cancels
+    /// (or reduces) the others. Orders already placed are cancelled if a
+    /// later one fails.
+    pub async fn place_oca_group(
+        &self,
+        group: &str,
+        oca_type: OcaType,
+        legs: Vec<(Contract, Order)>,
+    ) -> Result<Vec<i32>> {
+        self.ensure_writable("place_oca_group")?;
+        self.ensure_trading_allowed("place_oca_group")?;
+        info!("Placing {} orders in OCA group {}", legs.len(), group);
+
+        if group.trim().is_empty() {
+            return Err(IBKRMCPError::InvalidParameter(
+                "oca_group must not be empty".to_string(),
+            ));
+        }
+        if legs.len() < 2 {
+            return Err(IBKRMCPError::InvalidParameter(
+                "An OCA group needs at least two orders".to_string(),
+            ));
         }
 
-        // Return mock market data
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+        // At most one leg fills, so only the largest one is reserved
+        // towards the day once every leg passed on its own
+        let mut largest: Option<(usize, RiskContext, f64)> = None;
+        for (i, (contract, order)) in legs.iter().enumerate() {
+            order.validate()?;
+            let ctx = self.risk_context(contract, order, None).await?;
+            let notional = self.risk.check(contract, order, &ctx)?.notional();
+            let larger = match &largest {
+                Some((_, _, max)) => notional > *max,
+                None => true,
+            };
+            if larger {
+                largest = Some((i, ctx, notional));
+            }
+        }
+        let (i, ctx, _) = largest.expect("at least two legs");
+        let reservation = self.risk.check(&legs[i].0, &legs[i].1, &ctx)?;
+
+        let mut placed = Vec::with_capacity(legs.len());
+        for (contract, order) in legs {
+            let order = order.with_oca_group(group, oca_type);
+            match self.backend.place_order(&contract, &order).await {
+                Ok(order_id) => placed.push(order_id),
+                Err(e) => {
+                    self.cancel_quietly(&placed).await;
+                    return Err(e);
+                }
+            }
+        }
+        reservation.commit();
+        Ok(placed)
+    }
 
-        let base_price = match contract.symbol.as_str() {
-            "AAPL" => 175.0,
-            "MSFT" => 375.0,
-            "TSLA" => 180.0,
-            "GOOGL" => 140.0,
-            _ => 100.0,
-        };
+    /// Cancel every working order in an OCA group, returning the IDs that
+    /// were cancelled
+    pub async fn cancel_oca_group(&self, group: &str) -> Result<Vec<i32>> {
+        self.ensure_writable("cancel_oca_group")?;
+        info!("Cancelling OCA group {}", group);
+
+        let members: Vec<i32> = self
+            .backend
+            .get_open_orders()
+            .await?
+            .into_iter()
+            .filter(|o| o.order.oca_group.as_deref() == Some(group))
+            .map(|o| o.order_id)
+            .collect();
+        if members.is_empty() {
+            return Err(IBKRMCPError::Order(format!(
+                "No open orders in OCA group {}",
+                group
+            )));
+        }
 
-        let variation = rng.gen_range(-2.0..2.0);
+        let mut cancelled = Vec::with_capacity(members.len());
+        for order_id in members {
+            if self.backend.cancel_order(order_id).await? {
+                cancelled.push(order_id);
+            }
+        }
+        Ok(cancelled)
+    }
 
-        Ok(serde_json::json!({
-            "symbol": contract.symbol,
-            "last": base_price + variation,
-            "bid": base_price + variation - 0.05,
-            "ask": base_price + variation + 0.05,
-            "volume": rng.gen_range(1000000..5000000),
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        }))
+    /// Best-effort cleanup after a partially placed order group
+    async fn cancel_quietly(&self, order_ids: &[i32]) {
+        for &order_id in order_ids.iter().rev() {
+            if let Err(e) = self.backend.cancel_order(order_id).await {
+                warn!("Failed to cancel order {} during cleanup: {}", order_id, e);
+            }
+        }
     }
 
-    pub async fn get_historical_data(
+    /// Amend a working order in place, keeping its ID and queue position
+    /// where the exchange allows it
+    pub async fn modify_order(
         &self,
-        contract: &Contract,
-        _duration: &str,
-        _bar_size: &str,
-        _what_to_show: &str,
-    ) -> Result<Vec<serde_json::Value>> {
-        info!("Fetching historical data for {}", contract.symbol);
+        order_id: i32,
+        changes: &OrderChanges,
+    ) -> Result<TrackedOrder> {
+        self.ensure_writable("modify_order")?;
+        self.ensure_trading_allowed("modify_order")?;
+        info!("Modifying order {}", order_id);
+
+        if changes.is_empty() {
+            return Err(IBKRMCPError::InvalidParameter(
+                "No changes given for modify_order".to_string(),
+            ));
+        }
 
-        if !self.is_connected().await {
-            return Err(IBKRMCPError::NotConnected);
+        let tracked = self.backend.get_order_status(order_id).await?;
+        if !tracked.status.is_modifiable() {
+            return Err(IBKRMCPError::Order(format!(
+                "Order {} is {} and can no longer be modified",
+                order_id,
+                tracked.status.as_str()
+            )));
+        }
+        if let Some(quantity) = changes.quantity {
+            if quantity <= tracked.filled {
+                return Err(IBKRMCPError::InvalidParameter(format!(
+                    "quantity {} must exceed the {} already filled",
+                    quantity, tracked.filled
+                )));
+            }
         }
 
-        // Return mock historical bars
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let mut bars = Vec::new();
+        let mut order = tracked.order.clone().with_changes(changes);
+        order.order_id = Some(order_id);
+        order.validate()?;
+        let reservation = self
+            .reserve_risk(&tracked.contract, &order, Some(&tracked.order))
+            .await?;
 
-        let base_price = 100.0;
-        let now = chrono::Utc::now();
+        self.backend.place_order(&tracked.contract, &order).await?;
+        reservation.commit();
+        self.backend.get_order_status(order_id).await
+    }
+
+    pub async fn cancel_order(&self, order_id: i32) -> Result<bool> {
+        self.ensure_writable("cancel_order")?;
+        info!("Cancelling order {}", order_id);
+        self.backend.cancel_order(order_id).await
+    }
+
+    /// Cancel every working order matching `filter`, or all of them when
+    /// the filter is empty, reporting the outcome per order
+    pub async fn cancel_orders(&self, filter: &OrderFilter) -> Result<Vec<CancelOutcome>> {
+        self.ensure_writable("cancel_orders")?;
 
-        for i in 0..10 {
-            let time = now - chrono::Duration::minutes(10 - i);
-            let open = base_price + rng.gen_range(-1.0..1.0);
-            let high = open + rng.gen_range(0.0..0.5);
-            let low = open - rng.gen_range(0.0..0.5);
-            let close = open + rng.gen_range(-0.3..0.3);
-
-            bars.push(serde_json::json!({
-                "date": time.to_rfc3339(),
-                "open": open,
-                "high": high,
-                "low": low,
-                "close": close,
-                "volume": rng.gen_range(100000..500000)
-            }));
+        let now = chrono::Utc::now();
+        let targets: Vec<_> = self
+            .backend
+            .get_open_orders()
+            .await?
+            .into_iter()
+            .filter(|o| filter.matches(o, now))
+            .collect();
+        info!("Cancelling {} orders matching {:?}", targets.len(), filter);
+
+        let mut outcomes = Vec::with_capacity(targets.len());
+        for target in targets {
+            let outcome = |cancelled, status, error| CancelOutcome {
+                order_id: target.order_id,
+                symbol: target.contract.symbol.clone(),
+                cancelled,
+                status,
+                error,
+            };
+            // Attached orders may already have gone with their parent
+            if let Ok(current) = self.backend.get_order_status(target.order_id).await {
+                if !current.is_open() {
+                    let cancelled = current.status == OrderStatus::Cancelled;
+                    outcomes.push(outcome(cancelled, Some(current.status), None));
+                    continue;
+                }
+            }
+
+            outcomes.push(match self.backend.cancel_order(target.order_id).await {
+                Ok(cancelled) => {
+                    let status = self
+                        .backend
+                        .get_order_status(target.order_id)
+                        .await
+                        .ok()
+                        .map(|o| o.status);
+                    outcome(cancelled, status, None)
+                }
+                Err(e) => {
+                    warn!("Failed to cancel order {}: {}", target.order_id, e);
+                    outcome(false, None, Some(e.to_string()))
+                }
+            });
         }
+        Ok(outcomes)
+    }
+
+    pub async fn get_open_orders(&self) -> Result<Vec<TrackedOrder>> {
+        info!("Fetching open orders");
+        self.backend.get_open_orders().await
+    }
+
+    pub async fn get_order_status(&self, order_id: i32) -> Result<TrackedOrder> {
+        info!("Fetching status of order {}", order_id);
+        self.backend.get_order_status(order_id).await
+    }
+
+    /// Orders tagged with `order_ref`, working or completed today
+    pub async fn find_orders_by_ref(&self, order_ref: &str) -> Result<Vec<OrderRefMatch>> {
+        info!("Looking up orders with orderRef {}", order_ref);
+        self.backend.find_orders_by_ref(order_ref).await
+    }
+
+    // Market data operations
+    pub async fn get_market_data(&self, contract: &Contract) -> Result<MarketSnapshot> {
+        info!("Fetching market data for {}", contract.symbol);
+        self.backend.get_market_data(contract).await
+    }
 
-        Ok(bars)
+    pub async fn get_historical_data(
+        &self,
+        contract: &Contract,
+        duration: &str,
+        bar_size: &str,
+        what_to_show: &str,
+    ) -> Result<Vec<BarData>> {
+        info!("Fetching historical data for {}", contract.symbol);
+        self.backend
+            .get_historical_data(contract, duration, bar_size, what_to_show)
+            .await
     }
 }
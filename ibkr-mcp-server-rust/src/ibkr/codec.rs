@@ -0,0 +1,3063 @@
+/// TWS wire codec
+///
+/// Every TWS API message is a 4-byte big-endian length followed by
+/// NUL-terminated text fields. The first exchange is special: the client
+/// sends `API\0` plus its supported version range, and the server answers
+/// with its version and connection time before any regular message.
+///
+/// Field layouts follow the TWS API reference client and depend on the
+/// negotiated server version; [`server_versions`] lists the versions at
+/// which the implemented layouts change.
+///
+/// [`TwsCodec`] is the client side (decodes [`IncomingMessage`], encodes
+/// [`OutgoingMessage`]); [`GatewayCodec`] is the mirror image used by
+/// gateways and test doubles.
+use bytes::{Buf, BufMut, BytesMut};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::warn;
+
+use crate::{
+    error::{IBKRMCPError, Result},
+    models::{AlgoParams, BarData, Contract, OcaType, Order, OrderPreview, SecType},
+};
+
+/// Lowest TWS API client version we negotiate. Older servers still send a
+/// version field in `openOrder` and lack `usePriceMgmtAlgo`.
+pub const MIN_CLIENT_VERSION: i32 = server_versions::PRICE_MGMT_ALGO;
+/// Highest TWS API client version we negotiate; newer servers change the
+/// `placeOrder` and `openOrder` layouts again
+pub const MAX_CLIENT_VERSION: i32 = server_versions::BOND_ISSUERID;
+
+/// Upper bound on a single frame, matching the TWS API limit
+pub const MAX_FRAME_LEN: usize = 0x00FF_FFFF;
+
+const API_PREFIX: &[u8] = b"API\0";
+
+/// Server versions at which the layouts implemented here change, named as
+/// in the TWS API reference client
+pub mod server_versions {
+    pub const PRICE_MGMT_ALGO: i32 = 151;
+    pub const DURATION: i32 = 158;
+    pub const POST_TO_ATS: i32 = 160;
+    pub const AUTO_CANCEL_PARENT: i32 = 162;
+    pub const ADVANCED_ORDER_REJECT: i32 = 166;
+    pub const MANUAL_ORDER_TIME: i32 = 169;
+    pub const PEGBEST_PEGMID_OFFSETS: i32 = 170;
+    pub const BOND_ISSUERID: i32 = 176;
+}
+
+/// Java's `Double.MAX_VALUE`, which TWS sends for unset prices
+const UNSET_DOUBLE: f64 = f64::MAX;
+/// Java's `Integer.MAX_VALUE`, which TWS sends for unset integers
+const UNSET_INTEGER: i32 = i32::MAX;
+
+/// Incoming (server to client) message IDs
+pub mod incoming {
+    pub const TICK_PRICE: i32 = 1;
+    pub const TICK_SIZE: i32 = 2;
+    pub const ORDER_STATUS: i32 = 3;
+    pub const ERR_MSG: i32 = 4;
+    pub const OPEN_ORDER: i32 = 5;
+    pub const NEXT_VALID_ID: i32 = 9;
+    pub const EXECUTION_DATA: i32 = 11;
+    pub const MANAGED_ACCTS: i32 = 15;
+    pub const HISTORICAL_DATA: i32 = 17;
+    pub const CURRENT_TIME: i32 = 49;
+    pub const OPEN_ORDER_END: i32 = 53;
+    pub const EXECUTION_DATA_END: i32 = 55;
+    pub const TICK_SNAPSHOT_END: i32 = 57;
+    pub const COMMISSION_REPORT: i32 = 59;
+    pub const POSITION_DATA: i32 = 61;
+    pub const POSITION_END: i32 = 62;
+    pub const ACCOUNT_SUMMARY: i32 = 63;
+    pub const ACCOUNT_SUMMARY_END: i32 = 64;
+    pub const COMPLETED_ORDER: i32 = 101;
+    pub const COMPLETED_ORDERS_END: i32 = 102;
+}
+
+/// Outgoing (client to server) message IDs
+pub mod outgoing {
+    pub const REQ_MKT_DATA: i32 = 1;
+    pub const CANCEL_MKT_DATA: i32 = 2;
+    pub const PLACE_ORDER: i32 = 3;
+    pub const CANCEL_ORDER: i32 = 4;
+    pub const REQ_OPEN_ORDERS: i32 = 5;
+    pub const REQ_EXECUTIONS: i32 = 7;
+    pub const REQ_IDS: i32 = 8;
+    pub const REQ_ALL_OPEN_ORDERS: i32 = 16;
+    pub const REQ_HISTORICAL_DATA: i32 = 20;
+    pub const REQ_CURRENT_TIME: i32 = 49;
+    pub const REQ_GLOBAL_CANCEL: i32 = 58;
+    pub const REQ_POSITIONS: i32 = 61;
+    pub const REQ_ACCOUNT_SUMMARY: i32 = 62;
+    pub const CANCEL_ACCOUNT_SUMMARY: i32 = 63;
+    pub const CANCEL_POSITIONS: i32 = 64;
+    pub const START_API: i32 = 71;
+    pub const REQ_COMPLETED_ORDERS: i32 = 99;
+}
+
+/// Tick types carried by `tickPrice` / `tickSize`
+pub mod tick {
+    pub const BID_SIZE: i32 = 0;
+    pub const BID: i32 = 1;
+    pub const ASK: i32 = 2;
+    pub const ASK_SIZE: i32 = 3;
+    pub const LAST: i32 = 4;
+    pub const VOLUME: i32 = 8;
+}
+
+/// Order state reported alongside `openOrder`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderState {
+    pub status: String,
+    /// Margin and commission estimates; only filled in for what-if orders
+    pub preview: Box<OrderPreview>,
+}
+
+/// `orderStatus` payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderStatusUpdate {
+    pub order_id: i32,
+    pub status: String,
+    pub filled: f64,
+    pub remaining: f64,
+    pub avg_fill_price: f64,
+    pub perm_id: i32,
+    pub parent_id: i32,
+    pub last_fill_price: f64,
+    pub client_id: i32,
+    pub why_held: String,
+    pub mkt_cap_price: f64,
+}
+
+/// Single fill reported by `execDetails`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub exec_id: String,
+    pub time: String,
+    pub account: String,
+    pub exchange: String,
+    pub side: String,
+    pub shares: f64,
+    pub price: f64,
+    pub perm_id: i32,
+    pub client_id: i32,
+    pub cum_qty: f64,
+    pub avg_price: f64,
+    /// `orderRef` of the order that filled; empty if it had none
+    pub order_ref: String,
+}
+
+/// `commissionReport` payload, keyed by execution ID
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommissionReport {
+    pub exec_id: String,
+    pub commission: f64,
+    pub currency: String,
+    pub realized_pnl: Option<f64>,
+}
+
+/// Messages sent by TWS / IB Gateway
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncomingMessage {
+    /// Handshake reply; always the first message on a connection
+    ServerVersion {
+        version: i32,
+        connection_time: String,
+    },
+    TickPrice {
+        req_id: i32,
+        tick_type: i32,
+        price: f64,
+        size: f64,
+        attr_mask: i32,
+    },
+    TickSize {
+        req_id: i32,
+        tick_type: i32,
+        size: f64,
+    },
+    OrderStatus(OrderStatusUpdate),
+    Error {
+        id: i32,
+        code: i32,
+        message: String,
+    },
+    OpenOrder {
+        order_id: i32,
+        contract: Contract,
+        order: Order,
+        state: OrderState,
+    },
+    NextValidId {
+        order_id: i32,
+    },
+    ExecutionData {
+        req_id: i32,
+        order_id: i32,
+        contract: Contract,
+        execution: Execution,
+    },
+    ManagedAccounts {
+        accounts: Vec<String>,
+    },
+    HistoricalData {
+        req_id: i32,
+        start: String,
+        end: String,
+        bars: Vec<BarData>,
+    },
+    CurrentTime {
+        time: i64,
+    },
+    OpenOrderEnd,
+    ExecutionDataEnd {
+        req_id: i32,
+    },
+    TickSnapshotEnd {
+        req_id: i32,
+    },
+    CommissionReport(CommissionReport),
+    Position {
+        account: String,
+        contract: Contract,
+        position: f64,
+        avg_cost: f64,
+    },
+    PositionEnd,
+    AccountSummary {
+        req_id: i32,
+        account: String,
+        tag: String,
+        value: String,
+        currency: String,
+    },
+    AccountSummaryEnd {
+        req_id: i32,
+    },
+    /// An order that no longer works, as TWS reports it after
+    /// `reqCompletedOrders`; it carries no order ID
+    CompletedOrder {
+        contract: Contract,
+        order: Order,
+        perm_id: i32,
+        status: String,
+    },
+    CompletedOrdersEnd,
+    /// Any message ID this codec does not model, kept as raw fields
+    Unknown {
+        msg_id: i32,
+        fields: Vec<String>,
+    },
+}
+
+/// Messages sent by the API client
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutgoingMessage {
+    /// `API\0` prefix plus the supported client version range
+    Handshake {
+        min_version: i32,
+        max_version: i32,
+    },
+    StartApi {
+        client_id: i32,
+        optional_capabilities: String,
+    },
+    ReqMktData {
+        req_id: i32,
+        contract: Contract,
+        generic_ticks: String,
+        snapshot: bool,
+    },
+    CancelMktData {
+        req_id: i32,
+    },
+    PlaceOrder {
+        order_id: i32,
+        contract: Contract,
+        order: Box<Order>,
+    },
+    CancelOrder {
+        order_id: i32,
+    },
+    ReqOpenOrders,
+    ReqExecutions {
+        req_id: i32,
+    },
+    ReqIds {
+        num_ids: i32,
+    },
+    ReqAllOpenOrders,
+    ReqHistoricalData {
+        req_id: i32,
+        contract: Contract,
+        end_date_time: String,
+        duration: String,
+        bar_size: String,
+        what_to_show: String,
+        use_rth: bool,
+    },
+    ReqCurrentTime,
+    ReqGlobalCancel,
+    ReqPositions,
+    ReqAccountSummary {
+        req_id: i32,
+        group: String,
+        tags: String,
+    },
+    CancelAccountSummary {
+        req_id: i32,
+    },
+    CancelPositions,
+    /// Orders filled or cancelled today; with `api_only`, only those
+    /// placed through the API
+    ReqCompletedOrders {
+        api_only: bool,
+    },
+}
+
+/// Sequential reader over the fields of one message
+struct FieldReader<'a> {
+    fields: &'a [String],
+    pos: usize,
+}
+
+impl<'a> FieldReader<'a> {
+    fn new(fields: &'a [String]) -> Self {
+        Self { fields, pos: 0 }
+    }
+
+    fn next_str(&mut self) -> Result<&'a str> {
+        let field = self.fields.get(self.pos).ok_or_else(|| {
+            IBKRMCPError::Protocol(format!(
+                "Message truncated at field {}: {:?}",
+                self.pos, self.fields
+            ))
+        })?;
+        self.pos += 1;
+        Ok(field)
+    }
+
+    fn next_string(&mut self) -> Result<String> {
+        self.next_str().map(str::to_string)
+    }
+
+    fn next_opt_string(&mut self) -> Result<Option<String>> {
+        let s = self.next_str()?;
+        Ok((!s.is_empty()).then(|| s.to_string()))
+    }
+
+    fn next_parsed<T: std::str::FromStr>(&mut self) -> Result<T> {
+        let pos = self.pos;
+        let s = self.next_str()?;
+        s.parse()
+            .map_err(|_| IBKRMCPError::Protocol(format!("Invalid value {:?} at field {}", s, pos)))
+    }
+
+    fn next_opt_parsed<T: std::str::FromStr>(&mut self) -> Result<Option<T>> {
+        if self.fields.get(self.pos).is_some_and(|s| s.is_empty()) {
+            self.pos += 1;
+            return Ok(None);
+        }
+        self.next_parsed().map(Some)
+    }
+
+    /// A price TWS may leave unset, as an empty field or `Double.MAX_VALUE`
+    fn next_opt_f64(&mut self) -> Result<Option<f64>> {
+        Ok(self
+            .next_opt_parsed::<f64>()?
+            .filter(|v| *v != UNSET_DOUBLE))
+    }
+
+    /// An integer TWS may leave unset, as an empty field or
+    /// `Integer.MAX_VALUE`
+    fn next_opt_i32(&mut self) -> Result<Option<i32>> {
+        Ok(self
+            .next_opt_parsed::<i32>()?
+            .filter(|v| *v != UNSET_INTEGER))
+    }
+
+    /// Element count of a repeated group; TWS sends nothing or -1 for none
+    fn next_count(&mut self) -> Result<usize> {
+        Ok(self.next_opt_i32()?.unwrap_or(0).max(0) as usize)
+    }
+
+    fn next_i32(&mut self) -> Result<i32> {
+        self.next_parsed()
+    }
+
+    fn next_i64(&mut self) -> Result<i64> {
+        self.next_parsed()
+    }
+
+    fn next_f64(&mut self) -> Result<f64> {
+        self.next_parsed()
+    }
+
+    fn next_bool(&mut self) -> Result<bool> {
+        match self.next_str()? {
+            "1" | "true" => Ok(true),
+            "0" | "false" | "" => Ok(false),
+            other => Err(IBKRMCPError::Protocol(format!("Invalid bool {:?}", other))),
+        }
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        for _ in 0..n {
+            self.next_str()?;
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> usize {
+        self.fields.len().saturating_sub(self.pos)
+    }
+}
+
+/// Builder for the fields of one message
+#[derive(Default)]
+struct FieldWriter {
+    fields: Vec<String>,
+}
+
+impl FieldWriter {
+    fn push(&mut self, value: impl ToString) -> &mut Self {
+        self.fields.push(value.to_string());
+        self
+    }
+
+    fn push_opt<T: ToString>(&mut self, value: Option<T>) -> &mut Self {
+        self.fields
+            .push(value.map(|v| v.to_string()).unwrap_or_default());
+        self
+    }
+
+    fn push_bool(&mut self, value: bool) -> &mut Self {
+        self.push(if value { "1" } else { "0" })
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.fields)
+    }
+}
+
+/// Contract of market data and historical data requests: conId, symbol,
+/// secType, lastTradeDateOrContractMonth, strike, right, multiplier,
+/// exchange, primaryExchange, currency, localSymbol, tradingClass
+fn write_request_contract(w: &mut FieldWriter, contract: &Contract) {
+    write_contract_head(w, contract);
+    w.push(&contract.exchange)
+        .push("") // primaryExchange
+        .push(&contract.currency)
+        .push_opt(contract.local_symbol.as_ref())
+        .push(""); // tradingClass
+}
+
+fn read_request_contract(r: &mut FieldReader) -> Result<Contract> {
+    let mut contract = read_contract_head(r)?;
+    contract.exchange = r.next_string()?;
+    r.skip(1)?; // primaryExchange
+    contract.currency = r.next_string()?;
+    contract.local_symbol = r.next_opt_string()?;
+    r.skip(1)?; // tradingClass
+    Ok(contract)
+}
+
+/// Contract of `placeOrder`: the request layout plus secIdType and secId
+fn write_order_contract(w: &mut FieldWriter, contract: &Contract) {
+    write_request_contract(w, contract);
+    w.push("").push(""); // secIdType, secId
+}
+
+fn read_order_contract(r: &mut FieldReader) -> Result<Contract> {
+    let contract = read_request_contract(r)?;
+    r.skip(2)?; // secIdType, secId
+    Ok(contract)
+}
+
+/// Contract as TWS reports it in `openOrder`, `execDetails` and `position`:
+/// the request layout without primaryExchange
+fn write_report_contract(w: &mut FieldWriter, contract: &Contract) {
+    write_contract_head(w, contract);
+    w.push(&contract.exchange)
+        .push(&contract.currency)
+        .push_opt(contract.local_symbol.as_ref())
+        .push(""); // tradingClass
+}
+
+fn read_report_contract(r: &mut FieldReader) -> Result<Contract> {
+    let mut contract = read_contract_head(r)?;
+    contract.exchange = r.next_string()?;
+    contract.currency = r.next_string()?;
+    contract.local_symbol = r.next_opt_string()?;
+    r.skip(1)?; // tradingClass
+    Ok(contract)
+}
+
+// conId, symbol, secType, lastTradeDateOrContractMonth, strike, right and
+// multiplier, which every contract layout starts with
+fn write_contract_head(w: &mut FieldWriter, contract: &Contract) {
+    w.push(contract.con_id.unwrap_or(0))
+        .push(&contract.symbol)
+        .push(contract.sec_type.as_str())
+        .push_opt(
+            contract
+                .last_trade_date
+                .as_ref()
+                .or(contract.expiry.as_ref()),
+        )
+        .push(contract.strike.unwrap_or(0.0))
+        .push_opt(contract.right.as_ref())
+        .push_opt(contract.multiplier);
+}
+
+fn read_contract_head(r: &mut FieldReader) -> Result<Contract> {
+    let con_id: i32 = r.next_i32()?;
+    let symbol = r.next_string()?;
+    let sec_type: SecType = r.next_str()?.parse()?;
+    let last_trade_date = r.next_opt_string()?;
+    // Reports fill in 0 and "?" for contracts without a strike or right
+    let strike = r.next_opt_f64()?.filter(|s| *s != 0.0);
+    let right = r.next_opt_string()?.filter(|r| r != "?" && r != "0");
+    // Fractional multipliers are not modeled
+    let multiplier = r
+        .next_opt_parsed::<f64>()?
+        .filter(|m| m.fract() == 0.0 && *m >= 1.0 && *m <= i32::MAX as f64)
+        .map(|m| m as i32);
+
+    // Options carry an expiry, everything else a last trade date
+    let (expiry, last_trade_date) = match sec_type {
+        SecType::Option | SecType::Warrant => (last_trade_date, None),
+        _ => (None, last_trade_date),
+    };
+
+    Ok(Contract {
+        symbol,
+        sec_type,
+        con_id: (con_id != 0).then_some(con_id),
+        strike,
+        right,
+        expiry,
+        last_trade_date,
+        multiplier,
+        ..Default::default()
+    })
+}
+
+/// `placeOrder` fields from action to the end of the message. Attributes
+/// [`Order`] does not model are sent with the reference client's defaults.
+fn write_place_order(w: &mut FieldWriter, contract: &Contract, order: &Order, version: i32) {
+    use server_versions::*;
+
+    w.push(order.action.as_str())
+        .push(order.total_quantity)
+        .push(order.order_type.as_str())
+        .push_opt(order.lmt_price)
+        .push_opt(order.aux_price)
+        .push(order.time_in_force.as_str())
+        .push_opt(order.oca_group.as_ref())
+        .push_opt(order.account.as_ref())
+        .push("") // openClose
+        .push(0) // origin: customer
+        .push_opt(order.order_ref.as_ref())
+        .push_bool(order.transmit)
+        .push(order.parent_id.unwrap_or(0))
+        .push_bool(false) // blockOrder
+        .push_bool(false) // sweepToFill
+        .push(0) // displaySize
+        .push(0) // triggerMethod
+        .push_bool(order.outside_rth)
+        .push_bool(order.hidden)
+        .push("") // sharesAllocation, deprecated
+        .push(0) // discretionaryAmt
+        .push_opt(order.good_after_time.as_ref())
+        .push_opt(order.good_till_date.as_ref())
+        .push("") // faGroup
+        .push("") // faMethod
+        .push("") // faPercentage
+        .push("") // faProfile
+        .push("") // modelCode
+        .push(0) // shortSaleSlot
+        .push("") // designatedLocation
+        .push(-1) // exemptCode
+        .push(order.oca_type.map(|t| t.code()).unwrap_or(0))
+        .push("") // rule80A
+        .push("") // settlingFirm
+        .push_bool(false) // allOrNone
+        .push("") // minQty
+        .push("") // percentOffset
+        .push_bool(false) // eTradeOnly
+        .push_bool(false) // firmQuoteOnly
+        .push("") // nbboPriceCap
+        .push(0) // auctionStrategy
+        .push("") // startingPrice
+        .push("") // stockRefPrice
+        .push("") // delta
+        .push("") // stockRangeLower
+        .push("") // stockRangeUpper
+        .push_bool(false) // overridePercentageConstraints
+        .push("") // volatility
+        .push("") // volatilityType
+        .push("") // deltaNeutralOrderType
+        .push("") // deltaNeutralAuxPrice
+        .push_bool(false) // continuousUpdate
+        .push("") // referencePriceType
+        .push_opt(order.trail_stop_price)
+        .push_opt(order.trailing_percent)
+        .push("") // scaleInitLevelSize
+        .push("") // scaleSubsLevelSize
+        .push("") // scalePriceIncrement
+        .push("") // scaleTable
+        .push("") // activeStartTime
+        .push("") // activeStopTime
+        .push("") // hedgeType
+        .push_bool(false) // optOutSmartRouting
+        .push("") // clearingAccount
+        .push("") // clearingIntent
+        .push_bool(false) // notHeld
+        .push_bool(false); // deltaNeutralContract
+    write_algo(w, order);
+    w.push("") // algoId
+        .push_bool(order.what_if)
+        .push("") // orderMiscOptions
+        .push_bool(false) // solicited
+        .push_bool(false) // randomizeSize
+        .push_bool(false) // randomizePrice
+        .push(0) // conditions
+        .push("") // adjustedOrderType
+        .push("") // triggerPrice
+        .push_opt(order.lmt_price_offset)
+        .push("") // adjustedStopPrice
+        .push("") // adjustedStopLimitPrice
+        .push("") // adjustedTrailingAmount
+        .push(0) // adjustableTrailingUnit
+        .push("") // extOperator
+        .push("") // softDollarTier name
+        .push("") // softDollarTier value
+        .push("") // cashQty
+        .push("") // mifid2DecisionMaker
+        .push("") // mifid2DecisionAlgo
+        .push("") // mifid2ExecutionTrader
+        .push("") // mifid2ExecutionAlgo
+        .push_bool(false) // dontUseAutoPriceForHedge
+        .push_bool(false) // isOmsContainer
+        .push_bool(false) // discretionaryUpToLimitPrice
+        .push(""); // usePriceMgmtAlgo
+    if version >= DURATION {
+        w.push("");
+    }
+    if version >= POST_TO_ATS {
+        w.push("");
+    }
+    if version >= AUTO_CANCEL_PARENT {
+        w.push_bool(false);
+    }
+    if version >= ADVANCED_ORDER_REJECT {
+        w.push(""); // advancedErrorOverride
+    }
+    if version >= MANUAL_ORDER_TIME {
+        w.push(""); // manualOrderTime
+    }
+    if version >= PEGBEST_PEGMID_OFFSETS && contract.exchange == "IBKRATS" {
+        w.push(""); // minTradeQty
+    }
+}
+
+// Counterpart of `write_place_order`, for gateways. It stops after the last
+// attribute `Order` models.
+fn read_place_order(r: &mut FieldReader) -> Result<Order> {
+    let action = r.next_str()?.parse()?;
+    let total_quantity = r.next_f64()?;
+    let order_type = r.next_str()?.parse()?;
+
+    let mut order = Order::new(action, total_quantity, order_type);
+    order.lmt_price = r.next_opt_f64()?;
+    order.aux_price = r.next_opt_f64()?;
+    order.time_in_force = r.next_str()?.parse()?;
+    order.oca_group = r.next_opt_string()?;
+    order.account = r.next_opt_string()?;
+    r.skip(2)?; // openClose, origin
+    order.order_ref = r.next_opt_string()?;
+    order.transmit = r.next_bool()?;
+    order.parent_id = Some(r.next_i32()?).filter(|&id| id != 0);
+    r.skip(4)?; // blockOrder, sweepToFill, displaySize, triggerMethod
+    order.outside_rth = r.next_bool()?;
+    order.hidden = r.next_bool()?;
+    r.skip(2)?; // sharesAllocation, discretionaryAmt
+    order.good_after_time = r.next_opt_string()?;
+    order.good_till_date = r.next_opt_string()?;
+    // FA allocation, modelCode and short sale fields
+    r.skip(8)?;
+    order.oca_type = OcaType::from_code(r.next_i32()?);
+    // rule80A through overridePercentageConstraints
+    r.skip(15)?;
+    skip_vol_order_params(r, true)?;
+    order.trail_stop_price = r.next_opt_f64()?;
+    order.trailing_percent = r.next_opt_f64()?;
+    skip_scale_order_params(r)?;
+    r.skip(3)?; // scaleTable, activeStartTime, activeStopTime
+    skip_hedge_params(r)?;
+    r.skip(4)?; // optOutSmartRouting, clearingAccount, clearingIntent, notHeld
+    skip_delta_neutral_contract(r)?;
+    read_algo(r, &mut order)?;
+    r.skip(1)?; // algoId
+    order.what_if = r.next_bool()?;
+    r.skip(4)?; // orderMiscOptions, solicited, randomizeSize, randomizePrice
+    skip_conditions(r)?;
+    r.skip(2)?; // adjustedOrderType, triggerPrice
+    order.lmt_price_offset = r.next_opt_f64()?;
+    Ok(order)
+}
+
+/// `openOrder` after the message ID: orderId, the reported contract, the
+/// order and its state
+fn write_open_order(
+    w: &mut FieldWriter,
+    order_id: i32,
+    contract: &Contract,
+    order: &Order,
+    state: &OrderState,
+    version: i32,
+) {
+    use server_versions::*;
+
+    w.push(order_id);
+    write_report_contract(w, contract);
+    w.push(order.action.as_str())
+        .push(order.total_quantity)
+        .push(order.order_type.as_str())
+        .push_opt(order.lmt_price)
+        .push_opt(order.aux_price)
+        .push(order.time_in_force.as_str())
+        .push_opt(order.oca_group.as_ref())
+        .push_opt(order.account.as_ref())
+        .push("") // openClose
+        .push(0) // origin
+        .push_opt(order.order_ref.as_ref())
+        .push(order.client_id.unwrap_or(0))
+        .push(0) // permId
+        .push_bool(order.outside_rth)
+        .push_bool(order.hidden)
+        .push(0) // discretionaryAmt
+        .push_opt(order.good_after_time.as_ref())
+        .push("") // sharesAllocation
+        .push("") // faGroup
+        .push("") // faMethod
+        .push("") // faPercentage
+        .push("") // faProfile
+        .push("") // modelCode
+        .push_opt(order.good_till_date.as_ref())
+        .push("") // rule80A
+        .push("") // percentOffset
+        .push("") // settlingFirm
+        .push(0) // shortSaleSlot
+        .push("") // designatedLocation
+        .push(-1) // exemptCode
+        .push(0) // auctionStrategy
+        .push("") // startingPrice
+        .push("") // stockRefPrice
+        .push("") // delta
+        .push("") // stockRangeLower
+        .push("") // stockRangeUpper
+        .push("") // displaySize
+        .push_bool(false) // blockOrder
+        .push_bool(false) // sweepToFill
+        .push_bool(false) // allOrNone
+        .push("") // minQty
+        .push(order.oca_type.map(|t| t.code()).unwrap_or(0))
+        .push_bool(false) // eTradeOnly
+        .push_bool(false) // firmQuoteOnly
+        .push("") // nbboPriceCap
+        .push(order.parent_id.unwrap_or(0))
+        .push(0) // triggerMethod
+        .push("") // volatility
+        .push("") // volatilityType
+        .push("") // deltaNeutralOrderType
+        .push("") // deltaNeutralAuxPrice
+        .push_bool(false) // continuousUpdate
+        .push("") // referencePriceType
+        .push_opt(order.trail_stop_price)
+        .push_opt(order.trailing_percent)
+        .push("") // basisPoints
+        .push("") // basisPointsType
+        .push("") // comboLegsDescrip
+        .push(0) // comboLegs
+        .push(0) // orderComboLegs
+        .push(0) // smartComboRoutingParams
+        .push("") // scaleInitLevelSize
+        .push("") // scaleSubsLevelSize
+        .push("") // scalePriceIncrement
+        .push("") // hedgeType
+        .push_bool(false) // optOutSmartRouting
+        .push("") // clearingAccount
+        .push("") // clearingIntent
+        .push_bool(false) // notHeld
+        .push_bool(false); // deltaNeutralContract
+    write_algo(w, order);
+    w.push_bool(false) // solicited
+        .push_bool(order.what_if);
+    write_order_state(w, state);
+    w.push_bool(false) // randomizeSize
+        .push_bool(false) // randomizePrice
+        .push(0) // conditions
+        .push("") // adjustedOrderType
+        .push("") // triggerPrice
+        .push_opt(order.trail_stop_price)
+        .push_opt(order.lmt_price_offset)
+        .push("") // adjustedStopPrice
+        .push("") // adjustedStopLimitPrice
+        .push("") // adjustedTrailingAmount
+        .push(0) // adjustableTrailingUnit
+        .push("") // softDollarTier name
+        .push("") // softDollarTier value
+        .push("") // softDollarTier displayName
+        .push("") // cashQty
+        .push_bool(false) // dontUseAutoPriceForHedge
+        .push_bool(false) // isOmsContainer
+        .push_bool(false) // discretionaryUpToLimitPrice
+        .push_bool(false); // usePriceMgmtAlgo
+    if version >= DURATION {
+        w.push("");
+    }
+    if version >= POST_TO_ATS {
+        w.push("");
+    }
+    if version >= AUTO_CANCEL_PARENT {
+        w.push_bool(false);
+    }
+    if version >= PEGBEST_PEGMID_OFFSETS {
+        // minTradeQty, minCompeteSize, competeAgainstBestOffset,
+        // midOffsetAtWhole, midOffsetAtHalf
+        w.push("").push("").push("").push("").push("");
+    }
+}
+
+// Counterpart of `write_open_order`. It stops after the last attribute
+// `Order` models; the fields after it do not depend on earlier values.
+fn read_open_order(r: &mut FieldReader) -> Result<IncomingMessage> {
+    let order_id = r.next_i32()?;
+    let contract = read_report_contract(r)?;
+    let mut order = read_order_head(r)?;
+    order.client_id = Some(r.next_i32()?);
+    r.skip(1)?; // permId
+    order.outside_rth = r.next_bool()?;
+    order.hidden = r.next_bool()?;
+    r.skip(1)?; // discretionaryAmt
+    order.good_after_time = r.next_opt_string()?;
+    // sharesAllocation, FA allocation and modelCode
+    r.skip(6)?;
+    order.good_till_date = r.next_opt_string()?;
+    // rule80A through minQty
+    r.skip(17)?;
+    order.oca_type = OcaType::from_code(r.next_i32()?);
+    r.skip(3)?; // eTradeOnly, firmQuoteOnly, nbboPriceCap
+    order.parent_id = Some(r.next_i32()?).filter(|&id| id != 0);
+    r.skip(1)?; // triggerMethod
+    skip_vol_order_params(r, true)?;
+    order.trail_stop_price = r.next_opt_f64()?;
+    order.trailing_percent = r.next_opt_f64()?;
+    r.skip(2)?; // basisPoints, basisPointsType
+    skip_combo_legs(r)?;
+    skip_scale_order_params(r)?;
+    skip_hedge_params(r)?;
+    r.skip(4)?; // optOutSmartRouting, clearingAccount, clearingIntent, notHeld
+    skip_delta_neutral_contract(r)?;
+    read_algo(r, &mut order)?;
+    r.skip(1)?; // solicited
+    order.what_if = r.next_bool()?;
+    let state = read_order_state(r)?;
+    r.skip(2)?; // randomizeSize, randomizePrice
+    skip_conditions(r)?;
+    r.skip(2)?; // adjustedOrderType, triggerPrice
+                // trailStopPrice again, then lmtPriceOffset
+    order.trail_stop_price = order.trail_stop_price.or(r.next_opt_f64()?);
+    order.lmt_price_offset = r.next_opt_f64()?;
+
+    Ok(IncomingMessage::OpenOrder {
+        order_id,
+        contract,
+        order,
+        state,
+    })
+}
+
+fn write_completed_order(
+    w: &mut FieldWriter,
+    contract: &Contract,
+    order: &Order,
+    perm_id: i32,
+    status: &str,
+    version: i32,
+) {
+    write_report_contract(w, contract);
+    w.push(order.action.as_str())
+        .push(order.total_quantity)
+        .push(order.order_type.as_str())
+        .push_opt(order.lmt_price)
+        .push_opt(order.aux_price)
+        .push(order.time_in_force.as_str())
+        .push_opt(order.oca_group.as_ref())
+        .push_opt(order.account.as_ref())
+        .push("") // openClose
+        .push(0) // origin
+        .push_opt(order.order_ref.as_ref())
+        .push(perm_id)
+        .push_bool(order.outside_rth)
+        .push_bool(order.hidden)
+        .push(0) // discretionaryAmt
+        .push_opt(order.good_after_time.as_ref())
+        .push("") // faGroup
+        .push("") // faMethod
+        .push("") // faPercentage
+        .push("") // faProfile
+        .push("") // modelCode
+        .push_opt(order.good_till_date.as_ref())
+        .push("") // rule80A
+        .push("") // percentOffset
+        .push("") // settlingFirm
+        .push(0) // shortSaleSlot
+        .push("") // designatedLocation
+        .push(-1) // exemptCode
+        .push("") // startingPrice
+        .push("") // stockRefPrice
+        .push("") // delta
+        .push("") // stockRangeLower
+        .push("") // stockRangeUpper
+        .push("") // displaySize
+        .push_bool(false) // sweepToFill
+        .push_bool(false) // allOrNone
+        .push("") // minQty
+        .push(order.oca_type.map(|t| t.code()).unwrap_or(0))
+        .push(0) // triggerMethod
+        .push("") // volatility
+        .push("") // volatilityType
+        .push("") // deltaNeutralOrderType
+        .push("") // deltaNeutralAuxPrice
+        .push_bool(false) // continuousUpdate
+        .push("") // referencePriceType
+        .push_opt(order.trail_stop_price)
+        .push_opt(order.trailing_percent)
+        .push("") // comboLegsDescrip
+        .push(0) // comboLegs
+        .push(0) // orderComboLegs
+        .push(0) // smartComboRoutingParams
+        .push("") // scaleInitLevelSize
+        .push("") // scaleSubsLevelSize
+        .push("") // scalePriceIncrement
+        .push("") // hedgeType
+        .push("") // clearingAccount
+        .push("") // clearingIntent
+        .push_bool(false) // notHeld
+        .push_bool(false); // deltaNeutralContract
+    write_algo(w, order);
+    w.push_bool(false) // solicited
+        .push(status)
+        .push_bool(false) // randomizeSize
+        .push_bool(false) // randomizePrice
+        .push(0) // conditions
+        .push_opt(order.trail_stop_price)
+        .push_opt(order.lmt_price_offset)
+        .push("") // cashQty
+        .push_bool(false) // dontUseAutoPriceForHedge
+        .push_bool(false) // isOmsContainer
+        .push("") // autoCancelDate
+        .push("") // filledQuantity
+        .push(0) // refFuturesConId
+        .push_bool(false) // autoCancelParent
+        .push("") // shareholder
+        .push_bool(false) // imbalanceOnly
+        .push_bool(false) // routeMarketableToBbo
+        .push(0) // parentPermId
+        .push("") // completedTime
+        .push(""); // completedStatus
+    if version >= server_versions::PEGBEST_PEGMID_OFFSETS {
+        // minTradeQty, minCompeteSize, competeAgainstBestOffset,
+        // midOffsetAtWhole, midOffsetAtHalf
+        w.push("").push("").push("").push("").push("");
+    }
+}
+
+// Counterpart of `write_completed_order`. Completed orders share the head
+// of `openOrder` but leave out the fields only working orders have; like
+// `read_open_order` it stops after the last attribute `Order` models.
+fn read_completed_order(r: &mut FieldReader) -> Result<IncomingMessage> {
+    let contract = read_report_contract(r)?;
+    let mut order = read_order_head(r)?;
+    let perm_id = r.next_i32()?;
+    order.outside_rth = r.next_bool()?;
+    order.hidden = r.next_bool()?;
+    r.skip(1)?; // discretionaryAmt
+    order.good_after_time = r.next_opt_string()?;
+    r.skip(5)?; // FA allocation and modelCode
+    order.good_till_date = r.next_opt_string()?;
+    // rule80A through minQty
+    r.skip(15)?;
+    order.oca_type = OcaType::from_code(r.next_i32()?);
+    r.skip(1)?; // triggerMethod
+    skip_vol_order_params(r, false)?;
+    order.trail_stop_price = r.next_opt_f64()?;
+    order.trailing_percent = r.next_opt_f64()?;
+    skip_combo_legs(r)?;
+    skip_scale_order_params(r)?;
+    skip_hedge_params(r)?;
+    r.skip(3)?; // clearingAccount, clearingIntent, notHeld
+    skip_delta_neutral_contract(r)?;
+    read_algo(r, &mut order)?;
+    r.skip(1)?; // solicited
+    let status = r.next_string()?;
+    r.skip(2)?; // randomizeSize, randomizePrice
+    skip_conditions(r)?;
+    // trailStopPrice again, then lmtPriceOffset
+    order.trail_stop_price = order.trail_stop_price.or(r.next_opt_f64()?);
+    order.lmt_price_offset = r.next_opt_f64()?;
+
+    Ok(IncomingMessage::CompletedOrder {
+        contract,
+        order,
+        perm_id,
+        status,
+    })
+}
+
+// Action through orderRef, as reported for existing orders
+fn read_order_head(r: &mut FieldReader) -> Result<Order> {
+    let action = r.next_str()?.parse()?;
+    let total_quantity = r.next_f64()?;
+    let order_type = r.next_str()?.parse()?;
+
+    let mut order = Order::new(action, total_quantity, order_type);
+    // Prices the order type does not use are reported as 0
+    order.lmt_price = r.next_opt_f64()?.filter(|p| *p != 0.0);
+    order.aux_price = r.next_opt_f64()?.filter(|p| *p != 0.0);
+    order.time_in_force = r.next_str()?.parse()?;
+    order.oca_group = r.next_opt_string()?;
+    order.account = r.next_opt_string()?;
+    r.skip(2)?; // openClose, origin
+    order.order_ref = r.next_opt_string()?;
+    Ok(order)
+}
+
+fn write_algo(w: &mut FieldWriter, order: &Order) {
+    w.push(order.algo_strategy.map(|s| s.as_str()).unwrap_or(""));
+    if order.algo_strategy.is_some() {
+        let tags = order.algo_params.to_tag_values();
+        w.push(tags.len());
+        for (tag, value) in tags {
+            w.push(tag).push(value);
+        }
+    }
+}
+
+fn read_algo(r: &mut FieldReader, order: &mut Order) -> Result<()> {
+    order.algo_strategy = r.next_opt_parsed()?;
+    if order.algo_strategy.is_some() {
+        let count = r.next_count()?;
+        let mut tags = Vec::new();
+        for _ in 0..count {
+            tags.push((r.next_string()?, r.next_string()?));
+        }
+        order.algo_params = AlgoParams::from_tag_values(&tags)?;
+    }
+    Ok(())
+}
+
+// volatility through referencePriceType. Delta-neutral orders add a
+// conId and short sale fields, plus settling firm, clearing fields and
+// openClose everywhere except in completed orders.
+fn skip_vol_order_params(r: &mut FieldReader, with_clearing: bool) -> Result<()> {
+    r.skip(2)?; // volatility, volatilityType
+    let delta_neutral_order_type = r.next_str()?;
+    r.skip(1)?; // deltaNeutralAuxPrice
+    if !delta_neutral_order_type.is_empty() && delta_neutral_order_type != "None" {
+        r.skip(if with_clearing { 8 } else { 4 })?;
+    }
+    r.skip(2) // continuousUpdate, referencePriceType
+}
+
+fn skip_combo_legs(r: &mut FieldReader) -> Result<()> {
+    r.skip(1)?; // comboLegsDescrip
+                // conId, ratio, action, exchange, openClose, shortSaleSlot,
+                // designatedLocation, exemptCode
+    let legs = r.next_count()?;
+    r.skip(legs * 8)?;
+    let leg_prices = r.next_count()?;
+    r.skip(leg_prices)?;
+    let routing_params = r.next_count()?;
+    r.skip(routing_params * 2)
+}
+
+fn skip_scale_order_params(r: &mut FieldReader) -> Result<()> {
+    r.skip(2)?; // scaleInitLevelSize, scaleSubsLevelSize
+    if r.next_opt_f64()?.is_some_and(|increment| increment > 0.0) {
+        // scalePriceAdjustValue, scalePriceAdjustInterval,
+        // scaleProfitOffset, scaleAutoReset, scaleInitPosition,
+        // scaleInitFillQty, scaleRandomPercent
+        r.skip(7)?;
+    }
+    Ok(())
+}
+
+fn skip_hedge_params(r: &mut FieldReader) -> Result<()> {
+    if !r.next_str()?.is_empty() {
+        r.skip(1)?; // hedgeParam
+    }
+    Ok(())
+}
+
+fn skip_delta_neutral_contract(r: &mut FieldReader) -> Result<()> {
+    if r.next_bool()? {
+        r.skip(3)?; // conId, delta, price
+    }
+    Ok(())
+}
+
+// Order conditions are not modeled; each is skipped by the field count of
+// its type, which follows a conjunction field
+fn skip_conditions(r: &mut FieldReader) -> Result<()> {
+    let count = r.next_count()?;
+    if count == 0 {
+        return Ok(());
+    }
+    for _ in 0..count {
+        let fields = match r.next_i32()? {
+            // Price: isMore, price, conId, exchange, triggerMethod
+            1 => 5,
+            // Time and margin: isMore, value
+            3 | 4 => 2,
+            // Execution: secType, exchange, symbol
+            5 => 3,
+            // Volume and percent change: isMore, value, conId, exchange
+            6 | 7 => 4,
+            other => {
+                return Err(IBKRMCPError::Protocol(format!(
+                    "Unknown order condition type {}",
+                    other
+                )))
+            }
+        };
+        r.skip(1 + fields)?;
+    }
+    r.skip(2) // conditionsIgnoreRth, conditionsCancelOrder
+}
+
+fn write_order_state(w: &mut FieldWriter, state: &OrderState) {
+    let p = &state.preview;
+    w.push(&state.status);
+    for value in [
+        p.init_margin_before,
+        p.maint_margin_before,
+        p.equity_with_loan_before,
+        p.init_margin_change,
+        p.maint_margin_change,
+        p.equity_with_loan_change,
+        p.init_margin_after,
+        p.maint_margin_after,
+        p.equity_with_loan_after,
+        p.commission,
+        p.min_commission,
+        p.max_commission,
+    ] {
+        w.push_opt(value);
+    }
+    w.push_opt(p.commission_currency.as_ref())
+        .push_opt(p.warning_text.as_ref());
+}
+
+fn read_order_state(r: &mut FieldReader) -> Result<OrderState> {
+    let status = r.next_string()?;
+    // TWS marks values it did not compute with f64::MAX
+    let mut amount =
+        || -> Result<Option<f64>> { Ok(r.next_opt_parsed::<f64>()?.filter(|v| *v != f64::MAX)) };
+    let preview = OrderPreview {
+        init_margin_before: amount()?,
+        maint_margin_before: amount()?,
+        equity_with_loan_before: amount()?,
+        init_margin_change: amount()?,
+        maint_margin_change: amount()?,
+        equity_with_loan_change: amount()?,
+        init_margin_after: amount()?,
+        maint_margin_after: amount()?,
+        equity_with_loan_after: amount()?,
+        commission: amount()?,
+        min_commission: amount()?,
+        max_commission: amount()?,
+        commission_currency: r.next_opt_string()?,
+        warning_text: r.next_opt_string()?,
+    };
+    Ok(OrderState {
+        status,
+        preview: Box::new(preview),
+    })
+}
+
+impl IncomingMessage {
+    /// Serialize into message fields (without the frame header) in the
+    /// layout of server `version`
+    pub fn encode_fields(&self, version: i32) -> Vec<String> {
+        use incoming::*;
+
+        let mut w = FieldWriter::default();
+        match self {
+            IncomingMessage::ServerVersion {
+                version,
+                connection_time,
+            } => {
+                w.push(version).push(connection_time);
+            }
+            IncomingMessage::TickPrice {
+                req_id,
+                tick_type,
+                price,
+                size,
+                attr_mask,
+            } => {
+                w.push(TICK_PRICE)
+                    .push(6)
+                    .push(req_id)
+                    .push(tick_type)
+                    .push(price)
+                    .push(size)
+                    .push(attr_mask);
+            }
+            IncomingMessage::TickSize {
+                req_id,
+                tick_type,
+                size,
+            } => {
+                w.push(TICK_SIZE)
+                    .push(6)
+                    .push(req_id)
+                    .push(tick_type)
+                    .push(size);
+            }
+            IncomingMessage::OrderStatus(s) => {
+                w.push(ORDER_STATUS)
+                    .push(s.order_id)
+                    .push(&s.status)
+                    .push(s.filled)
+                    .push(s.remaining)
+                    .push(s.avg_fill_price)
+                    .push(s.perm_id)
+                    .push(s.parent_id)
+                    .push(s.last_fill_price)
+                    .push(s.client_id)
+                    .push(&s.why_held)
+                    .push(s.mkt_cap_price);
+            }
+            IncomingMessage::Error { id, code, message } => {
+                w.push(ERR_MSG).push(2).push(id).push(code).push(message);
+                if version >= server_versions::ADVANCED_ORDER_REJECT {
+                    w.push(""); // advancedOrderRejectJson
+                }
+            }
+            IncomingMessage::OpenOrder {
+                order_id,
+                contract,
+                order,
+                state,
+            } => {
+                w.push(OPEN_ORDER);
+                write_open_order(&mut w, *order_id, contract, order, state, version);
+            }
+            IncomingMessage::NextValidId { order_id } => {
+                w.push(NEXT_VALID_ID).push(1).push(order_id);
+            }
+            IncomingMessage::ExecutionData {
+                req_id,
+                order_id,
+                contract,
+                execution: e,
+            } => {
+                w.push(EXECUTION_DATA).push(req_id).push(order_id);
+                write_report_contract(&mut w, contract);
+                w.push(&e.exec_id)
+                    .push(&e.time)
+                    .push(&e.account)
+                    .push(&e.exchange)
+                    .push(&e.side)
+                    .push(e.shares)
+                    .push(e.price)
+                    .push(e.perm_id)
+                    .push(e.client_id)
+                    .push(0) // liquidation
+                    .push(e.cum_qty)
+                    .push(e.avg_price)
+                    .push(&e.order_ref)
+                    .push("") // evRule
+                    .push("") // evMultiplier
+                    .push("") // modelCode
+                    .push(0); // lastLiquidity
+            }
+            IncomingMessage::ManagedAccounts { accounts } => {
+                w.push(MANAGED_ACCTS).push(1).push(accounts.join(","));
+            }
+            IncomingMessage::HistoricalData {
+                req_id,
+                start,
+                end,
+                bars,
+            } => {
+                w.push(HISTORICAL_DATA)
+                    .push(req_id)
+                    .push(start)
+                    .push(end)
+                    .push(bars.len());
+                for bar in bars {
+                    w.push(bar.date.timestamp())
+                        .push(bar.open)
+                        .push(bar.high)
+                        .push(bar.low)
+                        .push(bar.close)
+                        .push(bar.volume)
+                        .push_opt(bar.wap)
+                        .push_opt(bar.count);
+                }
+            }
+            IncomingMessage::CurrentTime { time } => {
+                w.push(CURRENT_TIME).push(1).push(time);
+            }
+            IncomingMessage::OpenOrderEnd => {
+                w.push(OPEN_ORDER_END).push(1);
+            }
+            IncomingMessage::ExecutionDataEnd { req_id } => {
+                w.push(EXECUTION_DATA_END).push(1).push(req_id);
+            }
+            IncomingMessage::TickSnapshotEnd { req_id } => {
+                w.push(TICK_SNAPSHOT_END).push(1).push(req_id);
+            }
+            IncomingMessage::CommissionReport(c) => {
+                w.push(COMMISSION_REPORT)
+                    .push(1)
+                    .push(&c.exec_id)
+                    .push(c.commission)
+                    .push(&c.currency)
+                    .push_opt(c.realized_pnl)
+                    .push("")
+                    .push("");
+            }
+            IncomingMessage::Position {
+                account,
+                contract,
+                position,
+                avg_cost,
+            } => {
+                w.push(POSITION_DATA).push(3).push(account);
+                write_report_contract(&mut w, contract);
+                w.push(position).push(avg_cost);
+            }
+            IncomingMessage::PositionEnd => {
+                w.push(POSITION_END).push(1);
+            }
+            IncomingMessage::AccountSummary {
+                req_id,
+                account,
+                tag,
+                value,
+                currency,
+            } => {
+                w.push(ACCOUNT_SUMMARY)
+                    .push(1)
+                    .push(req_id)
+                    .push(account)
+                    .push(tag)
+                    .push(value)
+                    .push(currency);
+            }
+            IncomingMessage::AccountSummaryEnd { req_id } => {
+                w.push(ACCOUNT_SUMMARY_END).push(1).push(req_id);
+            }
+            IncomingMessage::CompletedOrder {
+                contract,
+                order,
+                perm_id,
+                status,
+            } => {
+                w.push(COMPLETED_ORDER);
+                write_completed_order(&mut w, contract, order, *perm_id, status, version);
+            }
+            IncomingMessage::CompletedOrdersEnd => {
+                w.push(COMPLETED_ORDERS_END);
+            }
+            IncomingMessage::Unknown { msg_id, fields } => {
+                w.push(msg_id);
+                for field in fields {
+                    w.push(field);
+                }
+            }
+        }
+        w.finish()
+    }
+
+    /// Parse a regular (post-handshake) message from its fields
+    pub fn decode_fields(fields: &[String]) -> Result<Self> {
+        use incoming::*;
+
+        let mut r = FieldReader::new(fields);
+        let msg_id = r.next_i32()?;
+
+        let message = match msg_id {
+            TICK_PRICE => {
+                r.skip(1)?;
+                IncomingMessage::TickPrice {
+                    req_id: r.next_i32()?,
+                    tick_type: r.next_i32()?,
+                    price: r.next_f64()?,
+                    size: r.next_f64()?,
+                    attr_mask: r.next_i32()?,
+                }
+            }
+            TICK_SIZE => {
+                r.skip(1)?;
+                IncomingMessage::TickSize {
+                    req_id: r.next_i32()?,
+                    tick_type: r.next_i32()?,
+                    size: r.next_f64()?,
+                }
+            }
+            ORDER_STATUS => IncomingMessage::OrderStatus(OrderStatusUpdate {
+                order_id: r.next_i32()?,
+                status: r.next_string()?,
+                filled: r.next_f64()?,
+                remaining: r.next_f64()?,
+                avg_fill_price: r.next_f64()?,
+                perm_id: r.next_i32()?,
+                parent_id: r.next_i32()?,
+                last_fill_price: r.next_f64()?,
+                client_id: r.next_i32()?,
+                why_held: r.next_string()?,
+                mkt_cap_price: r.next_f64()?,
+            }),
+            ERR_MSG => {
+                r.skip(1)?;
+                IncomingMessage::Error {
+                    id: r.next_i32()?,
+                    code: r.next_i32()?,
+                    message: r.next_string()?,
+                }
+            }
+            OPEN_ORDER => read_open_order(&mut r)?,
+            NEXT_VALID_ID => {
+                r.skip(1)?;
+                IncomingMessage::NextValidId {
+                    order_id: r.next_i32()?,
+                }
+            }
+            EXECUTION_DATA => IncomingMessage::ExecutionData {
+                req_id: r.next_i32()?,
+                order_id: r.next_i32()?,
+                contract: read_report_contract(&mut r)?,
+                execution: Execution {
+                    exec_id: r.next_string()?,
+                    time: r.next_string()?,
+                    account: r.next_string()?,
+                    exchange: r.next_string()?,
+                    side: r.next_string()?,
+                    shares: r.next_f64()?,
+                    price: r.next_f64()?,
+                    perm_id: r.next_i32()?,
+                    client_id: r.next_i32()?,
+                    cum_qty: {
+                        r.skip(1)?; // liquidation
+                        r.next_f64()?
+                    },
+                    avg_price: r.next_f64()?,
+                    order_ref: r.next_string()?,
+                },
+            },
+            MANAGED_ACCTS => {
+                r.skip(1)?;
+                IncomingMessage::ManagedAccounts {
+                    accounts: r
+                        .next_str()?
+                        .split(',')
+                        .filter(|a| !a.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                }
+            }
+            HISTORICAL_DATA => {
+                let req_id = r.next_i32()?;
+                let start = r.next_string()?;
+                let end = r.next_string()?;
+                let count: usize = r.next_parsed()?;
+                let mut bars = Vec::with_capacity(count.min(r.remaining()));
+                for _ in 0..count {
+                    bars.push(BarData {
+                        date: parse_bar_date(r.next_str()?)?,
+                        open: r.next_f64()?,
+                        high: r.next_f64()?,
+                        low: r.next_f64()?,
+                        close: r.next_f64()?,
+                        volume: parse_volume(r.next_str()?)?,
+                        wap: r.next_opt_parsed()?,
+                        count: r.next_opt_parsed()?,
+                    });
+                }
+                IncomingMessage::HistoricalData {
+                    req_id,
+                    start,
+                    end,
+                    bars,
+                }
+            }
+            CURRENT_TIME => {
+                r.skip(1)?;
+                IncomingMessage::CurrentTime {
+                    time: r.next_i64()?,
+                }
+            }
+            OPEN_ORDER_END => IncomingMessage::OpenOrderEnd,
+            COMPLETED_ORDER => read_completed_order(&mut r)?,
+            COMPLETED_ORDERS_END => IncomingMessage::CompletedOrdersEnd,
+            EXECUTION_DATA_END => {
+                r.skip(1)?;
+                IncomingMessage::ExecutionDataEnd {
+                    req_id: r.next_i32()?,
+                }
+            }
+            TICK_SNAPSHOT_END => {
+                r.skip(1)?;
+                IncomingMessage::TickSnapshotEnd {
+                    req_id: r.next_i32()?,
+                }
+            }
+            COMMISSION_REPORT => {
+                r.skip(1)?;
+                IncomingMessage::CommissionReport(CommissionReport {
+                    exec_id: r.next_string()?,
+                    commission: r.next_f64()?,
+                    currency: r.next_string()?,
+                    realized_pnl: r.next_opt_f64()?,
+                })
+            }
+            POSITION_DATA => {
+                r.skip(1)?;
+                IncomingMessage::Position {
+                    account: r.next_string()?,
+                    contract: read_report_contract(&mut r)?,
+                    position: r.next_f64()?,
+                    avg_cost: r.next_f64()?,
+                }
+            }
+            POSITION_END => IncomingMessage::PositionEnd,
+            ACCOUNT_SUMMARY => {
+                r.skip(1)?;
+                IncomingMessage::AccountSummary {
+                    req_id: r.next_i32()?,
+                    account: r.next_string()?,
+                    tag: r.next_string()?,
+                    value: r.next_string()?,
+                    currency: r.next_string()?,
+                }
+            }
+            ACCOUNT_SUMMARY_END => {
+                r.skip(1)?;
+                IncomingMessage::AccountSummaryEnd {
+                    req_id: r.next_i32()?,
+                }
+            }
+            _ => IncomingMessage::Unknown {
+                msg_id,
+                fields: fields[1..].to_vec(),
+            },
+        };
+
+        Ok(message)
+    }
+}
+
+impl OutgoingMessage {
+    /// Serialize into message fields (without the frame header) in the
+    /// layout of server `version`
+    pub fn encode_fields(&self, version: i32) -> Vec<String> {
+        use outgoing::*;
+
+        let mut w = FieldWriter::default();
+        match self {
+            OutgoingMessage::Handshake {
+                min_version,
+                max_version,
+            } => {
+                w.push(format!("v{}..{}", min_version, max_version));
+            }
+            OutgoingMessage::StartApi {
+                client_id,
+                optional_capabilities,
+            } => {
+                w.push(START_API)
+                    .push(2)
+                    .push(client_id)
+                    .push(optional_capabilities);
+            }
+            OutgoingMessage::ReqMktData {
+                req_id,
+                contract,
+                generic_ticks,
+                snapshot,
+            } => {
+                w.push(REQ_MKT_DATA).push(11).push(req_id);
+                write_request_contract(&mut w, contract);
+                w.push_bool(false) // delta neutral contract
+                    .push(generic_ticks)
+                    .push_bool(*snapshot)
+                    .push_bool(false) // regulatory snapshot
+                    .push("");
+            }
+            OutgoingMessage::CancelMktData { req_id } => {
+                w.push(CANCEL_MKT_DATA).push(2).push(req_id);
+            }
+            OutgoingMessage::PlaceOrder {
+                order_id,
+                contract,
+                order,
+            } => {
+                w.push(PLACE_ORDER).push(order_id);
+                write_order_contract(&mut w, contract);
+                write_place_order(&mut w, contract, order, version);
+            }
+            OutgoingMessage::CancelOrder { order_id } => {
+                w.push(CANCEL_ORDER).push(1).push(order_id);
+                if version >= server_versions::MANUAL_ORDER_TIME {
+                    w.push(""); // manualOrderCancelTime
+                }
+            }
+            OutgoingMessage::ReqOpenOrders => {
+                w.push(REQ_OPEN_ORDERS).push(1);
+            }
+            OutgoingMessage::ReqExecutions { req_id } => {
+                // Empty filter: clientId, account, time, symbol, secType,
+                // exchange, side
+                w.push(REQ_EXECUTIONS)
+                    .push(3)
+                    .push(req_id)
+                    .push(0)
+                    .push("")
+                    .push("")
+                    .push("")
+                    .push("")
+                    .push("")
+                    .push("");
+            }
+            OutgoingMessage::ReqIds { num_ids } => {
+                w.push(REQ_IDS).push(1).push(num_ids);
+            }
+            OutgoingMessage::ReqAllOpenOrders => {
+                w.push(REQ_ALL_OPEN_ORDERS).push(1);
+            }
+            OutgoingMessage::ReqHistoricalData {
+                req_id,
+                contract,
+                end_date_time,
+                duration,
+                bar_size,
+                what_to_show,
+                use_rth,
+            } => {
+                w.push(REQ_HISTORICAL_DATA).push(req_id);
+                write_request_contract(&mut w, contract);
+                w.push_bool(false) // include expired
+                    .push(end_date_time)
+                    .push(bar_size)
+                    .push(duration)
+                    .push_bool(*use_rth)
+                    .push(what_to_show)
+                    .push(2) // format dates as epoch seconds
+                    .push_bool(false) // keep up to date
+                    .push("");
+            }
+            OutgoingMessage::ReqCurrentTime => {
+                w.push(REQ_CURRENT_TIME).push(1);
+            }
+            OutgoingMessage::ReqGlobalCancel => {
+                w.push(REQ_GLOBAL_CANCEL).push(1);
+            }
+            OutgoingMessage::ReqPositions => {
+                w.push(REQ_POSITIONS).push(1);
+            }
+            OutgoingMessage::ReqAccountSummary {
+                req_id,
+                group,
+                tags,
+            } => {
+                w.push(REQ_ACCOUNT_SUMMARY)
+                    .push(1)
+                    .push(req_id)
+                    .push(group)
+                    .push(tags);
+            }
+            OutgoingMessage::CancelAccountSummary { req_id } => {
+                w.push(CANCEL_ACCOUNT_SUMMARY).push(1).push(req_id);
+            }
+            OutgoingMessage::CancelPositions => {
+                w.push(CANCEL_POSITIONS).push(1);
+            }
+            OutgoingMessage::ReqCompletedOrders { api_only } => {
+                w.push(REQ_COMPLETED_ORDERS).push_bool(*api_only);
+            }
+        }
+        w.finish()
+    }
+
+    /// Parse a regular (post-handshake) message from its fields
+    pub fn decode_fields(fields: &[String]) -> Result<Self> {
+        use outgoing::*;
+
+        let mut r = FieldReader::new(fields);
+        let msg_id = r.next_i32()?;
+
+        let message = match msg_id {
+            START_API => {
+                r.skip(1)?;
+                OutgoingMessage::StartApi {
+                    client_id: r.next_i32()?,
+                    optional_capabilities: r.next_string()?,
+                }
+            }
+            REQ_MKT_DATA => {
+                r.skip(1)?;
+                let req_id = r.next_i32()?;
+                let contract = read_request_contract(&mut r)?;
+                r.skip(1)?;
+                let generic_ticks = r.next_string()?;
+                let snapshot = r.next_bool()?;
+                OutgoingMessage::ReqMktData {
+                    req_id,
+                    contract,
+                    generic_ticks,
+                    snapshot,
+                }
+            }
+            CANCEL_MKT_DATA => {
+                r.skip(1)?;
+                OutgoingMessage::CancelMktData {
+                    req_id: r.next_i32()?,
+                }
+            }
+            PLACE_ORDER => {
+                let order_id = r.next_i32()?;
+                let contract = read_order_contract(&mut r)?;
+                let order = read_place_order(&mut r)?;
+                OutgoingMessage::PlaceOrder {
+                    order_id,
+                    contract,
+                    order: Box::new(order),
+                }
+            }
+            CANCEL_ORDER => {
+                r.skip(1)?;
+                OutgoingMessage::CancelOrder {
+                    order_id: r.next_i32()?,
+                }
+            }
+            REQ_OPEN_ORDERS => OutgoingMessage::ReqOpenOrders,
+            REQ_EXECUTIONS => {
+                r.skip(1)?;
+                OutgoingMessage::ReqExecutions {
+                    req_id: r.next_i32()?,
+                }
+            }
+            REQ_IDS => {
+                r.skip(1)?;
+                OutgoingMessage::ReqIds {
+                    num_ids: r.next_i32()?,
+                }
+            }
+            REQ_ALL_OPEN_ORDERS => OutgoingMessage::ReqAllOpenOrders,
+            REQ_HISTORICAL_DATA => {
+                let req_id = r.next_i32()?;
+                let contract = read_request_contract(&mut r)?;
+                r.skip(1)?;
+                let end_date_time = r.next_string()?;
+                let bar_size = r.next_string()?;
+                let duration = r.next_string()?;
+                let use_rth = r.next_bool()?;
+                let what_to_show = r.next_string()?;
+                OutgoingMessage::ReqHistoricalData {
+                    req_id,
+                    contract,
+                    end_date_time,
+                    duration,
+                    bar_size,
+                    what_to_show,
+                    use_rth,
+                }
+            }
+            REQ_CURRENT_TIME => OutgoingMessage::ReqCurrentTime,
+            REQ_GLOBAL_CANCEL => OutgoingMessage::ReqGlobalCancel,
+            REQ_POSITIONS => OutgoingMessage::ReqPositions,
+            REQ_ACCOUNT_SUMMARY => {
+                r.skip(1)?;
+                OutgoingMessage::ReqAccountSummary {
+                    req_id: r.next_i32()?,
+                    group: r.next_string()?,
+                    tags: r.next_string()?,
+                }
+            }
+            CANCEL_ACCOUNT_SUMMARY => {
+                r.skip(1)?;
+                OutgoingMessage::CancelAccountSummary {
+                    req_id: r.next_i32()?,
+                }
+            }
+            CANCEL_POSITIONS => OutgoingMessage::CancelPositions,
+            REQ_COMPLETED_ORDERS => OutgoingMessage::ReqCompletedOrders {
+                api_only: r.next_bool()?,
+            },
+            _ => {
+                return Err(IBKRMCPError::Protocol(format!(
+                    "Unsupported outgoing message {}",
+                    msg_id
+                )))
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+/// Bar date as epoch seconds; daily and longer bars come as `YYYYMMDD`
+/// even when epoch seconds were requested
+fn parse_bar_date(date: &str) -> Result<DateTime<Utc>> {
+    let invalid = || IBKRMCPError::Protocol(format!("Invalid bar date {:?}", date));
+    if date.len() == 8 {
+        let day = NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| invalid())?;
+        return Ok(Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).ok_or_else(invalid)?));
+    }
+    let timestamp: i64 = date.parse().map_err(|_| invalid())?;
+    Utc.timestamp_opt(timestamp, 0).single().ok_or_else(invalid)
+}
+
+/// Bar volume, which TWS sends as a decimal for some instruments
+fn parse_volume(volume: &str) -> Result<i64> {
+    volume
+        .parse::<i64>()
+        .or_else(|_| volume.parse::<f64>().map(|v| v as i64))
+        .map_err(|_| IBKRMCPError::Protocol(format!("Invalid bar volume {:?}", volume)))
+}
+
+/// Split one length-prefixed frame off `src`, or `None` if incomplete
+fn decode_frame(src: &mut BytesMut, max_frame_len: usize) -> Result<Option<Vec<String>>> {
+    if src.len() < 4 {
+        return Ok(None);
+    }
+
+    let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+    if len > max_frame_len {
+        return Err(IBKRMCPError::Protocol(format!(
+            "Frame of {} bytes exceeds limit of {}",
+            len, max_frame_len
+        )));
+    }
+
+    if src.len() < 4 + len {
+        src.reserve(4 + len - src.len());
+        return Ok(None);
+    }
+
+    src.advance(4);
+    let payload = src.split_to(len);
+    let payload = payload.strip_suffix(b"\0").unwrap_or(&payload);
+
+    Ok(Some(
+        payload
+            .split(|b| *b == 0)
+            .map(|f| String::from_utf8_lossy(f).into_owned())
+            .collect(),
+    ))
+}
+
+/// Append `fields` to `dst` as a NUL-terminated, length-prefixed frame
+fn encode_frame(fields: &[String], max_frame_len: usize, dst: &mut BytesMut) -> Result<()> {
+    let len: usize = fields.iter().map(|f| f.len() + 1).sum();
+    if len > max_frame_len {
+        return Err(IBKRMCPError::Protocol(format!(
+            "Frame of {} bytes exceeds limit of {}",
+            len, max_frame_len
+        )));
+    }
+
+    dst.reserve(4 + len);
+    dst.put_u32(len as u32);
+    for field in fields {
+        dst.put_slice(field.as_bytes());
+        dst.put_u8(0);
+    }
+    Ok(())
+}
+
+/// Client-side codec: decodes [`IncomingMessage`], encodes [`OutgoingMessage`]
+#[derive(Debug, Clone)]
+pub struct TwsCodec {
+    server_version: Option<i32>,
+    max_frame_len: usize,
+}
+
+impl Default for TwsCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TwsCodec {
+    /// Codec for a fresh connection; the first decoded frame is the
+    /// handshake reply
+    pub fn new() -> Self {
+        Self {
+            server_version: None,
+            max_frame_len: MAX_FRAME_LEN,
+        }
+    }
+
+    /// Codec for a connection whose handshake already completed
+    pub fn with_server_version(server_version: i32) -> Self {
+        Self {
+            server_version: Some(server_version),
+            max_frame_len: MAX_FRAME_LEN,
+        }
+    }
+
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Server version negotiated during the handshake
+    pub fn server_version(&self) -> Option<i32> {
+        self.server_version
+    }
+}
+
+impl Decoder for TwsCodec {
+    type Item = IncomingMessage;
+    type Error = IBKRMCPError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<IncomingMessage>> {
+        let Some(fields) = decode_frame(src, self.max_frame_len)? else {
+            return Ok(None);
+        };
+
+        if self.server_version.is_some() {
+            // A message this codec cannot parse does not affect framing, so
+            // it is passed on raw instead of failing the connection
+            return Ok(Some(
+                IncomingMessage::decode_fields(&fields).unwrap_or_else(|e| {
+                    warn!("Undecodable TWS message: {}", e);
+                    IncomingMessage::Unknown {
+                        msg_id: fields.first().and_then(|id| id.parse().ok()).unwrap_or(-1),
+                        fields: fields.get(1..).unwrap_or_default().to_vec(),
+                    }
+                }),
+            ));
+        }
+
+        let mut r = FieldReader::new(&fields);
+        let version = r.next_i32()?;
+        if !(MIN_CLIENT_VERSION..=MAX_CLIENT_VERSION).contains(&version) {
+            return Err(IBKRMCPError::Connection(format!(
+                "Server version {} outside supported range {}..{}",
+                version, MIN_CLIENT_VERSION, MAX_CLIENT_VERSION
+            )));
+        }
+        self.server_version = Some(version);
+
+        Ok(Some(IncomingMessage::ServerVersion {
+            version,
+            connection_time: r.next_opt_string()?.unwrap_or_default(),
+        }))
+    }
+}
+
+impl Encoder<OutgoingMessage> for TwsCodec {
+    type Error = IBKRMCPError;
+
+    fn encode(&mut self, item: OutgoingMessage, dst: &mut BytesMut) -> Result<()> {
+        if let OutgoingMessage::Handshake { max_version, .. } = item {
+            // The version range is length-prefixed but not NUL-terminated
+            let fields = item.encode_fields(max_version);
+            let range = fields[0].as_bytes();
+            dst.reserve(API_PREFIX.len() + 4 + range.len());
+            dst.put_slice(API_PREFIX);
+            dst.put_u32(range.len() as u32);
+            dst.put_slice(range);
+            return Ok(());
+        }
+        let version = self.server_version.ok_or_else(|| {
+            IBKRMCPError::Protocol("Cannot encode messages before the handshake".into())
+        })?;
+        encode_frame(&item.encode_fields(version), self.max_frame_len, dst)
+    }
+}
+
+/// Gateway-side codec: decodes [`OutgoingMessage`], encodes [`IncomingMessage`]
+#[derive(Debug, Clone)]
+pub struct GatewayCodec {
+    handshake_done: bool,
+    /// Layout version for encoding: the `ServerVersion` sent, or the
+    /// client's maximum until then
+    server_version: i32,
+    max_frame_len: usize,
+}
+
+impl Default for GatewayCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GatewayCodec {
+    pub fn new() -> Self {
+        Self {
+            handshake_done: false,
+            server_version: MAX_CLIENT_VERSION,
+            max_frame_len: MAX_FRAME_LEN,
+        }
+    }
+
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+}
+
+impl Decoder for GatewayCodec {
+    type Item = OutgoingMessage;
+    type Error = IBKRMCPError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<OutgoingMessage>> {
+        if self.handshake_done {
+            return match decode_frame(src, self.max_frame_len)? {
+                Some(fields) => OutgoingMessage::decode_fields(&fields).map(Some),
+                None => Ok(None),
+            };
+        }
+
+        if src.len() < API_PREFIX.len() {
+            return Ok(None);
+        }
+        if &src[..API_PREFIX.len()] != API_PREFIX {
+            return Err(IBKRMCPError::Protocol(
+                "Missing API prefix in handshake".into(),
+            ));
+        }
+
+        // Only consume the prefix once the version frame is complete
+        let mut rest = BytesMut::from(&src[API_PREFIX.len()..]);
+        let Some(fields) = decode_frame(&mut rest, self.max_frame_len)? else {
+            return Ok(None);
+        };
+        let consumed = src.len() - rest.len();
+        src.advance(consumed);
+
+        let range = fields.first().map(String::as_str).unwrap_or_default();
+        let (min, max) = range
+            .strip_prefix('v')
+            .and_then(|r| r.split_once(".."))
+            .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)))
+            .ok_or_else(|| IBKRMCPError::Protocol(format!("Invalid version range {:?}", range)))?;
+
+        self.handshake_done = true;
+        self.server_version = max;
+        Ok(Some(OutgoingMessage::Handshake {
+            min_version: min,
+            max_version: max,
+        }))
+    }
+}
+
+impl Encoder<IncomingMessage> for GatewayCodec {
+    type Error = IBKRMCPError;
+
+    fn encode(&mut self, item: IncomingMessage, dst: &mut BytesMut) -> Result<()> {
+        if let IncomingMessage::ServerVersion { version, .. } = item {
+            self.server_version = version;
+        }
+        encode_frame(
+            &item.encode_fields(self.server_version),
+            self.max_frame_len,
+            dst,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        AdaptivePriority, AlgoStrategy, OrderAction, OrderType, RiskAversion, TimeInForce,
+    };
+    use proptest::prelude::*;
+
+    fn encode_outgoing(messages: &[OutgoingMessage]) -> BytesMut {
+        let mut codec = TwsCodec::with_server_version(MAX_CLIENT_VERSION);
+        let mut buf = BytesMut::new();
+        for message in messages {
+            codec.encode(message.clone(), &mut buf).unwrap();
+        }
+        buf
+    }
+
+    fn decode_all<D: Decoder<Error = IBKRMCPError>>(
+        codec: &mut D,
+        buf: &mut BytesMut,
+    ) -> Vec<D::Item> {
+        let mut out = Vec::new();
+        while let Some(item) = codec.decode(buf).unwrap() {
+            out.push(item);
+        }
+        out
+    }
+
+    fn handshake() -> OutgoingMessage {
+        OutgoingMessage::Handshake {
+            min_version: MIN_CLIENT_VERSION,
+            max_version: MAX_CLIENT_VERSION,
+        }
+    }
+
+    #[test]
+    fn test_handshake_fixture() {
+        let buf = encode_outgoing(&[handshake()]);
+        assert_eq!(&buf[..], b"API\0\0\0\0\x09v151..176");
+
+        let mut codec = TwsCodec::new();
+        let mut reply = BytesMut::from(&b"\0\0\0\x1a176\x0020240101 12:00:00 EST\0"[..]);
+        assert_eq!(
+            codec.decode(&mut reply).unwrap(),
+            Some(IncomingMessage::ServerVersion {
+                version: 176,
+                connection_time: "20240101 12:00:00 EST".into(),
+            })
+        );
+        assert_eq!(codec.server_version(), Some(176));
+    }
+
+    #[test]
+    fn test_rejects_unsupported_server_version() {
+        let mut codec = TwsCodec::new();
+        let mut reply = BytesMut::from(&b"\0\0\0\x0399\0"[..]);
+        assert!(codec.decode(&mut reply).is_err());
+    }
+
+    #[test]
+    fn test_partial_frame() {
+        let mut full = BytesMut::new();
+        GatewayCodec::new()
+            .encode(IncomingMessage::NextValidId { order_id: 42 }, &mut full)
+            .unwrap();
+
+        let mut codec = TwsCodec::with_server_version(MAX_CLIENT_VERSION);
+        let mut buf = BytesMut::new();
+
+        // Header split across reads, then payload split across reads
+        for chunk in [&full[..2], &full[2..5], &full[5..full.len() - 1]] {
+            buf.extend_from_slice(chunk);
+            assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        }
+        buf.extend_from_slice(&full[full.len() - 1..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(IncomingMessage::NextValidId { order_id: 42 })
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_partial_handshake() {
+        let full = encode_outgoing(&[handshake()]);
+        let mut codec = GatewayCodec::new();
+        let mut buf = BytesMut::from(&full[..6]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert_eq!(buf.len(), 6);
+
+        buf.extend_from_slice(&full[6..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(handshake()));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_oversize_frame() {
+        let mut codec = TwsCodec::with_server_version(MAX_CLIENT_VERSION);
+        let mut buf = BytesMut::new();
+        buf.put_u32(MAX_FRAME_LEN as u32 + 1);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(IBKRMCPError::Protocol(_))
+        ));
+
+        let mut small = TwsCodec::with_server_version(MAX_CLIENT_VERSION).with_max_frame_len(8);
+        let mut out = BytesMut::new();
+        assert!(small
+            .encode(
+                OutgoingMessage::ReqAccountSummary {
+                    req_id: 1,
+                    group: "All".into(),
+                    tags: "NetLiquidation".into(),
+                },
+                &mut out,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_unknown_message_is_preserved() {
+        let fields: Vec<String> = ["999", "1", "x"].iter().map(|s| s.to_string()).collect();
+        let message = IncomingMessage::decode_fields(&fields).unwrap();
+        assert_eq!(
+            message,
+            IncomingMessage::Unknown {
+                msg_id: 999,
+                fields: vec!["1".into(), "x".into()],
+            }
+        );
+        assert_eq!(message.encode_fields(MAX_CLIENT_VERSION), fields);
+    }
+
+    #[test]
+    fn test_option_contract_fixture() {
+        let mut contract = Contract::new("AAPL", SecType::Option);
+        contract.expiry = Some("20250117".into());
+        contract.strike = Some(200.0);
+        contract.right = Some("C".into());
+        contract.multiplier = Some(100);
+
+        let message = OutgoingMessage::ReqMktData {
+            req_id: 7,
+            contract,
+            generic_ticks: String::new(),
+            snapshot: true,
+        };
+        let fields = message.encode_fields(MAX_CLIENT_VERSION);
+        assert_eq!(
+            &fields[..14],
+            &[
+                "1", "11", "7", "0", "AAPL", "OPT", "20250117", "200", "C", "100", "SMART", "",
+                "USD", ""
+            ]
+        );
+        assert_eq!(OutgoingMessage::decode_fields(&fields).unwrap(), message);
+    }
+
+    fn wire(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    // Java's Double.MAX_VALUE and Integer.MAX_VALUE as TWS prints them
+    const MAX_DOUBLE: &str = "1.7976931348623157E308";
+    const MAX_INT: &str = "2147483647";
+
+    fn limit_order() -> Order {
+        let mut order =
+            Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(185.5);
+        order.account = Some("DU123456".into());
+        order.order_ref = Some("mcp-1".into());
+        order
+    }
+
+    // Field sequence of the reference client's placeOrder for a plain limit
+    // order, at the highest server version negotiated
+    #[rustfmt::skip]
+    const PLACE_ORDER_FIELDS: &[&str] = &[
+        "3", "12",
+        // conId, symbol, secType, lastTradeDate, strike, right, multiplier,
+        // exchange, primaryExchange, currency, localSymbol, tradingClass,
+        // secIdType, secId
+        "0", "AAPL", "STK", "", "0", "", "", "SMART", "", "USD", "", "", "", "",
+        // action, totalQuantity, orderType, lmtPrice, auxPrice
+        "BUY", "100", "LMT", "185.5", "",
+        // tif, ocaGroup, account, openClose, origin, orderRef, transmit,
+        // parentId, blockOrder, sweepToFill, displaySize, triggerMethod,
+        // outsideRth, hidden
+        "DAY", "", "DU123456", "", "0", "mcp-1", "1", "0", "0", "0", "0", "0", "0", "0",
+        // sharesAllocation, discretionaryAmt, goodAfterTime, goodTillDate,
+        // faGroup, faMethod, faPercentage, faProfile, modelCode
+        "", "0", "", "", "", "", "", "", "",
+        // shortSaleSlot, designatedLocation, exemptCode, ocaType, rule80A,
+        // settlingFirm, allOrNone, minQty, percentOffset, eTradeOnly,
+        // firmQuoteOnly, nbboPriceCap, auctionStrategy
+        "0", "", "-1", "0", "", "", "0", "", "", "0", "0", "", "0",
+        // startingPrice, stockRefPrice, delta, stockRangeLower,
+        // stockRangeUpper, overridePercentageConstraints
+        "", "", "", "", "", "0",
+        // volatility, volatilityType, deltaNeutralOrderType,
+        // deltaNeutralAuxPrice, continuousUpdate, referencePriceType
+        "", "", "", "", "0", "",
+        // trailStopPrice, trailingPercent
+        "", "",
+        // scaleInitLevelSize, scaleSubsLevelSize, scalePriceIncrement,
+        // scaleTable, activeStartTime, activeStopTime
+        "", "", "", "", "", "",
+        // hedgeType, optOutSmartRouting, clearingAccount, clearingIntent,
+        // notHeld, deltaNeutralContract, algoStrategy, algoId, whatIf,
+        // orderMiscOptions, solicited, randomizeSize, randomizePrice
+        "", "0", "", "", "0", "0", "", "", "0", "", "0", "0", "0",
+        // conditions, adjustedOrderType, triggerPrice, lmtPriceOffset,
+        // adjustedStopPrice, adjustedStopLimitPrice, adjustedTrailingAmount,
+        // adjustableTrailingUnit
+        "0", "", "", "", "", "", "", "0",
+        // extOperator, softDollarTier name and value, cashQty, MiFID II
+        // decision maker, decision algo, execution trader, execution algo
+        "", "", "", "", "", "", "", "",
+        // dontUseAutoPriceForHedge, isOmsContainer,
+        // discretionaryUpToLimitPrice, usePriceMgmtAlgo
+        "0", "0", "0", "",
+        // duration, postToAts, autoCancelParent, advancedErrorOverride,
+        // manualOrderTime
+        "", "", "0", "", "",
+    ];
+
+    #[test]
+    fn test_place_order_fixture() {
+        let message = OutgoingMessage::PlaceOrder {
+            order_id: 12,
+            contract: Contract::new("AAPL", SecType::Stock),
+            order: Box::new(limit_order()),
+        };
+        assert_eq!(
+            message.encode_fields(MAX_CLIENT_VERSION),
+            wire(PLACE_ORDER_FIELDS)
+        );
+        assert_eq!(
+            OutgoingMessage::decode_fields(&wire(PLACE_ORDER_FIELDS)).unwrap(),
+            message
+        );
+
+        // Servers before 158 end the message at usePriceMgmtAlgo
+        let fields = message.encode_fields(server_versions::DURATION - 1);
+        assert_eq!(
+            fields,
+            wire(&PLACE_ORDER_FIELDS[..PLACE_ORDER_FIELDS.len() - 5])
+        );
+        let fields = message.encode_fields(server_versions::ADVANCED_ORDER_REJECT);
+        assert_eq!(
+            fields,
+            wire(&PLACE_ORDER_FIELDS[..PLACE_ORDER_FIELDS.len() - 1])
+        );
+    }
+
+    #[test]
+    fn test_cancel_order_by_server_version() {
+        let message = OutgoingMessage::CancelOrder { order_id: 12 };
+        assert_eq!(
+            message.encode_fields(server_versions::MANUAL_ORDER_TIME - 1),
+            wire(&["4", "1", "12"])
+        );
+        // manualOrderCancelTime
+        assert_eq!(
+            message.encode_fields(server_versions::MANUAL_ORDER_TIME),
+            wire(&["4", "1", "12", ""])
+        );
+    }
+
+    #[test]
+    fn test_open_order_fixture() {
+        // openOrder for a working limit order as TWS reports it: stocks carry
+        // strike 0 and right "?", and unset values are Java's MAX_VALUEs
+        #[rustfmt::skip]
+        let fields = wire(&[
+            "5", "12",
+            // conId, symbol, secType, lastTradeDate, strike, right,
+            // multiplier, exchange, currency, localSymbol, tradingClass
+            "265598", "AAPL", "STK", "", "0", "?", "", "SMART", "USD", "AAPL", "NMS",
+            // action, totalQuantity, orderType, lmtPrice, auxPrice, tif,
+            // ocaGroup, account, openClose, origin, orderRef, clientId,
+            // permId, outsideRth, hidden, discretionaryAmt, goodAfterTime
+            "BUY", "100", "LMT", "185.5", "0.0", "DAY", "", "DU123456", "O", "0", "mcp-1",
+            "7", "1376327563", "0", "0", "0.0", "",
+            // sharesAllocation, faGroup, faMethod, faPercentage, faProfile,
+            // modelCode, goodTillDate, rule80A, percentOffset, settlingFirm,
+            // shortSaleSlot, designatedLocation, exemptCode, auctionStrategy
+            "", "", "", "", "", "", "", "", "", "", "0", "", "-1", "0",
+            // startingPrice, stockRefPrice, delta, stockRangeLower,
+            // stockRangeUpper, displaySize, blockOrder, sweepToFill,
+            // allOrNone, minQty, ocaType, eTradeOnly, firmQuoteOnly,
+            // nbboPriceCap, parentId, triggerMethod
+            "", "", "", "", "", "", "0", "0", "0", "", "3", "0", "0", "", "0", "0",
+            // volatility, volatilityType, deltaNeutralOrderType,
+            // deltaNeutralAuxPrice, continuousUpdate, referencePriceType
+            "", "0", "None", "", "0", "0",
+            // trailStopPrice, trailingPercent, basisPoints, basisPointsType,
+            // comboLegsDescrip, comboLegs, orderComboLegs,
+            // smartComboRoutingParams
+            "", "", "", "", "", "0", "0", "0",
+            // scaleInitLevelSize, scaleSubsLevelSize, scalePriceIncrement,
+            // hedgeType, optOutSmartRouting, clearingAccount, clearingIntent,
+            // notHeld, deltaNeutralContract, algoStrategy, solicited, whatIf
+            "", "", "", "", "0", "", "IB", "0", "0", "", "0", "0",
+            // status, margin before/change/after, commission, minCommission,
+            // maxCommission, commissionCurrency, warningText
+            "Submitted",
+            MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE,
+            MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE,
+            "", "",
+            // randomizeSize, randomizePrice, conditions, adjustedOrderType,
+            // triggerPrice, trailStopPrice, lmtPriceOffset, adjustedStopPrice,
+            // adjustedStopLimitPrice, adjustedTrailingAmount,
+            // adjustableTrailingUnit
+            "0", "0", "0", "None", MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE,
+            MAX_DOUBLE, MAX_DOUBLE, "0",
+            // softDollarTier name, value, displayName, cashQty,
+            // dontUseAutoPriceForHedge, isOmsContainer,
+            // discretionaryUpToLimitPrice, usePriceMgmtAlgo, duration,
+            // postToAts, autoCancelParent
+            "", "", "", MAX_DOUBLE, "0", "0", "0", "0", MAX_INT, MAX_INT, "0",
+            // minTradeQty, minCompeteSize, competeAgainstBestOffset,
+            // midOffsetAtWhole, midOffsetAtHalf
+            MAX_INT, MAX_INT, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE,
+        ]);
+
+        let mut contract = Contract::new("AAPL", SecType::Stock);
+        contract.con_id = Some(265598);
+        contract.local_symbol = Some("AAPL".into());
+        let mut order = limit_order();
+        order.client_id = Some(7);
+        order.oca_type = Some(OcaType::ReduceNonBlock);
+        assert_eq!(
+            IncomingMessage::decode_fields(&fields).unwrap(),
+            IncomingMessage::OpenOrder {
+                order_id: 12,
+                contract,
+                order,
+                state: OrderState {
+                    status: "Submitted".into(),
+                    ..Default::default()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_completed_order_fixture() {
+        // completedOrder for a filled limit order: no order ID, client ID or
+        // the fields only working orders have
+        #[rustfmt::skip]
+        let fields = wire(&[
+            "101",
+            // conId, symbol, secType, lastTradeDate, strike, right,
+            // multiplier, exchange, currency, localSymbol, tradingClass
+            "265598", "AAPL", "STK", "", "0", "?", "", "SMART", "USD", "AAPL", "NMS",
+            // action, totalQuantity, orderType, lmtPrice, auxPrice, tif,
+            // ocaGroup, account, openClose, origin, orderRef, permId,
+            // outsideRth, hidden, discretionaryAmt, goodAfterTime
+            "BUY", "100", "LMT", "185.5", "0.0", "DAY", "", "DU123456", "", "0", "mcp-1",
+            "1376327563", "0", "0", "0.0", "",
+            // faGroup, faMethod, faPercentage, faProfile, modelCode,
+            // goodTillDate, rule80A, percentOffset, settlingFirm,
+            // shortSaleSlot, designatedLocation, exemptCode
+            "", "", "", "", "", "", "", "", "", "0", "", "-1",
+            // startingPrice, stockRefPrice, delta, stockRangeLower,
+            // stockRangeUpper, displaySize, sweepToFill, allOrNone, minQty,
+            // ocaType, triggerMethod
+            "", "", "", "", "", "", "0", "0", "", "3", "0",
+            // volatility, volatilityType, deltaNeutralOrderType,
+            // deltaNeutralAuxPrice, continuousUpdate, referencePriceType
+            "", "0", "None", "", "0", "0",
+            // trailStopPrice, trailingPercent, comboLegsDescrip, comboLegs,
+            // orderComboLegs, smartComboRoutingParams
+            "", "", "", "0", "0", "0",
+            // scaleInitLevelSize, scaleSubsLevelSize, scalePriceIncrement,
+            // hedgeType, clearingAccount, clearingIntent, notHeld,
+            // deltaNeutralContract, algoStrategy, solicited, status
+            "", "", "", "", "", "IB", "0", "0", "", "0", "Filled",
+            // randomizeSize, randomizePrice, conditions, trailStopPrice,
+            // lmtPriceOffset, cashQty, dontUseAutoPriceForHedge,
+            // isOmsContainer, autoCancelDate, filledQuantity,
+            // refFuturesConId, autoCancelParent, shareholder, imbalanceOnly,
+            // routeMarketableToBbo, parentPermId, completedTime,
+            // completedStatus
+            "0", "0", "0", MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE, "0", "0", "", "100",
+            "0", "0", "Not an insider or substantial shareholder", "0", "0", "0",
+            "20240102 10:00:01 America/New_York", "Filled Size: 100",
+            // minTradeQty, minCompeteSize, competeAgainstBestOffset,
+            // midOffsetAtWhole, midOffsetAtHalf
+            MAX_INT, MAX_INT, MAX_DOUBLE, MAX_DOUBLE, MAX_DOUBLE,
+        ]);
+
+        let mut contract = Contract::new("AAPL", SecType::Stock);
+        contract.con_id = Some(265598);
+        contract.local_symbol = Some("AAPL".into());
+        let mut order = limit_order();
+        order.oca_type = Some(OcaType::ReduceNonBlock);
+        assert_eq!(
+            IncomingMessage::decode_fields(&fields).unwrap(),
+            IncomingMessage::CompletedOrder {
+                contract,
+                order,
+                perm_id: 1376327563,
+                status: "Filled".into(),
+            }
+        );
+        assert_eq!(
+            OutgoingMessage::ReqCompletedOrders { api_only: true }
+                .encode_fields(MAX_CLIENT_VERSION),
+            wire(&["99", "1"])
+        );
+    }
+
+    #[test]
+    fn test_position_and_execution_fixtures() {
+        // Neither report carries a primary exchange
+        #[rustfmt::skip]
+        let position = wire(&[
+            "61", "3", "DU123456",
+            "265598", "AAPL", "STK", "", "0.0", "", "", "NASDAQ", "USD", "AAPL", "NMS",
+            "100", "185.42",
+        ]);
+        let mut contract = Contract::new("AAPL", SecType::Stock).with_exchange("NASDAQ");
+        contract.con_id = Some(265598);
+        contract.local_symbol = Some("AAPL".into());
+        let expected = IncomingMessage::Position {
+            account: "DU123456".into(),
+            contract: contract.clone(),
+            position: 100.0,
+            avg_cost: 185.42,
+        };
+        assert_eq!(IncomingMessage::decode_fields(&position).unwrap(), expected);
+
+        // reqId, orderId, contract, then execId, time, account, exchange,
+        // side, shares, price, permId, clientId, liquidation, cumQty,
+        // avgPrice, orderRef, evRule, evMultiplier, modelCode, lastLiquidity
+        #[rustfmt::skip]
+        let execution = wire(&[
+            "11", "-1", "12",
+            "265598", "AAPL", "STK", "", "0.0", "", "", "ISLAND", "USD", "AAPL", "NMS",
+            "0000e0d5.65f9a1c2.01.01", "20240102 10:15:03 US/Eastern", "DU123456", "ISLAND",
+            "BOT", "100", "185.42", "1376327563", "7", "0", "100", "185.42", "mcp-1",
+            "", "", "", "2",
+        ]);
+        let IncomingMessage::ExecutionData {
+            order_id,
+            contract: exec_contract,
+            execution,
+            ..
+        } = IncomingMessage::decode_fields(&execution).unwrap()
+        else {
+            panic!("expected execDetails");
+        };
+        assert_eq!(order_id, 12);
+        assert_eq!(exec_contract, contract.with_exchange("ISLAND"));
+        assert_eq!(execution.cum_qty, 100.0);
+        assert_eq!(execution.avg_price, 185.42);
+        assert_eq!(execution.order_ref, "mcp-1");
+
+        // Commission reports without a realized P&L send Double.MAX_VALUE
+        let commission = wire(&[
+            "59",
+            "1",
+            "0000e0d5.65f9a1c2.01.01",
+            "1.0",
+            "USD",
+            MAX_DOUBLE,
+            MAX_DOUBLE,
+            "",
+        ]);
+        assert!(matches!(
+            IncomingMessage::decode_fields(&commission).unwrap(),
+            IncomingMessage::CommissionReport(CommissionReport {
+                realized_pnl: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_daily_bars_and_decimal_volume() {
+        let fields = wire(&[
+            "17", "3", "20240101", "20240105", "1", "20240102", "185.1", "186.7", "184.2", "185.6",
+            "52164.5", "185.4", "12345",
+        ]);
+        let IncomingMessage::HistoricalData { bars, .. } =
+            IncomingMessage::decode_fields(&fields).unwrap()
+        else {
+            panic!("expected historicalData");
+        };
+        assert_eq!(
+            bars[0].date,
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap()
+        );
+        assert_eq!(bars[0].volume, 52164);
+    }
+
+    #[test]
+    fn test_undecodable_message_does_not_fail_connection() {
+        let mut bytes = BytesMut::new();
+        encode_frame(&wire(&["61", "3", "DU1", "x"]), MAX_FRAME_LEN, &mut bytes).unwrap();
+        GatewayCodec::new()
+            .encode(IncomingMessage::PositionEnd, &mut bytes)
+            .unwrap();
+
+        let mut codec = TwsCodec::with_server_version(MAX_CLIENT_VERSION);
+        assert_eq!(
+            decode_all(&mut codec, &mut bytes),
+            vec![
+                IncomingMessage::Unknown {
+                    msg_id: 61,
+                    fields: wire(&["3", "DU1", "x"]),
+                },
+                IncomingMessage::PositionEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoding_requires_server_version() {
+        let mut buf = BytesMut::new();
+        assert!(TwsCodec::new()
+            .encode(OutgoingMessage::ReqPositions, &mut buf)
+            .is_err());
+    }
+
+    fn sec_type() -> impl Strategy<Value = SecType> {
+        prop_oneof![
+            Just(SecType::Stock),
+            Just(SecType::Future),
+            Just(SecType::Forex),
+            Just(SecType::Index),
+        ]
+    }
+
+    fn text() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9 .:-]{0,12}"
+    }
+
+    fn contract() -> impl Strategy<Value = Contract> {
+        (
+            "[A-Z]{1,5}",
+            sec_type(),
+            proptest::option::of(1..i32::MAX),
+            proptest::option::of("[0-9]{8}"),
+            proptest::option::of(1..1000i32),
+            "[A-Z]{3,6}",
+            "[A-Z]{3}",
+        )
+            .prop_map(
+                |(symbol, sec_type, con_id, last_trade_date, multiplier, exchange, currency)| {
+                    let mut contract = Contract::new(symbol, sec_type)
+                        .with_exchange(exchange)
+                        .with_currency(currency);
+                    contract.con_id = con_id;
+                    contract.last_trade_date = last_trade_date;
+                    contract.multiplier = multiplier;
+                    contract
+                },
+            )
+    }
+
+    fn order() -> impl Strategy<Value = Order> {
+        (
+            prop_oneof![Just(OrderAction::Buy), Just(OrderAction::Sell)],
+            1u32..100_000,
+            prop_oneof![
+                Just(OrderType::Market),
+                Just(OrderType::Limit),
+                Just(OrderType::StopLimit),
+                Just(OrderType::TrailLimit),
+            ],
+            proptest::option::of(0.01f64..10_000.0),
+            proptest::option::of(0.01f64..10_000.0),
+            (
+                proptest::option::of(0.01f64..100.0),
+                proptest::option::of(0.01f64..10_000.0),
+                proptest::option::of(0.01f64..100.0),
+            ),
+            prop_oneof![
+                Just(TimeInForce::Day),
+                Just(TimeInForce::Gtc),
+                Just(TimeInForce::Ioc),
+                Just(TimeInForce::Gtd),
+            ],
+            any::<(bool, bool)>(),
+            proptest::option::of("[0-9]{8} [0-9:]{8}"),
+            (
+                proptest::option::of(1..i32::MAX),
+                any::<bool>(),
+                any::<bool>(),
+            ),
+            (
+                proptest::option::of("[A-Za-z0-9_]{1,12}"),
+                proptest::option::of(prop_oneof![
+                    Just(OcaType::CancelWithBlock),
+                    Just(OcaType::ReduceWithBlock),
+                    Just(OcaType::ReduceNonBlock),
+                ]),
+                proptest::option::of(algo()),
+                proptest::option::of("[A-Za-z0-9_-]{1,24}"),
+                proptest::option::of("[A-Z]{1,2}[0-9]{5,7}"),
+            ),
+        )
+            .prop_map(
+                |(
+                    action,
+                    qty,
+                    order_type,
+                    lmt,
+                    aux,
+                    (trailing_percent, trail_stop_price, lmt_price_offset),
+                    tif,
+                    (outside_rth, hidden),
+                    gtd,
+                    (parent_id, transmit, what_if),
+                    (oca_group, oca_type, algo, order_ref, account),
+                )| {
+                    let mut order = Order::new(action, qty as f64, order_type).with_tif(tif);
+                    order.lmt_price = lmt;
+                    order.aux_price = aux;
+                    order.trailing_percent = trailing_percent;
+                    order.trail_stop_price = trail_stop_price;
+                    order.lmt_price_offset = lmt_price_offset;
+                    order.outside_rth = outside_rth;
+                    order.hidden = hidden;
+                    order.good_till_date = gtd;
+                    order.parent_id = parent_id;
+                    order.transmit = transmit;
+                    order.what_if = what_if;
+                    order.order_ref = order_ref;
+                    order.account = account;
+                    order.oca_group = oca_group;
+                    order.oca_type = oca_type;
+                    if let Some((strategy, params)) = algo {
+                        order = order.with_algo(strategy, params);
+                    }
+                    order
+                },
+            )
+    }
+
+    fn algo() -> impl Strategy<Value = (AlgoStrategy, AlgoParams)> {
+        prop_oneof![
+            prop_oneof![
+                Just(AdaptivePriority::Urgent),
+                Just(AdaptivePriority::Normal),
+                Just(AdaptivePriority::Patient),
+            ]
+            .prop_map(|priority| (
+                AlgoStrategy::Adaptive,
+                AlgoParams {
+                    adaptive_priority: Some(priority),
+                    ..Default::default()
+                }
+            )),
+            (
+                0.01f64..0.5,
+                proptest::option::of("[0-9]{2}:[0-9]{2}:[0-9]{2}"),
+                proptest::option::of(any::<bool>()),
+            )
+                .prop_map(|(max_pct_vol, start_time, no_take_liq)| (
+                    AlgoStrategy::Vwap,
+                    AlgoParams {
+                        max_pct_vol: Some(max_pct_vol),
+                        start_time,
+                        no_take_liq,
+                        ..Default::default()
+                    }
+                )),
+            (
+                prop_oneof![Just(RiskAversion::GetDone), Just(RiskAversion::Passive),],
+                any::<bool>(),
+            )
+                .prop_map(|(risk_aversion, force_completion)| (
+                    AlgoStrategy::ArrivalPx,
+                    AlgoParams {
+                        risk_aversion: Some(risk_aversion),
+                        force_completion: Some(force_completion),
+                        ..Default::default()
+                    }
+                )),
+        ]
+    }
+
+    fn outgoing_message() -> impl Strategy<Value = OutgoingMessage> {
+        prop_oneof![
+            (any::<i32>(), text()).prop_map(|(client_id, caps)| OutgoingMessage::StartApi {
+                client_id,
+                optional_capabilities: caps,
+            }),
+            (any::<i32>(), contract(), any::<bool>()).prop_map(|(req_id, contract, snapshot)| {
+                OutgoingMessage::ReqMktData {
+                    req_id,
+                    contract,
+                    generic_ticks: "233".into(),
+                    snapshot,
+                }
+            }),
+            any::<i32>().prop_map(|req_id| OutgoingMessage::CancelMktData { req_id }),
+            (any::<i32>(), contract(), order()).prop_map(|(order_id, contract, order)| {
+                OutgoingMessage::PlaceOrder {
+                    order_id,
+                    contract,
+                    order: Box::new(order),
+                }
+            }),
+            any::<i32>().prop_map(|order_id| OutgoingMessage::CancelOrder { order_id }),
+            Just(OutgoingMessage::ReqOpenOrders),
+            any::<i32>().prop_map(|req_id| OutgoingMessage::ReqExecutions { req_id }),
+            any::<i32>().prop_map(|num_ids| OutgoingMessage::ReqIds { num_ids }),
+            Just(OutgoingMessage::ReqAllOpenOrders),
+            any::<bool>().prop_map(|api_only| OutgoingMessage::ReqCompletedOrders { api_only }),
+            (any::<i32>(), contract(), text(), any::<bool>()).prop_map(
+                |(req_id, contract, end, use_rth)| OutgoingMessage::ReqHistoricalData {
+                    req_id,
+                    contract,
+                    end_date_time: end,
+                    duration: "1 D".into(),
+                    bar_size: "1 min".into(),
+                    what_to_show: "TRADES".into(),
+                    use_rth,
+                }
+            ),
+            Just(OutgoingMessage::ReqCurrentTime),
+            Just(OutgoingMessage::ReqGlobalCancel),
+            Just(OutgoingMessage::ReqPositions),
+            (any::<i32>(), text(), text()).prop_map(|(req_id, group, tags)| {
+                OutgoingMessage::ReqAccountSummary {
+                    req_id,
+                    group,
+                    tags,
+                }
+            }),
+            any::<i32>().prop_map(|req_id| OutgoingMessage::CancelAccountSummary { req_id }),
+            Just(OutgoingMessage::CancelPositions),
+        ]
+    }
+
+    fn bar() -> impl Strategy<Value = BarData> {
+        (
+            // Epoch seconds; eight digits would read as a YYYYMMDD date
+            1_000_000_000i64..4_000_000_000,
+            [-1e9f64..1e9, -1e9f64..1e9, -1e9f64..1e9, -1e9f64..1e9],
+            any::<i64>(),
+            proptest::option::of(-1e9f64..1e9),
+            proptest::option::of(any::<i32>()),
+        )
+            .prop_map(
+                |(ts, [open, high, low, close], volume, wap, count)| BarData {
+                    date: Utc.timestamp_opt(ts, 0).unwrap(),
+                    open,
+                    high,
+                    low,
+                    close,
+                    volume,
+                    wap,
+                    count,
+                },
+            )
+    }
+
+    fn incoming_message() -> impl Strategy<Value = IncomingMessage> {
+        let finite = -1e12f64..1e12;
+        prop_oneof![
+            (
+                any::<i32>(),
+                any::<i32>(),
+                finite.clone(),
+                0f64..1e9,
+                any::<i32>()
+            )
+                .prop_map(|(req_id, tick_type, price, size, attr_mask)| {
+                    IncomingMessage::TickPrice {
+                        req_id,
+                        tick_type,
+                        price,
+                        size,
+                        attr_mask,
+                    }
+                }),
+            (any::<i32>(), any::<i32>(), 0f64..1e9).prop_map(|(req_id, tick_type, size)| {
+                IncomingMessage::TickSize {
+                    req_id,
+                    tick_type,
+                    size,
+                }
+            }),
+            (any::<i32>(), "[A-Za-z]{1,12}", finite.clone(), any::<i32>()).prop_map(
+                |(order_id, status, filled, perm_id)| {
+                    IncomingMessage::OrderStatus(OrderStatusUpdate {
+                        order_id,
+                        status,
+                        filled,
+                        remaining: 0.0,
+                        avg_fill_price: filled / 3.0,
+                        perm_id,
+                        parent_id: 0,
+                        last_fill_price: 1.5,
+                        client_id: 1,
+                        why_held: String::new(),
+                        mkt_cap_price: 0.0,
+                    })
+                }
+            ),
+            (any::<i32>(), any::<i32>(), text())
+                .prop_map(|(id, code, message)| { IncomingMessage::Error { id, code, message } }),
+            (
+                (any::<i32>(), any::<i32>()),
+                contract(),
+                order(),
+                "[A-Za-z]{1,12}",
+                (
+                    proptest::option::of(finite.clone()),
+                    proptest::option::of(finite.clone()),
+                    proptest::option::of("[A-Z]{3}"),
+                    proptest::option::of("[A-Za-z ]{1,40}"),
+                ),
+            )
+                .prop_map(
+                    |(
+                        (order_id, client_id),
+                        contract,
+                        mut order,
+                        status,
+                        (init_margin_change, commission, commission_currency, warning_text),
+                    )| IncomingMessage::OpenOrder {
+                        order_id,
+                        contract,
+                        // Reports carry the placing client but not `transmit`
+                        order: {
+                            order.client_id = Some(client_id);
+                            order.transmit = true;
+                            order
+                        },
+                        state: OrderState {
+                            status,
+                            preview: Box::new(OrderPreview {
+                                init_margin_change,
+                                commission,
+                                commission_currency,
+                                warning_text,
+                                ..Default::default()
+                            }),
+                        },
+                    }
+                ),
+            any::<i32>().prop_map(|order_id| IncomingMessage::NextValidId { order_id }),
+            (
+                any::<i32>(),
+                any::<i32>(),
+                contract(),
+                text(),
+                finite.clone()
+            )
+                .prop_map(|(req_id, order_id, contract, exec_id, price)| {
+                    IncomingMessage::ExecutionData {
+                        req_id,
+                        order_id,
+                        contract,
+                        execution: Execution {
+                            exec_id,
+                            time: "20240101 10:00:00".into(),
+                            account: "DU1".into(),
+                            exchange: "NYSE".into(),
+                            side: "BOT".into(),
+                            shares: 10.0,
+                            price,
+                            perm_id: 1,
+                            client_id: 1,
+                            cum_qty: 10.0,
+                            avg_price: price,
+                            order_ref: "mcp-1".into(),
+                        },
+                    }
+                }),
+            proptest::collection::vec("[A-Z]{2}[0-9]{4,7}", 0..4)
+                .prop_map(|accounts| IncomingMessage::ManagedAccounts { accounts }),
+            (any::<i32>(), proptest::collection::vec(bar(), 0..5)).prop_map(|(req_id, bars)| {
+                IncomingMessage::HistoricalData {
+                    req_id,
+                    start: "20240101".into(),
+                    end: "20240102".into(),
+                    bars,
+                }
+            }),
+            any::<i64>().prop_map(|time| IncomingMessage::CurrentTime { time }),
+            Just(IncomingMessage::OpenOrderEnd),
+            (contract(), order(), any::<i32>(), "[A-Za-z]{1,12}").prop_map(
+                |(contract, mut order, perm_id, status)| {
+                    // Completed orders carry neither the placing client nor
+                    // the parent's order ID, and are never what-if
+                    order.transmit = true;
+                    order.parent_id = None;
+                    order.what_if = false;
+                    IncomingMessage::CompletedOrder {
+                        contract,
+                        order,
+                        perm_id,
+                        status,
+                    }
+                }
+            ),
+            Just(IncomingMessage::CompletedOrdersEnd),
+            any::<i32>().prop_map(|req_id| IncomingMessage::ExecutionDataEnd { req_id }),
+            any::<i32>().prop_map(|req_id| IncomingMessage::TickSnapshotEnd { req_id }),
+            (text(), finite.clone(), proptest::option::of(finite.clone())).prop_map(
+                |(exec_id, commission, realized_pnl)| {
+                    IncomingMessage::CommissionReport(CommissionReport {
+                        exec_id,
+                        commission,
+                        currency: "USD".into(),
+                        realized_pnl,
+                    })
+                }
+            ),
+            (text(), contract(), finite.clone(), finite).prop_map(
+                |(account, contract, position, avg_cost)| IncomingMessage::Position {
+                    account,
+                    contract,
+                    position,
+                    avg_cost,
+                }
+            ),
+            Just(IncomingMessage::PositionEnd),
+            (any::<i32>(), text(), text(), text()).prop_map(|(req_id, account, tag, value)| {
+                IncomingMessage::AccountSummary {
+                    req_id,
+                    account,
+                    tag,
+                    value,
+                    currency: "USD".into(),
+                }
+            }),
+            any::<i32>().prop_map(|req_id| IncomingMessage::AccountSummaryEnd { req_id }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_outgoing_round_trip(messages in proptest::collection::vec(outgoing_message(), 1..8)) {
+            let mut all = vec![handshake()];
+            all.extend(messages);
+
+            let mut buf = encode_outgoing(&all);
+            let decoded = decode_all(&mut GatewayCodec::new(), &mut buf);
+            prop_assert_eq!(decoded, all);
+            prop_assert!(buf.is_empty());
+        }
+
+        #[test]
+        fn prop_incoming_round_trip(
+            messages in proptest::collection::vec(incoming_message(), 1..8),
+            split in any::<prop::sample::Index>(),
+        ) {
+            let mut all = vec![IncomingMessage::ServerVersion {
+                version: MAX_CLIENT_VERSION,
+                connection_time: "20240101 12:00:00 EST".into(),
+            }];
+            all.extend(messages);
+
+            let mut encoder = GatewayCodec::new();
+            let mut bytes = BytesMut::new();
+            for message in &all {
+                encoder.encode(message.clone(), &mut bytes).unwrap();
+            }
+
+            // Deliver the stream in two arbitrary pieces
+            let at = split.index(bytes.len() + 1);
+            let mut codec = TwsCodec::new();
+            let mut buf = BytesMut::from(&bytes[..at]);
+            let mut decoded = decode_all(&mut codec, &mut buf);
+            buf.extend_from_slice(&bytes[at..]);
+            decoded.extend(decode_all(&mut codec, &mut buf));
+
+            prop_assert_eq!(decoded, all);
+            prop_assert!(buf.is_empty());
+        }
+    }
+}
@@ -1,17 +1,544 @@
 /// Connection management utilities
-use crate::error::Result;
+///
+/// Implements the TWS socket handshake: the `API\0` prefix, the client
+/// version range, `startApi`, and waiting for `nextValidId` /
+/// `managedAccounts` before the connection is handed to the client, plus
+/// the `ConnectionManager` that keeps a connection alive.
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
+use futures::{SinkExt, StreamExt};
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, broadcast::error::RecvError, watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::{debug, info, warn};
+
+use super::codec::{
+    IncomingMessage, OutgoingMessage, TwsCodec, MAX_CLIENT_VERSION, MIN_CLIENT_VERSION,
+};
+use crate::{
+    config::{IBKRConfig, ReconnectConfig},
+    error::{IBKRMCPError, Result},
+};
+
+/// Capacity of the incoming message fan-out
+const INCOMING_CAPACITY: usize = 1024;
+
+/// An established, handshaken TWS API session
+pub struct Connection {
+    writer: Mutex<FramedWrite<OwnedWriteHalf, TwsCodec>>,
+    incoming: broadcast::Receiver<IncomingMessage>,
+    alive: Arc<AtomicBool>,
+    reader: JoinHandle<()>,
+    server_version: i32,
+    connection_time: String,
+    next_valid_id: i32,
+    managed_accounts: Vec<String>,
+}
+
+impl Connection {
+    /// Connect to TWS / IB Gateway and complete the API handshake
+    pub async fn connect(config: &IBKRConfig) -> Result<Self> {
+        let timeout = Duration::from_secs(config.timeout);
+        let addr = format!("{}:{}", config.host, config.port);
+
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(&addr))
+            .await
+            .map_err(|_| IBKRMCPError::Timeout)?
+            .map_err(|e| IBKRMCPError::Connection(format!("{}: {}", addr, e)))?;
+        stream.set_nodelay(true)?;
+
+        tokio::time::timeout(timeout, Self::handshake(stream, config.client_id))
+            .await
+            .map_err(|_| IBKRMCPError::Timeout)?
+    }
+
+    async fn handshake(stream: TcpStream, client_id: i32) -> Result<Self> {
+        let (read_half, write_half) = stream.into_split();
+        let mut reader = FramedRead::new(read_half, TwsCodec::new());
+        let mut writer = FramedWrite::new(write_half, TwsCodec::new());
+
+        writer
+            .send(OutgoingMessage::Handshake {
+                min_version: MIN_CLIENT_VERSION,
+                max_version: MAX_CLIENT_VERSION,
+            })
+            .await?;
+
+        let (server_version, connection_time) = match next_message(&mut reader).await? {
+            IncomingMessage::ServerVersion {
+                version,
+                connection_time,
+            } => (version, connection_time),
+            other => {
+                return Err(IBKRMCPError::Connection(format!(
+                    "Invalid handshake reply: {:?}",
+                    other
+                )))
+            }
+        };
+        debug!(
+            "TWS server version {} (connection time {})",
+            server_version, connection_time
+        );
+
+        let mut writer = writer.map_encoder(|_| TwsCodec::with_server_version(server_version));
+        writer
+            .send(OutgoingMessage::StartApi {
+                client_id,
+                optional_capabilities: String::new(),
+            })
+            .await?;
+
+        // TWS answers startApi with nextValidId and managedAccounts; farm
+        // status notices may be interleaved and are only logged.
+        let mut next_valid_id = None;
+        let mut managed_accounts = None;
+        while next_valid_id.is_none() || managed_accounts.is_none() {
+            match next_message(&mut reader).await? {
+                IncomingMessage::NextValidId { order_id } => next_valid_id = Some(order_id),
+                IncomingMessage::ManagedAccounts { accounts } => managed_accounts = Some(accounts),
+                IncomingMessage::Error { code, message, .. } => {
+                    if is_informational(code) {
+                        debug!("TWS notice {}: {}", code, message);
+                    } else {
+                        return Err(IBKRMCPError::Connection(format!(
+                            "TWS error {} during handshake: {}",
+                            code, message
+                        )));
+                    }
+                }
+                other => debug!("Ignoring {:?} during handshake", other),
+            }
+        }
+
+        // The reader task owns the only sender, so subscribers observe
+        // `RecvError::Closed` once the socket goes away.
+        let (sender, incoming) = broadcast::channel(INCOMING_CAPACITY);
+        let alive = Arc::new(AtomicBool::new(true));
+        let reader = tokio::spawn(read_loop(reader, sender, Arc::clone(&alive)));
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+            incoming,
+            alive,
+            reader,
+            server_version,
+            connection_time,
+            next_valid_id: next_valid_id.unwrap_or_default(),
+            managed_accounts: managed_accounts.unwrap_or_default(),
+        })
+    }
+
+    /// Whether the socket is still open
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    pub fn server_version(&self) -> i32 {
+        self.server_version
+    }
+
+    pub fn connection_time(&self) -> &str {
+        &self.connection_time
+    }
+
+    /// First order ID the gateway will accept from this client
+    pub fn next_valid_id(&self) -> i32 {
+        self.next_valid_id
+    }
+
+    /// Accounts visible to this login
+    pub fn managed_accounts(&self) -> &[String] {
+        &self.managed_accounts
+    }
+
+    /// Receive every message decoded after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<IncomingMessage> {
+        self.incoming.resubscribe()
+    }
+
+    /// Send one message to the gateway
+    pub async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        if !self.is_alive() {
+            return Err(IBKRMCPError::NotConnected);
+        }
+        self.writer.lock().await.send(message).await
+    }
+
+    /// Close the socket and stop the reader task
+    pub async fn close(&self) {
+        self.alive.store(false, Ordering::SeqCst);
+        self.reader.abort();
+        let _ = self.writer.lock().await.close().await;
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn next_message(reader: &mut FramedRead<OwnedReadHalf, TwsCodec>) -> Result<IncomingMessage> {
+    reader
+        .next()
+        .await
+        .unwrap_or_else(|| Err(IBKRMCPError::Connection("Connection closed by TWS".into())))
+}
+
+async fn read_loop(
+    mut reader: FramedRead<OwnedReadHalf, TwsCodec>,
+    incoming: broadcast::Sender<IncomingMessage>,
+    alive: Arc<AtomicBool>,
+) {
+    while let Some(message) = reader.next().await {
+        match message {
+            Ok(IncomingMessage::Error { id, code, message }) if is_informational(code) => {
+                debug!("TWS notice {} (id {}): {}", code, id, message);
+            }
+            Ok(message) => {
+                // No subscribers is normal between requests
+                let _ = incoming.send(message);
+            }
+            Err(e) => {
+                warn!("TWS connection error: {}", e);
+                break;
+            }
+        }
+    }
+    info!("TWS connection closed");
+    alive.store(false, Ordering::SeqCst);
+}
+
+/// Farm status and similar notices that TWS reports through the error channel
+fn is_informational(code: i32) -> bool {
+    matches!(code, 2104 | 2106 | 2107 | 2108 | 2119 | 2158)
+}
+
+/// Connection health as reported to MCP clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// Establishing or re-establishing the socket
+    Connecting,
+    Connected,
+    /// Socket is up but TWS lost its upstream link or missed heartbeats
+    Degraded,
+    Disconnected,
+}
+
+impl ConnectionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Degraded => "degraded",
+            ConnectionState::Disconnected => "disconnected",
+        }
+    }
+}
+
+/// Callback invoked for every message on every connection
+pub type Observer = Arc<dyn Fn(&IncomingMessage) + Send + Sync>;
+
+/// Owns the live connection and keeps it alive
+///
+/// Once started, a supervisor task sends `reqCurrentTime` heartbeats,
+/// tracks TWS connectivity notices (1100/1101/1102), and replaces the
+/// connection with jittered exponential backoff when the socket is lost.
+/// After every reconnect open orders are re-requested. Market data is only
+/// ever requested as one-shot snapshots, so there are no streams to replay;
+/// a snapshot cut off by the reconnect fails and is retried by its caller.
 pub struct ConnectionManager {
-    // Connection pool and management logic
+    config: IBKRConfig,
+    connection: RwLock<Option<Arc<Connection>>>,
+    state: watch::Sender<ConnectionState>,
+    reconnects: AtomicU32,
+    supervisor: std::sync::Mutex<Option<JoinHandle<()>>>,
+    observers: Arc<std::sync::Mutex<Vec<Observer>>>,
 }
 
 impl ConnectionManager {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(config: IBKRConfig) -> Self {
+        Self {
+            config,
+            connection: RwLock::new(None),
+            state: watch::channel(ConnectionState::Disconnected).0,
+            reconnects: AtomicU32::new(0),
+            supervisor: std::sync::Mutex::new(None),
+            observers: Arc::new(std::sync::Mutex::new(Vec::new())),
+        }
     }
 
-    pub async fn maintain_connection(&self) -> Result<()> {
-        // Implement connection health check and auto-reconnect
+    /// Connect once and start supervising the connection
+    pub async fn start(self: &Arc<Self>) -> Result<()> {
+        self.stop().await;
+        self.set_state(ConnectionState::Connecting);
+
+        let connection = match Connection::connect(&self.config).await {
+            Ok(connection) => Arc::new(connection),
+            Err(e) => {
+                self.set_state(ConnectionState::Disconnected);
+                return Err(e);
+            }
+        };
+        self.dispatch(&connection);
+        *self.connection.write().await = Some(Arc::clone(&connection));
+        self.restore(&connection).await?;
+        self.set_state(ConnectionState::Connected);
+
+        if self.config.reconnect.enabled {
+            let supervisor = tokio::spawn(Arc::clone(self).supervise());
+            *self.supervisor.lock().unwrap() = Some(supervisor);
+        }
         Ok(())
     }
+
+    /// Stop supervising and close the connection
+    pub async fn stop(&self) {
+        if let Some(supervisor) = self.supervisor.lock().unwrap().take() {
+            supervisor.abort();
+        }
+        if let Some(connection) = self.connection.write().await.take() {
+            connection.close().await;
+        }
+        self.set_state(ConnectionState::Disconnected);
+    }
+
+    /// Drop the current connection and connect again immediately
+    pub async fn reconnect(self: &Arc<Self>) -> Result<()> {
+        warn!("Reconnecting to TWS on request");
+        self.reconnects.fetch_add(1, Ordering::SeqCst);
+        self.start().await
+    }
+
+    /// The open connection, if any
+    pub async fn current(&self) -> Result<Arc<Connection>> {
+        self.connection
+            .read()
+            .await
+            .as_ref()
+            .filter(|c| c.is_alive())
+            .cloned()
+            .ok_or(IBKRMCPError::NotConnected)
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Observe state transitions
+    pub fn watch_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// Reconnects performed since this manager was created
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects.load(Ordering::SeqCst)
+    }
+
+    /// Register a callback for every incoming message, across reconnects
+    pub fn observe(&self, observer: Observer) {
+        self.observers.lock().unwrap().push(observer);
+    }
+
+    /// Feed `connection`'s messages to the observers until it closes
+    fn dispatch(&self, connection: &Connection) {
+        let mut incoming = connection.subscribe();
+        let observers = Arc::clone(&self.observers);
+        tokio::spawn(async move {
+            loop {
+                match incoming.recv().await {
+                    Ok(message) => {
+                        for observer in observers.lock().unwrap().iter() {
+                            observer(&message);
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Observers missed {} messages", skipped)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.state.send_if_modified(|current| {
+            if *current == state {
+                return false;
+            }
+            info!("TWS connection {} -> {}", current.as_str(), state.as_str());
+            *current = state;
+            true
+        });
+    }
+
+    /// Refresh open orders on a new or recovered connection
+    async fn restore(&self, connection: &Connection) -> Result<()> {
+        connection.send(OutgoingMessage::ReqAllOpenOrders).await
+    }
+
+    async fn supervise(self: Arc<Self>) {
+        loop {
+            let Ok(connection) = self.current().await else {
+                self.reconnect_with_backoff().await;
+                continue;
+            };
+            self.monitor(&connection).await;
+            connection.close().await;
+            warn!("Lost connection to TWS");
+            self.reconnect_with_backoff().await;
+        }
+    }
+
+    /// Watch `connection` until it is closed or stops answering heartbeats
+    async fn monitor(&self, connection: &Connection) {
+        let policy = &self.config.reconnect;
+        let mut incoming = connection.subscribe();
+        let mut heartbeat =
+            tokio::time::interval(Duration::from_millis(policy.heartbeat_interval_ms.max(1)));
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await;
+
+        let mut awaiting_heartbeat = false;
+        let mut missed_heartbeats = 0;
+        let mut upstream_lost = false;
+
+        loop {
+            tokio::select! {
+                message = incoming.recv() => match message {
+                    Ok(IncomingMessage::CurrentTime { .. }) => {
+                        awaiting_heartbeat = false;
+                        missed_heartbeats = 0;
+                        if !upstream_lost {
+                            self.set_state(ConnectionState::Connected);
+                        }
+                    }
+                    Ok(IncomingMessage::Error { code: 1100, message, .. }) => {
+                        warn!("TWS lost connectivity: {}", message);
+                        upstream_lost = true;
+                        self.set_state(ConnectionState::Degraded);
+                    }
+                    Ok(IncomingMessage::Error { code: 1101, message, .. }) => {
+                        // Connectivity is back but data was lost; order
+                        // updates may have been missed
+                        info!("TWS connectivity restored, data lost: {}", message);
+                        upstream_lost = false;
+                        if self.restore(connection).await.is_err() {
+                            return;
+                        }
+                        self.set_state(ConnectionState::Connected);
+                    }
+                    Ok(IncomingMessage::Error { code: 1102, message, .. }) => {
+                        info!("TWS connectivity restored: {}", message);
+                        upstream_lost = false;
+                        self.set_state(ConnectionState::Connected);
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+                _ = heartbeat.tick() => {
+                    if awaiting_heartbeat {
+                        missed_heartbeats += 1;
+                        warn!("Missed TWS heartbeat ({} in a row)", missed_heartbeats);
+                        self.set_state(ConnectionState::Degraded);
+                        if missed_heartbeats >= policy.max_missed_heartbeats {
+                            return;
+                        }
+                    }
+                    if connection.send(OutgoingMessage::ReqCurrentTime).await.is_err() {
+                        return;
+                    }
+                    awaiting_heartbeat = true;
+                }
+            }
+        }
+    }
+
+    /// Retry until a new connection is established
+    async fn reconnect_with_backoff(&self) {
+        self.set_state(ConnectionState::Connecting);
+
+        let mut attempt = 0;
+        loop {
+            let delay = backoff_delay(&self.config.reconnect, attempt, &mut rand::thread_rng());
+            debug!("Reconnecting to TWS in {:?}", delay);
+            tokio::time::sleep(delay).await;
+
+            match Connection::connect(&self.config).await {
+                Ok(connection) => {
+                    let connection = Arc::new(connection);
+                    self.dispatch(&connection);
+                    *self.connection.write().await = Some(Arc::clone(&connection));
+                    self.reconnects.fetch_add(1, Ordering::SeqCst);
+                    if let Err(e) = self.restore(&connection).await {
+                        warn!("Failed to refresh open orders: {}", e);
+                        continue;
+                    }
+                    info!("Reconnected to TWS after {} attempts", attempt + 1);
+                    self.set_state(ConnectionState::Connected);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Reconnect attempt {} failed: {}", attempt + 1, e);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionManager {
+    fn drop(&mut self) {
+        if let Some(supervisor) = self.supervisor.lock().unwrap().take() {
+            supervisor.abort();
+        }
+    }
+}
+
+/// Exponential backoff capped at `max_backoff_ms`, with the upper half
+/// randomised so clients restarted together do not reconnect in lockstep
+fn backoff_delay(policy: &ReconnectConfig, attempt: u32, rng: &mut impl Rng) -> Duration {
+    let ceiling = policy
+        .initial_backoff_ms
+        .saturating_mul(1 << attempt.min(16))
+        .min(policy.max_backoff_ms);
+    Duration::from_millis(rng.gen_range(ceiling / 2..=ceiling))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = ReconnectConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            ..Default::default()
+        };
+        let mut rng = rand::thread_rng();
+
+        for attempt in 0..32 {
+            let ceiling = (100u64 << attempt.min(16)).min(1_000);
+            let delay = backoff_delay(&policy, attempt, &mut rng).as_millis() as u64;
+            assert!(
+                (ceiling / 2..=ceiling).contains(&delay),
+                "attempt {} delay {}",
+                attempt,
+                delay
+            );
+        }
+    }
 }
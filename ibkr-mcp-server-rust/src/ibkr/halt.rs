@@ -0,0 +1,145 @@
+/// Trading kill switch
+///
+/// While a halt is engaged `IBKRClient` refuses every new or amended order.
+/// The halt is written to `<state_dir>/trading_halt.json`, so a server
+/// restarted mid-incident comes back halted until trading is resumed
+/// explicitly.
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{error::Result, persist};
+
+/// Why and since when trading is halted
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TradingHalt {
+    pub reason: String,
+    pub halted_at: DateTime<Utc>,
+}
+
+/// Outcome of engaging the kill switch
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct HaltReport {
+    pub halt: TradingHalt,
+    /// Working orders cancelled with `reqGlobalCancel`
+    pub cancelled_orders: Vec<i32>,
+    /// Market orders sent to close positions
+    pub flatten_orders: Vec<i32>,
+    /// Cancel and flatten steps that failed; the halt stays engaged
+    pub errors: Vec<String>,
+}
+
+pub struct HaltSwitch {
+    path: Option<PathBuf>,
+    halt: Mutex<Option<TradingHalt>>,
+}
+
+impl HaltSwitch {
+    /// Switch persisting to `<state_dir>/trading_halt.json`, or kept in
+    /// memory only when `state_dir` is `None`. A halt file that cannot be
+    /// read keeps trading halted.
+    pub fn new(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|dir| dir.join("trading_halt.json"));
+        let halt = path.as_deref().and_then(load);
+        if let Some(halt) = &halt {
+            warn!(
+                "Trading is halted since {}: {}",
+                halt.halted_at.to_rfc3339(),
+                halt.reason
+            );
+        }
+        Self {
+            path,
+            halt: Mutex::new(halt),
+        }
+    }
+
+    pub fn current(&self) -> Option<TradingHalt> {
+        self.halt.lock().unwrap().clone()
+    }
+
+    /// Halt trading; an existing halt keeps its original reason and time
+    pub fn engage(&self, reason: &str) -> Result<TradingHalt> {
+        let mut halt = self.halt.lock().unwrap();
+        if let Some(existing) = halt.as_ref() {
+            return Ok(existing.clone());
+        }
+
+        let engaged = TradingHalt {
+            reason: reason.to_string(),
+            halted_at: Utc::now(),
+        };
+        if let Some(path) = &self.path {
+            persist::write_atomically(path, &serde_json::to_vec_pretty(&engaged)?)?;
+        }
+        warn!("Trading halted: {}", reason);
+        *halt = Some(engaged.clone());
+        Ok(engaged)
+    }
+
+    /// Lift the halt, returning it if one was engaged
+    pub fn release(&self) -> Result<Option<TradingHalt>> {
+        let mut halt = self.halt.lock().unwrap();
+        if let Some(path) = &self.path {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let released = halt.take();
+        if released.is_some() {
+            info!("Trading resumed");
+        }
+        Ok(released)
+    }
+}
+
+fn load(path: &Path) -> Option<TradingHalt> {
+    let bytes = match persist::read(path) {
+        Ok(bytes) => bytes?,
+        Err(e) => return Some(unreadable(path, &e)),
+    };
+    Some(serde_json::from_slice(&bytes).unwrap_or_else(|e| unreadable(path, &e)))
+}
+
+fn unreadable(path: &Path, error: &dyn std::fmt::Display) -> TradingHalt {
+    TradingHalt {
+        reason: format!("Halt state {} is unreadable: {}", path.display(), error),
+        halted_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halt_survives_restart_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let switch = HaltSwitch::new(Some(dir.path()));
+        assert_eq!(switch.current(), None);
+
+        let halt = switch.engage("runaway strategy").unwrap();
+        // Re-engaging keeps the original halt
+        assert_eq!(switch.engage("again").unwrap(), halt);
+
+        let restarted = HaltSwitch::new(Some(dir.path()));
+        assert_eq!(restarted.current(), Some(halt.clone()));
+        assert_eq!(restarted.release().unwrap(), Some(halt));
+        assert_eq!(HaltSwitch::new(Some(dir.path())).current(), None);
+    }
+
+    #[test]
+    fn corrupt_state_keeps_trading_halted() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("trading_halt.json"), b"{not json").unwrap();
+
+        let halt = HaltSwitch::new(Some(dir.path())).current().unwrap();
+        assert!(halt.reason.contains("unreadable"));
+    }
+}
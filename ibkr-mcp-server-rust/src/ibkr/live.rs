@@ -0,0 +1,728 @@
+/// Live TWS / IB Gateway backend
+///
+/// Each operation subscribes to the connection's incoming messages, sends
+/// its request, and folds replies until the matching end marker arrives or
+/// `IBKRConfig.timeout` elapses.
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::backend::BrokerBackend;
+use super::codec::{tick, IncomingMessage, OutgoingMessage};
+use super::connection::{Connection, ConnectionManager, ConnectionState};
+use super::order_book::{is_warning, OrderBook, OrderRefMatch, TrackedOrder};
+use super::order_ids::OrderIdAllocator;
+use crate::{
+    config::{BackendKind, IBKRConfig},
+    error::{IBKRMCPError, Result},
+    models::{
+        AccountValue, BarData, Contract, MarketSnapshot, Order, OrderAction, OrderPreview,
+        OrderStatus, Position,
+    },
+};
+
+/// First request ID. TWS reports errors for requests and orders through one
+/// ID space, so request IDs start far above any order ID in use.
+const FIRST_REQ_ID: i32 = 100_000_000;
+
+/// Tags requested by `get_account_summary`
+const ACCOUNT_SUMMARY_TAGS: &str =
+    "NetLiquidation,TotalCashValue,GrossPositionValue,BuyingPower,AvailableFunds,ExcessLiquidity";
+
+pub struct LiveBackend {
+    config: IBKRConfig,
+    manager: Arc<ConnectionManager>,
+    next_req_id: AtomicI32,
+    order_ids: OrderIdAllocator,
+    order_book: Arc<OrderBook>,
+    /// reqPositions, reqAllOpenOrders and reqCompletedOrders carry no
+    /// request ID, so concurrent requests of one kind would read each
+    /// other's rows and end markers; each kind runs one at a time
+    positions_request: Mutex<()>,
+    open_orders_request: Mutex<()>,
+    completed_orders_request: Mutex<()>,
+}
+
+impl LiveBackend {
+    pub fn new(config: IBKRConfig) -> Self {
+        let manager = Arc::new(ConnectionManager::new(config.clone()));
+        let order_book = Arc::new(OrderBook::new());
+        let book = Arc::clone(&order_book);
+        manager.observe(Arc::new(move |message| book.apply(message)));
+
+        Self {
+            manager,
+            order_book,
+            order_ids: OrderIdAllocator::new(
+                config.state_dir.as_deref().map(Path::new),
+                config.client_id,
+            ),
+            next_req_id: AtomicI32::new(FIRST_REQ_ID),
+            positions_request: Mutex::new(()),
+            open_orders_request: Mutex::new(()),
+            completed_orders_request: Mutex::new(()),
+            config,
+        }
+    }
+
+    /// Orders seen on any connection of this backend
+    pub fn order_book(&self) -> &Arc<OrderBook> {
+        &self.order_book
+    }
+
+    /// Supervisor of the underlying TWS connection
+    pub fn manager(&self) -> &Arc<ConnectionManager> {
+        &self.manager
+    }
+
+    async fn connection(&self) -> Result<Arc<Connection>> {
+        let connection = self.manager.current().await?;
+        // A reconnect may hand out a higher nextValidId
+        self.order_ids
+            .observe_next_valid_id(connection.next_valid_id())?;
+        Ok(connection)
+    }
+
+    fn next_req_id(&self) -> i32 {
+        self.next_req_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Ask the gateway for a fresh `nextValidId` via `reqIds`
+    pub async fn request_ids(&self) -> Result<i32> {
+        let next_valid_id = self
+            .request(
+                OutgoingMessage::ReqIds { num_ids: 1 },
+                0,
+                |next_valid_id, message| match message {
+                    IncomingMessage::NextValidId { order_id } => {
+                        *next_valid_id = order_id;
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                },
+            )
+            .await?;
+
+        self.order_ids.observe_next_valid_id(next_valid_id)?;
+        Ok(self.order_ids.peek())
+    }
+
+    /// Transmit one order and wait for TWS to acknowledge or reject it
+    async fn submit_order(
+        &self,
+        order_id: i32,
+        contract: &Contract,
+        order: &Order,
+    ) -> Result<Submission> {
+        info!("Submitting order {} for {}", order_id, contract.symbol);
+        self.order_book.track_submission(order_id, contract, order);
+
+        // TWS holds untransmitted orders without acknowledging them; they
+        // are confirmed once a later order in the group is transmitted
+        if !order.transmit {
+            self.connection()
+                .await?
+                .send(OutgoingMessage::PlaceOrder {
+                    order_id,
+                    contract: contract.clone(),
+                    order: Box::new(order.clone()),
+                })
+                .await?;
+            return Ok(Submission::Accepted);
+        }
+
+        self.request(
+            OutgoingMessage::PlaceOrder {
+                order_id,
+                contract: contract.clone(),
+                order: Box::new(order.clone()),
+            },
+            Submission::Accepted,
+            |submission, message| {
+                self.order_book.apply(&message);
+                match message {
+                    IncomingMessage::OpenOrder { order_id: id, .. } if id == order_id => Ok(true),
+                    IncomingMessage::OrderStatus(status) if status.order_id == order_id => Ok(true),
+                    IncomingMessage::Error { id, code, message }
+                        if id == order_id && !is_warning(code) =>
+                    {
+                        *submission = Submission::Rejected { code, message };
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            },
+        )
+        .await
+    }
+
+    /// Send `message` and feed every incoming message to `on_message` until
+    /// it returns `true`
+    async fn request<T>(
+        &self,
+        message: OutgoingMessage,
+        mut state: T,
+        mut on_message: impl FnMut(&mut T, IncomingMessage) -> Result<bool>,
+    ) -> Result<T> {
+        let connection = self.connection().await?;
+        let mut rx = connection.subscribe();
+        connection.send(message).await?;
+
+        let timeout = Duration::from_secs(self.config.timeout);
+        tokio::time::timeout(timeout, async {
+            loop {
+                match rx.recv().await {
+                    Ok(message) => {
+                        if on_message(&mut state, message)? {
+                            return Ok(());
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Dropped {} messages while waiting for a reply", skipped)
+                    }
+                    Err(RecvError::Closed) => return Err(IBKRMCPError::NotConnected),
+                }
+            }
+        })
+        .await
+        .map_err(|_| IBKRMCPError::Timeout)??;
+
+        Ok(state)
+    }
+}
+
+/// TWS error code for an order ID that was already used
+const DUPLICATE_ORDER_ID: i32 = 103;
+
+/// Market data notices TWS reports through the error channel while the
+/// request goes on: farm status warnings, and 10167/10168 for an account
+/// without a live data subscription
+fn is_market_data_notice(code: i32) -> bool {
+    is_warning(code) || code == 10167 || code == 10168
+}
+
+/// How TWS answered a `placeOrder`
+enum Submission {
+    Accepted,
+    Rejected { code: i32, message: String },
+}
+
+impl Submission {
+    fn into_result(self, order_id: i32) -> Result<i32> {
+        match self {
+            Submission::Accepted => Ok(order_id),
+            Submission::Rejected { code, message } => Err(IBKRMCPError::Order(format!(
+                "Order {} rejected ({}): {}",
+                order_id, code, message
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl BrokerBackend for LiveBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Live
+    }
+
+    async fn connect(&self) -> Result<()> {
+        self.order_ids.load()?;
+        self.manager.start().await?;
+        self.connection().await?;
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.manager.stop().await;
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        self.manager.reconnect().await?;
+        self.connection().await?;
+        Ok(())
+    }
+
+    async fn connection_state(&self) -> ConnectionState {
+        self.manager.state()
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.connection().await.is_ok()
+    }
+
+    async fn managed_accounts(&self) -> Vec<String> {
+        self.connection()
+            .await
+            .map(|c| c.managed_accounts().to_vec())
+            .unwrap_or_default()
+    }
+
+    async fn next_valid_id(&self) -> Option<i32> {
+        self.connection().await.ok()?;
+        Some(self.order_ids.peek())
+    }
+
+    async fn get_account_summary(&self) -> Result<Vec<AccountValue>> {
+        let req_id = self.next_req_id();
+        let rows = self
+            .request(
+                OutgoingMessage::ReqAccountSummary {
+                    req_id,
+                    group: "All".into(),
+                    tags: ACCOUNT_SUMMARY_TAGS.into(),
+                },
+                Vec::new(),
+                |rows, message| match message {
+                    IncomingMessage::AccountSummary {
+                        req_id: id,
+                        account,
+                        tag,
+                        value,
+                        currency,
+                    } if id == req_id => {
+                        rows.push(AccountValue {
+                            account,
+                            tag,
+                            value,
+                            currency,
+                        });
+                        Ok(false)
+                    }
+                    IncomingMessage::AccountSummaryEnd { req_id: id } if id == req_id => Ok(true),
+                    IncomingMessage::Error { id, code, message } if id == req_id => {
+                        Err(IBKRMCPError::Connection(format!(
+                            "Account summary error {}: {}",
+                            code, message
+                        )))
+                    }
+                    _ => Ok(false),
+                },
+            )
+            .await?;
+
+        self.connection()
+            .await?
+            .send(OutgoingMessage::CancelAccountSummary { req_id })
+            .await?;
+        Ok(rows)
+    }
+
+    async fn get_positions(&self) -> Result<Vec<Position>> {
+        let _request = self.positions_request.lock().await;
+        let positions = self
+            .request(
+                OutgoingMessage::ReqPositions,
+                Vec::new(),
+                |positions, message| match message {
+                    IncomingMessage::Position {
+                        account,
+                        contract,
+                        position,
+                        avg_cost,
+                    } => {
+                        positions.push(Position::new(account, contract, position, avg_cost));
+                        Ok(false)
+                    }
+                    IncomingMessage::PositionEnd => Ok(true),
+                    _ => Ok(false),
+                },
+            )
+            .await?;
+
+        self.connection()
+            .await?
+            .send(OutgoingMessage::CancelPositions)
+            .await?;
+        Ok(positions)
+    }
+
+    async fn place_order(&self, contract: &Contract, order: &Order) -> Result<i32> {
+        self.connection().await?;
+
+        if let Some(order_id) = order.order_id {
+            self.order_ids.reserve(order_id)?;
+            return self
+                .submit_order(order_id, contract, order)
+                .await?
+                .into_result(order_id);
+        }
+
+        let order_id = self.order_ids.allocate()?;
+        match self.submit_order(order_id, contract, order).await? {
+            Submission::Rejected {
+                code: DUPLICATE_ORDER_ID,
+                ..
+            } => {
+                // Another session used this ID; resync with reqIds and retry once
+                warn!("Order ID {} already used, requesting fresh IDs", order_id);
+                self.request_ids().await?;
+                let order_id = self.order_ids.allocate()?;
+                self.submit_order(order_id, contract, order)
+                    .await?
+                    .into_result(order_id)
+            }
+            submission => submission.into_result(order_id),
+        }
+    }
+
+    async fn preview_order(&self, contract: &Contract, order: &Order) -> Result<OrderPreview> {
+        let order_id = self.order_ids.allocate()?;
+        info!("Previewing order {} for {}", order_id, contract.symbol);
+
+        let mut order = order.clone();
+        order.what_if = true;
+        self.request(
+            OutgoingMessage::PlaceOrder {
+                order_id,
+                contract: contract.clone(),
+                order: Box::new(order),
+            },
+            None,
+            |preview, message| match message {
+                IncomingMessage::OpenOrder {
+                    order_id: id,
+                    state,
+                    ..
+                } if id == order_id => {
+                    *preview = Some(*state.preview);
+                    Ok(true)
+                }
+                IncomingMessage::Error { id, code, message }
+                    if id == order_id && !is_warning(code) =>
+                {
+                    Err(IBKRMCPError::Order(format!(
+                        "Preview of order {} rejected ({}): {}",
+                        order_id, code, message
+                    )))
+                }
+                _ => Ok(false),
+            },
+        )
+        .await?
+        .ok_or_else(|| IBKRMCPError::Order("No preview returned".to_string()))
+    }
+
+    async fn cancel_order(&self, order_id: i32) -> Result<bool> {
+        self.request(
+            OutgoingMessage::CancelOrder { order_id },
+            false,
+            |cancelled, message| {
+                self.order_book.apply(&message);
+                match message {
+                    // Statuses still in flight from earlier requests are skipped
+                    // until the cancel either lands or loses the race to a fill
+                    IncomingMessage::OrderStatus(status) if status.order_id == order_id => {
+                        match status.status.as_str() {
+                            "Cancelled" | "ApiCancelled" | "PendingCancel" => {
+                                *cancelled = true;
+                                Ok(true)
+                            }
+                            "Filled" => Ok(true),
+                            _ => Ok(false),
+                        }
+                    }
+                    IncomingMessage::Error { id, code, message }
+                        if id == order_id && !is_warning(code) =>
+                    {
+                        Err(IBKRMCPError::Order(format!(
+                            "Cancel of order {} failed ({}): {}",
+                            order_id, code, message
+                        )))
+                    }
+                    _ => Ok(false),
+                }
+            },
+        )
+        .await
+    }
+
+    async fn global_cancel(&self) -> Result<Vec<i32>> {
+        // Only orders TWS reports as working will report a final status;
+        // orders tracked locally but unknown to TWS would never answer
+        let mut working = Vec::new();
+        let request = self.open_orders_request.lock().await;
+        self.request(OutgoingMessage::ReqAllOpenOrders, (), |_, message| {
+            self.order_book.apply(&message);
+            match &message {
+                IncomingMessage::OpenOrder {
+                    order_id, state, ..
+                } if !is_terminal(&state.status) && !working.contains(order_id) => {
+                    working.push(*order_id);
+                }
+                IncomingMessage::OpenOrderEnd => return Ok(true),
+                _ => {}
+            }
+            Ok(false)
+        })
+        .await?;
+        drop(request);
+        // reqGlobalCancel has no reply of its own, so wait until every
+        // order that was working reports a final status
+        if working.is_empty() {
+            self.connection()
+                .await?
+                .send(OutgoingMessage::ReqGlobalCancel)
+                .await?;
+            return Ok(working);
+        }
+
+        let mut pending: HashSet<i32> = working.iter().copied().collect();
+        self.request(OutgoingMessage::ReqGlobalCancel, (), |_, message| {
+            self.order_book.apply(&message);
+            if let IncomingMessage::OrderStatus(status) = &message {
+                if is_terminal(&status.status) {
+                    pending.remove(&status.order_id);
+                }
+            }
+            Ok(pending.is_empty())
+        })
+        .await?;
+        Ok(working)
+    }
+
+    async fn get_open_orders(&self) -> Result<Vec<TrackedOrder>> {
+        // Refresh from TWS so orders placed by other sessions are included
+        let _request = self.open_orders_request.lock().await;
+        self.request(OutgoingMessage::ReqAllOpenOrders, (), |_, message| {
+            self.order_book.apply(&message);
+            Ok(matches!(message, IncomingMessage::OpenOrderEnd))
+        })
+        .await?;
+
+        Ok(self.order_book.open_orders())
+    }
+
+    async fn get_order_status(&self, order_id: i32) -> Result<TrackedOrder> {
+        self.order_book
+            .get(order_id)
+            .ok_or_else(|| IBKRMCPError::Order(format!("Unknown order ID {}", order_id)))
+    }
+
+    async fn find_orders_by_ref(&self, order_ref: &str) -> Result<Vec<OrderRefMatch>> {
+        // Orders this session tracks, refreshed with those of other sessions
+        let request = self.open_orders_request.lock().await;
+        self.request(OutgoingMessage::ReqAllOpenOrders, (), |_, message| {
+            self.order_book.apply(&message);
+            Ok(matches!(message, IncomingMessage::OpenOrderEnd))
+        })
+        .await?;
+        drop(request);
+        let mut found: Vec<OrderRefMatch> = self
+            .order_book
+            .all_orders()
+            .iter()
+            .filter(|o| o.order.order_ref.as_deref() == Some(order_ref))
+            .map(OrderRefMatch::from)
+            .collect();
+
+        // Today's executions tell the order IDs of completed orders
+        let req_id = self.next_req_id();
+        let executions = self
+            .request(
+                OutgoingMessage::ReqExecutions { req_id },
+                Vec::new(),
+                |executions, message| {
+                    match message {
+                        IncomingMessage::ExecutionData {
+                            req_id: id,
+                            order_id,
+                            contract,
+                            execution,
+                        } if id == req_id && execution.order_ref == order_ref => {
+                            executions.push((order_id, contract, execution));
+                        }
+                        IncomingMessage::ExecutionDataEnd { req_id: id } if id == req_id => {
+                            return Ok(true)
+                        }
+                        _ => {}
+                    }
+                    Ok(false)
+                },
+            )
+            .await?;
+
+        // Filled and cancelled orders, including those of earlier sessions
+        let _request = self.completed_orders_request.lock().await;
+        let completed = self
+            .request(
+                OutgoingMessage::ReqCompletedOrders { api_only: true },
+                Vec::new(),
+                |completed, message| {
+                    match message {
+                        IncomingMessage::CompletedOrder {
+                            contract,
+                            order,
+                            perm_id,
+                            status,
+                        } if order.order_ref.as_deref() == Some(order_ref) => {
+                            completed.push((contract, order, perm_id, status));
+                        }
+                        IncomingMessage::CompletedOrdersEnd => return Ok(true),
+                        _ => {}
+                    }
+                    Ok(false)
+                },
+            )
+            .await?;
+
+        let known = |found: &[OrderRefMatch], perm_id: i32| {
+            perm_id != 0 && found.iter().any(|m| m.perm_id == perm_id)
+        };
+        for (contract, order, perm_id, status) in completed {
+            if known(&found, perm_id) {
+                continue;
+            }
+            let order_id = executions
+                .iter()
+                .find(|(_, _, execution)| execution.perm_id == perm_id)
+                .map(|(order_id, _, _)| *order_id);
+            found.push(OrderRefMatch {
+                order_id,
+                perm_id,
+                contract,
+                action: order.action.clone(),
+                order: Some(order),
+                status,
+            });
+        }
+        // Executions whose order is reported nowhere else only tell the side
+        for (order_id, contract, execution) in executions {
+            if known(&found, execution.perm_id) {
+                continue;
+            }
+            found.push(OrderRefMatch {
+                order_id: Some(order_id),
+                perm_id: execution.perm_id,
+                contract,
+                action: match execution.side.as_str() {
+                    "SLD" => OrderAction::Sell,
+                    _ => OrderAction::Buy,
+                },
+                order: None,
+                status: OrderStatus::Filled.as_str().to_string(),
+            });
+        }
+        Ok(found)
+    }
+
+    async fn get_market_data(&self, contract: &Contract) -> Result<MarketSnapshot> {
+        let req_id = self.next_req_id();
+
+        let snapshot = self
+            .request(
+                OutgoingMessage::ReqMktData {
+                    req_id,
+                    contract: contract.clone(),
+                    generic_ticks: String::new(),
+                    snapshot: true,
+                },
+                MarketSnapshot {
+                    symbol: contract.symbol.clone(),
+                    bid: None,
+                    ask: None,
+                    last: None,
+                    volume: None,
+                    timestamp: chrono::Utc::now(),
+                },
+                |snapshot, message| match message {
+                    IncomingMessage::TickPrice {
+                        req_id: id,
+                        tick_type,
+                        price,
+                        ..
+                    } if id == req_id => {
+                        let field = match tick_type {
+                            tick::BID => &mut snapshot.bid,
+                            tick::ASK => &mut snapshot.ask,
+                            tick::LAST => &mut snapshot.last,
+                            _ => return Ok(false),
+                        };
+                        *field = Some(price);
+                        Ok(false)
+                    }
+                    IncomingMessage::TickSize {
+                        req_id: id,
+                        tick_type: tick::VOLUME,
+                        size,
+                    } if id == req_id => {
+                        snapshot.volume = Some(size);
+                        Ok(false)
+                    }
+                    IncomingMessage::TickSnapshotEnd { req_id: id } if id == req_id => Ok(true),
+                    IncomingMessage::Error { id, code, message }
+                        if id == req_id && !is_market_data_notice(code) =>
+                    {
+                        Err(IBKRMCPError::MarketData(format!(
+                            "{} ({}): {}",
+                            contract.symbol, code, message
+                        )))
+                    }
+                    _ => Ok(false),
+                },
+            )
+            .await?;
+
+        Ok(MarketSnapshot {
+            timestamp: chrono::Utc::now(),
+            ..snapshot
+        })
+    }
+
+    async fn get_historical_data(
+        &self,
+        contract: &Contract,
+        duration: &str,
+        bar_size: &str,
+        what_to_show: &str,
+    ) -> Result<Vec<BarData>> {
+        let req_id = self.next_req_id();
+
+        self.request(
+            OutgoingMessage::ReqHistoricalData {
+                req_id,
+                contract: contract.clone(),
+                end_date_time: String::new(),
+                duration: duration.to_string(),
+                bar_size: bar_size.to_string(),
+                what_to_show: what_to_show.to_string(),
+                use_rth: true,
+            },
+            Vec::new(),
+            |bars, message| match message {
+                IncomingMessage::HistoricalData {
+                    req_id: id,
+                    bars: received,
+                    ..
+                } if id == req_id => {
+                    *bars = received;
+                    Ok(true)
+                }
+                IncomingMessage::Error { id, code, message }
+                    if id == req_id && !is_market_data_notice(code) =>
+                {
+                    Err(IBKRMCPError::MarketData(format!(
+                        "{} ({}): {}",
+                        contract.symbol, code, message
+                    )))
+                }
+                _ => Ok(false),
+            },
+        )
+        .await
+    }
+}
+
+// A TWS status string after which the order reports nothing further
+fn is_terminal(status: &str) -> bool {
+    status
+        .parse::<OrderStatus>()
+        .is_ok_and(|status| status.is_terminal())
+}
@@ -0,0 +1,323 @@
+/// Deterministic in-memory backend
+///
+/// Serves a fixed paper account (DU123456 holding AAPL and MSFT), fixed
+/// quotes, and formula-generated bars. Orders are tracked in an
+/// [`OrderBook`] fed with the messages TWS would send, and never leave the
+/// process.
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use async_trait::async_trait;
+use chrono::{DurationRound, TimeDelta, Utc};
+
+use super::backend::BrokerBackend;
+use super::codec::{IncomingMessage, OrderState, OrderStatusUpdate};
+use super::order_book::{OrderBook, OrderRefMatch, TrackedOrder};
+use crate::{
+    config::BackendKind,
+    error::{IBKRMCPError, Result},
+    models::{
+        AccountValue, BarData, Contract, MarketSnapshot, Order, OrderPreview, OrderType, Position,
+        SecType,
+    },
+};
+
+const ACCOUNT: &str = "DU123456";
+
+/// First order ID handed out, well clear of anything a gateway would use
+const FIRST_ORDER_ID: i32 = 1000;
+
+pub struct InMemoryBackend {
+    connected: AtomicBool,
+    next_order_id: AtomicI32,
+    order_book: OrderBook,
+}
+
+impl Default for InMemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
+        Self {
+            connected: AtomicBool::new(false),
+            next_order_id: AtomicI32::new(FIRST_ORDER_ID),
+            order_book: OrderBook::new(),
+        }
+    }
+
+    fn ensure_connected(&self) -> Result<()> {
+        if self.connected.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(IBKRMCPError::NotConnected)
+        }
+    }
+
+    /// Fixed reference price per symbol
+    fn base_price(symbol: &str) -> f64 {
+        match symbol {
+            "AAPL" => 175.0,
+            "MSFT" => 375.0,
+            "TSLA" => 180.0,
+            "GOOGL" => 140.0,
+            _ => 100.0,
+        }
+    }
+}
+
+#[async_trait]
+impl BrokerBackend for InMemoryBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Memory
+    }
+
+    async fn connect(&self) -> Result<()> {
+        self.connected.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.connected.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    async fn managed_accounts(&self) -> Vec<String> {
+        if self.connected.load(Ordering::SeqCst) {
+            vec![ACCOUNT.to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    async fn next_valid_id(&self) -> Option<i32> {
+        self.ensure_connected().ok()?;
+        Some(self.next_order_id.load(Ordering::SeqCst))
+    }
+
+    async fn get_account_summary(&self) -> Result<Vec<AccountValue>> {
+        self.ensure_connected()?;
+
+        Ok(vec![
+            AccountValue::new(ACCOUNT, "NetLiquidation", "150000.00", "USD"),
+            AccountValue::new(ACCOUNT, "TotalCashValue", "50000.00", "USD"),
+            AccountValue::new(ACCOUNT, "GrossPositionValue", "100000.00", "USD"),
+        ])
+    }
+
+    async fn get_positions(&self) -> Result<Vec<Position>> {
+        self.ensure_connected()?;
+
+        Ok(vec![
+            Position {
+                account: ACCOUNT.to_string(),
+                contract: Contract::new("AAPL", SecType::Stock),
+                position: 100.0,
+                avg_cost: 150.25,
+                market_price: Some(175.50),
+                market_value: Some(17550.0),
+                unrealized_pnl: Some(2525.0),
+                realized_pnl: Some(0.0),
+            },
+            Position {
+                account: ACCOUNT.to_string(),
+                contract: Contract::new("MSFT", SecType::Stock),
+                position: 50.0,
+                avg_cost: 350.00,
+                market_price: Some(375.00),
+                market_value: Some(18750.0),
+                unrealized_pnl: Some(1250.0),
+                realized_pnl: Some(0.0),
+            },
+        ])
+    }
+
+    async fn place_order(&self, contract: &Contract, order: &Order) -> Result<i32> {
+        self.ensure_connected()?;
+
+        let order_id = match order.order_id {
+            Some(id) => id,
+            None => self.next_order_id.fetch_add(1, Ordering::SeqCst),
+        };
+
+        // Attached orders wait for their parent, as they would at TWS
+        let status = if order.parent_id.is_some() {
+            "PreSubmitted"
+        } else {
+            "Submitted"
+        };
+        self.order_book.track_submission(order_id, contract, order);
+        self.order_book.apply(&IncomingMessage::OpenOrder {
+            order_id,
+            contract: contract.clone(),
+            order: order.clone(),
+            state: OrderState {
+                status: status.to_string(),
+                ..Default::default()
+            },
+        });
+        self.order_book
+            .apply(&status_update(order_id, status, order.total_quantity));
+
+        Ok(order_id)
+    }
+
+    async fn preview_order(&self, contract: &Contract, order: &Order) -> Result<OrderPreview> {
+        self.ensure_connected()?;
+
+        // Flat 30% initial / 25% maintenance on a fixed $100k of positions,
+        // and IBKR Pro fixed commissions
+        let price = order
+            .lmt_price
+            .or(order
+                .aux_price
+                .filter(|_| order.order_type == OrderType::Stop))
+            .or(order.trail_stop_price)
+            .unwrap_or_else(|| Self::base_price(&contract.symbol));
+        let notional = order.total_quantity * price;
+        let commission = (order.total_quantity * 0.005).max(1.0);
+        let (gross, equity) = (100_000.0, 150_000.0);
+
+        Ok(OrderPreview {
+            init_margin_before: Some(gross * 0.30),
+            maint_margin_before: Some(gross * 0.25),
+            equity_with_loan_before: Some(equity),
+            init_margin_change: Some(notional * 0.30),
+            maint_margin_change: Some(notional * 0.25),
+            equity_with_loan_change: Some(-commission),
+            init_margin_after: Some((gross + notional) * 0.30),
+            maint_margin_after: Some((gross + notional) * 0.25),
+            equity_with_loan_after: Some(equity - commission),
+            commission: Some(commission),
+            min_commission: Some(commission),
+            max_commission: Some(commission),
+            commission_currency: Some(contract.currency.clone()),
+            warning_text: None,
+        })
+    }
+
+    async fn cancel_order(&self, order_id: i32) -> Result<bool> {
+        self.ensure_connected()?;
+
+        match self.order_book.get(order_id) {
+            Some(tracked) if tracked.is_open() => {
+                // Attached orders go with their parent
+                let children = self
+                    .order_book
+                    .open_orders()
+                    .into_iter()
+                    .filter(|o| o.order.parent_id == Some(order_id));
+                for order in std::iter::once(tracked).chain(children) {
+                    self.order_book.apply(&status_update(
+                        order.order_id,
+                        "Cancelled",
+                        order.remaining,
+                    ));
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn global_cancel(&self) -> Result<Vec<i32>> {
+        self.ensure_connected()?;
+
+        let working = self.order_book.open_orders();
+        for order in &working {
+            self.order_book
+                .apply(&status_update(order.order_id, "Cancelled", order.remaining));
+        }
+        Ok(working.iter().map(|o| o.order_id).collect())
+    }
+
+    async fn get_open_orders(&self) -> Result<Vec<TrackedOrder>> {
+        self.ensure_connected()?;
+        Ok(self.order_book.open_orders())
+    }
+
+    async fn get_order_status(&self, order_id: i32) -> Result<TrackedOrder> {
+        self.ensure_connected()?;
+        self.order_book
+            .get(order_id)
+            .ok_or_else(|| IBKRMCPError::Order(format!("Unknown order ID {}", order_id)))
+    }
+
+    async fn find_orders_by_ref(&self, order_ref: &str) -> Result<Vec<OrderRefMatch>> {
+        self.ensure_connected()?;
+        Ok(self
+            .order_book
+            .all_orders()
+            .iter()
+            .filter(|o| o.order.order_ref.as_deref() == Some(order_ref))
+            .map(OrderRefMatch::from)
+            .collect())
+    }
+
+    async fn get_market_data(&self, contract: &Contract) -> Result<MarketSnapshot> {
+        self.ensure_connected()?;
+
+        let last = Self::base_price(&contract.symbol);
+        Ok(MarketSnapshot {
+            symbol: contract.symbol.clone(),
+            bid: Some(last - 0.05),
+            ask: Some(last + 0.05),
+            last: Some(last),
+            volume: Some(2_500_000.0),
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn get_historical_data(
+        &self,
+        contract: &Contract,
+        _duration: &str,
+        _bar_size: &str,
+        _what_to_show: &str,
+    ) -> Result<Vec<BarData>> {
+        self.ensure_connected()?;
+
+        let base = Self::base_price(&contract.symbol);
+        let now = Utc::now()
+            .duration_trunc(TimeDelta::minutes(1))
+            .unwrap_or_else(|_| Utc::now());
+
+        Ok((0..10)
+            .map(|i| {
+                let open = base + (i % 5) as f64 * 0.1;
+                BarData {
+                    date: now - TimeDelta::minutes(10 - i),
+                    open,
+                    high: open + 0.25,
+                    low: open - 0.25,
+                    close: open + 0.05,
+                    volume: 100_000 + i * 10_000,
+                    wap: None,
+                    count: None,
+                }
+            })
+            .collect())
+    }
+}
+
+/// `orderStatus` for an order that has not been filled
+fn status_update(order_id: i32, status: &str, remaining: f64) -> IncomingMessage {
+    IncomingMessage::OrderStatus(OrderStatusUpdate {
+        order_id,
+        status: status.to_string(),
+        filled: 0.0,
+        remaining,
+        avg_fill_price: 0.0,
+        perm_id: order_id,
+        parent_id: 0,
+        last_fill_price: 0.0,
+        client_id: 0,
+        why_held: String::new(),
+        mkt_cap_price: 0.0,
+    })
+}
@@ -1,5 +1,18 @@
 /// IBKR client module
+pub mod backend;
 pub mod client;
+pub mod codec;
 pub mod connection;
+pub mod halt;
+pub mod live;
+pub mod memory;
+pub mod order_book;
+pub mod order_ids;
 
+pub use backend::BrokerBackend;
 pub use client::IBKRClient;
+pub use connection::{ConnectionManager, ConnectionState};
+pub use halt::{HaltReport, HaltSwitch, TradingHalt};
+pub use live::LiveBackend;
+pub use memory::InMemoryBackend;
+pub use order_book::{CancelOutcome, OrderBook, OrderFilter, OrderRefMatch, TrackedOrder};
@@ -0,0 +1,539 @@
+/// Order book
+///
+/// Folds `openOrder`, `orderStatus`, `execDetails`, and `commissionReport`
+/// messages into one `TrackedOrder` per order ID. Applying a message twice
+/// is harmless: fills are keyed by execution ID and status changes go
+/// through [`OrderStatus::can_transition_to`].
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use super::codec::{IncomingMessage, OrderStatusUpdate};
+use crate::models::{Contract, Order, OrderAction, OrderStatus, OrderType};
+
+/// One execution of (part of) an order
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct Fill {
+    pub exec_id: String,
+    pub time: String,
+    pub shares: f64,
+    pub price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commission: Option<f64>,
+}
+
+/// Everything known about one order
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct TrackedOrder {
+    pub order_id: i32,
+    pub perm_id: i32,
+    pub contract: Contract,
+    pub order: Order,
+    pub status: OrderStatus,
+    pub filled: f64,
+    pub remaining: f64,
+    pub avg_fill_price: f64,
+    pub last_fill_price: f64,
+    /// Sum of reported commissions over all fills
+    pub commission: f64,
+    pub fills: Vec<Fill>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub why_held: Option<String>,
+    /// Last error TWS reported for this order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// When this session first saw the order
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TrackedOrder {
+    fn new(order_id: i32, contract: Contract, order: Order) -> Self {
+        let remaining = order.total_quantity;
+        let now = Utc::now();
+        Self {
+            order_id,
+            perm_id: 0,
+            contract,
+            order,
+            status: OrderStatus::PendingSubmit,
+            filled: 0.0,
+            remaining,
+            avg_fill_price: 0.0,
+            last_fill_price: 0.0,
+            commission: 0.0,
+            fills: Vec::new(),
+            why_held: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Still working at the broker
+    pub fn is_open(&self) -> bool {
+        !self.status.is_terminal()
+    }
+
+    fn set_status(&mut self, status: OrderStatus) {
+        if self.status.can_transition_to(&status) {
+            self.status = status;
+        } else if self.status != status {
+            debug!(
+                "Ignoring {} -> {} for order {}",
+                self.status.as_str(),
+                status.as_str(),
+                self.order_id
+            );
+        }
+    }
+}
+
+/// Selects working orders for bulk operations; unset fields match any order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderFilter {
+    pub symbol: Option<String>,
+    pub action: Option<OrderAction>,
+    pub order_type: Option<OrderType>,
+    /// Only orders first seen at least this long ago
+    pub older_than: Option<Duration>,
+}
+
+impl OrderFilter {
+    /// Whether the filter matches every order
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn matches(&self, order: &TrackedOrder, now: DateTime<Utc>) -> bool {
+        if let Some(symbol) = &self.symbol {
+            if !symbol.eq_ignore_ascii_case(&order.contract.symbol) {
+                return false;
+            }
+        }
+        if let Some(action) = &self.action {
+            if *action != order.order.action {
+                return false;
+            }
+        }
+        if let Some(order_type) = &self.order_type {
+            if *order_type != order.order.order_type {
+                return false;
+            }
+        }
+        if let Some(min_age) = self.older_than {
+            if (now - order.created_at).to_std().unwrap_or_default() < min_age {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// What happened to one order in a bulk cancel
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct CancelOutcome {
+    pub order_id: i32,
+    pub symbol: String,
+    pub cancelled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<OrderStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An order found by its `orderRef`, working or not
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRefMatch {
+    /// Unknown for orders of earlier sessions that completed unfilled
+    pub order_id: Option<i32>,
+    pub perm_id: i32,
+    pub contract: Contract,
+    pub action: OrderAction,
+    /// The order as TWS reports it; `None` when only its executions are
+    /// known
+    pub order: Option<Order>,
+    pub status: String,
+}
+
+impl From<&TrackedOrder> for OrderRefMatch {
+    fn from(tracked: &TrackedOrder) -> Self {
+        Self {
+            order_id: Some(tracked.order_id),
+            perm_id: tracked.perm_id,
+            contract: tracked.contract.clone(),
+            action: tracked.order.action.clone(),
+            order: Some(tracked.order.clone()),
+            status: tracked.status.as_str().to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Book {
+    orders: HashMap<i32, TrackedOrder>,
+    /// Execution ID to order ID, for matching commission reports
+    executions: HashMap<String, i32>,
+}
+
+#[derive(Default)]
+pub struct OrderBook {
+    book: RwLock<Book>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an order about to be transmitted as `PendingSubmit`
+    pub fn track_submission(&self, order_id: i32, contract: &Contract, order: &Order) {
+        let mut book = self.book.write().unwrap();
+        match book.orders.get_mut(&order_id) {
+            // Resubmitting a known ID modifies the order
+            Some(tracked) => {
+                tracked.order = order.clone();
+                tracked.updated_at = Utc::now();
+            }
+            None => {
+                book.orders.insert(
+                    order_id,
+                    TrackedOrder::new(order_id, contract.clone(), order.clone()),
+                );
+            }
+        }
+    }
+
+    /// Fold one incoming message into the book; unrelated messages are ignored
+    pub fn apply(&self, message: &IncomingMessage) {
+        let mut book = self.book.write().unwrap();
+        match message {
+            // What-if previews never become working orders
+            IncomingMessage::OpenOrder { order, .. } if order.what_if => {}
+            IncomingMessage::OpenOrder {
+                order_id,
+                contract,
+                order,
+                state,
+            } => {
+                let tracked = book.orders.entry(*order_id).or_insert_with(|| {
+                    TrackedOrder::new(*order_id, contract.clone(), order.clone())
+                });
+                tracked.contract = contract.clone();
+                tracked.order = order.clone();
+                if let Ok(status) = state.status.parse() {
+                    tracked.set_status(status);
+                }
+                tracked.updated_at = Utc::now();
+            }
+            IncomingMessage::OrderStatus(update) => {
+                if let Some(tracked) = book.orders.get_mut(&update.order_id) {
+                    apply_status(tracked, update);
+                } else {
+                    debug!("orderStatus for untracked order {}", update.order_id);
+                }
+            }
+            IncomingMessage::ExecutionData {
+                order_id,
+                execution,
+                ..
+            } => {
+                let Some(tracked) = book.orders.get_mut(order_id) else {
+                    debug!("execDetails for untracked order {}", order_id);
+                    return;
+                };
+                if !tracked.fills.iter().any(|f| f.exec_id == execution.exec_id) {
+                    tracked.fills.push(Fill {
+                        exec_id: execution.exec_id.clone(),
+                        time: execution.time.clone(),
+                        shares: execution.shares,
+                        price: execution.price,
+                        commission: None,
+                    });
+                    // orderStatus is authoritative, but may trail the fill
+                    if execution.cum_qty > tracked.filled {
+                        tracked.filled = execution.cum_qty;
+                        tracked.remaining =
+                            (tracked.order.total_quantity - execution.cum_qty).max(0.0);
+                        tracked.avg_fill_price = execution.avg_price;
+                    }
+                    tracked.last_fill_price = execution.price;
+                    tracked.updated_at = Utc::now();
+                }
+                book.executions.insert(execution.exec_id.clone(), *order_id);
+            }
+            IncomingMessage::CommissionReport(report) => {
+                let Some(order_id) = book.executions.get(&report.exec_id).copied() else {
+                    debug!("commissionReport for unknown execution {}", report.exec_id);
+                    return;
+                };
+                let Some(tracked) = book.orders.get_mut(&order_id) else {
+                    return;
+                };
+                if let Some(fill) = tracked
+                    .fills
+                    .iter_mut()
+                    .find(|f| f.exec_id == report.exec_id && f.commission.is_none())
+                {
+                    fill.commission = Some(report.commission);
+                    tracked.commission = tracked.fills.iter().filter_map(|f| f.commission).sum();
+                    tracked.updated_at = Utc::now();
+                }
+            }
+            IncomingMessage::Error { id, code, message } => {
+                if let Some(tracked) = book.orders.get_mut(id) {
+                    debug!("Order {} error {}: {}", id, code, message);
+                    tracked.last_error = Some(format!("{}: {}", code, message));
+                    // Never acknowledged, so the order did not make it to TWS
+                    if tracked.status == OrderStatus::PendingSubmit && !is_warning(*code) {
+                        tracked.set_status(OrderStatus::Rejected);
+                    }
+                    tracked.updated_at = Utc::now();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get(&self, order_id: i32) -> Option<TrackedOrder> {
+        self.book.read().unwrap().orders.get(&order_id).cloned()
+    }
+
+    /// Orders still working, oldest ID first
+    pub fn open_orders(&self) -> Vec<TrackedOrder> {
+        let mut orders: Vec<_> = self
+            .book
+            .read()
+            .unwrap()
+            .orders
+            .values()
+            .filter(|o| o.is_open())
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| o.order_id);
+        orders
+    }
+
+    /// Every tracked order, oldest ID first
+    pub fn all_orders(&self) -> Vec<TrackedOrder> {
+        let mut orders: Vec<_> = self.book.read().unwrap().orders.values().cloned().collect();
+        orders.sort_by_key(|o| o.order_id);
+        orders
+    }
+}
+
+fn apply_status(tracked: &mut TrackedOrder, update: &OrderStatusUpdate) {
+    match update.status.parse() {
+        Ok(status) => tracked.set_status(status),
+        Err(_) => warn!(
+            "Unknown status {} for order {}",
+            update.status, update.order_id
+        ),
+    }
+    if update.filled >= tracked.filled {
+        tracked.filled = update.filled;
+        tracked.remaining = update.remaining;
+        if update.avg_fill_price > 0.0 {
+            tracked.avg_fill_price = update.avg_fill_price;
+        }
+        if update.last_fill_price > 0.0 {
+            tracked.last_fill_price = update.last_fill_price;
+        }
+    }
+    if update.perm_id != 0 {
+        tracked.perm_id = update.perm_id;
+    }
+    tracked.why_held = Some(update.why_held.clone()).filter(|w| !w.is_empty());
+    tracked.updated_at = Utc::now();
+}
+
+/// Order notices TWS reports through the error channel without rejecting
+pub(crate) fn is_warning(code: i32) -> bool {
+    code == 399 || (2100..2200).contains(&code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibkr::codec::{CommissionReport, Execution, OrderState};
+    use crate::models::{OrderAction, OrderType, SecType};
+
+    fn status(
+        order_id: i32,
+        status: &str,
+        filled: f64,
+        remaining: f64,
+        avg: f64,
+    ) -> IncomingMessage {
+        IncomingMessage::OrderStatus(OrderStatusUpdate {
+            order_id,
+            status: status.to_string(),
+            filled,
+            remaining,
+            avg_fill_price: avg,
+            perm_id: 555,
+            parent_id: 0,
+            last_fill_price: avg,
+            client_id: 1,
+            why_held: String::new(),
+            mkt_cap_price: 0.0,
+        })
+    }
+
+    fn execution(
+        order_id: i32,
+        exec_id: &str,
+        shares: f64,
+        price: f64,
+        cum_qty: f64,
+    ) -> IncomingMessage {
+        IncomingMessage::ExecutionData {
+            req_id: -1,
+            order_id,
+            contract: Contract::new("AAPL", SecType::Stock),
+            execution: Execution {
+                exec_id: exec_id.to_string(),
+                time: "20240102 10:00:00".to_string(),
+                account: "DU123456".to_string(),
+                exchange: "ISLAND".to_string(),
+                side: "BOT".to_string(),
+                shares,
+                price,
+                perm_id: 555,
+                client_id: 1,
+                cum_qty,
+                avg_price: price,
+                order_ref: String::new(),
+            },
+        }
+    }
+
+    fn commission(exec_id: &str, amount: f64) -> IncomingMessage {
+        IncomingMessage::CommissionReport(CommissionReport {
+            exec_id: exec_id.to_string(),
+            commission: amount,
+            currency: "USD".to_string(),
+            realized_pnl: None,
+        })
+    }
+
+    fn book_with_order(order_id: i32) -> OrderBook {
+        let book = OrderBook::new();
+        book.track_submission(
+            order_id,
+            &Contract::new("AAPL", SecType::Stock),
+            &Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(150.0),
+        );
+        book
+    }
+
+    #[test]
+    fn partial_then_full_fill() {
+        let book = book_with_order(1);
+        assert_eq!(book.get(1).unwrap().status, OrderStatus::PendingSubmit);
+
+        book.apply(&status(1, "Submitted", 0.0, 100.0, 0.0));
+        book.apply(&execution(1, "e1", 40.0, 150.0, 40.0));
+        book.apply(&commission("e1", 0.4));
+        book.apply(&status(1, "Submitted", 40.0, 60.0, 150.0));
+        let order = book.get(1).unwrap();
+        assert_eq!(order.status, OrderStatus::Submitted);
+        assert_eq!((order.filled, order.remaining), (40.0, 60.0));
+        assert_eq!(book.open_orders().len(), 1);
+
+        book.apply(&execution(1, "e2", 60.0, 149.0, 100.0));
+        book.apply(&commission("e2", 0.6));
+        book.apply(&status(1, "Filled", 100.0, 0.0, 149.4));
+        let order = book.get(1).unwrap();
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(order.filled, 100.0);
+        assert_eq!(order.avg_fill_price, 149.4);
+        assert_eq!(order.last_fill_price, 149.4);
+        assert_eq!(order.fills.len(), 2);
+        assert!((order.commission - 1.0).abs() < 1e-9);
+        assert_eq!(order.perm_id, 555);
+        assert!(book.open_orders().is_empty());
+        assert_eq!(book.all_orders().len(), 1);
+    }
+
+    #[test]
+    fn replayed_messages_are_idempotent() {
+        let book = book_with_order(1);
+        let messages = [
+            execution(1, "e1", 100.0, 150.0, 100.0),
+            commission("e1", 1.0),
+            status(1, "Filled", 100.0, 0.0, 150.0),
+        ];
+        for message in messages.iter().chain(messages.iter()) {
+            book.apply(message);
+        }
+
+        let order = book.get(1).unwrap();
+        assert_eq!(order.fills.len(), 1);
+        assert_eq!(order.commission, 1.0);
+        assert_eq!(order.filled, 100.0);
+    }
+
+    #[test]
+    fn terminal_states_are_final() {
+        let book = book_with_order(1);
+        book.apply(&status(1, "Cancelled", 0.0, 100.0, 0.0));
+        book.apply(&status(1, "Submitted", 0.0, 100.0, 0.0));
+        assert_eq!(book.get(1).unwrap().status, OrderStatus::Cancelled);
+
+        // ApiCancelled is TWS's spelling for API-initiated cancels
+        let book = book_with_order(2);
+        book.apply(&status(2, "PreSubmitted", 0.0, 100.0, 0.0));
+        book.apply(&status(2, "PendingSubmit", 0.0, 100.0, 0.0));
+        assert_eq!(book.get(2).unwrap().status, OrderStatus::PreSubmitted);
+        book.apply(&status(2, "ApiCancelled", 0.0, 100.0, 0.0));
+        assert_eq!(book.get(2).unwrap().status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn rejection_before_acknowledgement() {
+        let book = book_with_order(1);
+        book.apply(&IncomingMessage::Error {
+            id: 1,
+            code: 2109,
+            message: "Outside RTH flag ignored".into(),
+        });
+        assert_eq!(book.get(1).unwrap().status, OrderStatus::PendingSubmit);
+
+        book.apply(&IncomingMessage::Error {
+            id: 1,
+            code: 201,
+            message: "Order rejected - reason: margin".into(),
+        });
+        let order = book.get(1).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(
+            order.last_error.as_deref(),
+            Some("201: Order rejected - reason: margin")
+        );
+    }
+
+    #[test]
+    fn open_order_adopts_unknown_orders() {
+        let book = OrderBook::new();
+        book.apply(&IncomingMessage::OpenOrder {
+            order_id: 9,
+            contract: Contract::new("MSFT", SecType::Stock),
+            order: Order::new(OrderAction::Sell, 5.0, OrderType::Market),
+            state: OrderState {
+                status: "PreSubmitted".into(),
+                ..Default::default()
+            },
+        });
+
+        let order = book.get(9).unwrap();
+        assert_eq!(order.status, OrderStatus::PreSubmitted);
+        assert_eq!(order.remaining, 5.0);
+        assert_eq!(order.contract.symbol, "MSFT");
+    }
+}
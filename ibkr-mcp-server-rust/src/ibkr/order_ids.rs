@@ -0,0 +1,151 @@
+/// Order ID allocation
+///
+/// TWS requires every order from a client ID to use a strictly increasing
+/// ID at or above its `nextValidId`. The allocator takes the highest of the
+/// gateway's `nextValidId` (handshake and `reqIds` replies) and the
+/// high-water mark persisted for this client ID, so a restart never reuses
+/// an ID handed out by a previous run.
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{error::Result, persist};
+
+/// On-disk record of the next unused order ID
+#[derive(Debug, Serialize, Deserialize)]
+struct OrderIdState {
+    client_id: i32,
+    next_order_id: i32,
+}
+
+pub struct OrderIdAllocator {
+    client_id: i32,
+    path: Option<PathBuf>,
+    next: Mutex<i32>,
+}
+
+impl OrderIdAllocator {
+    /// Allocator persisting to `<state_dir>/order_ids_client_<client_id>.json`,
+    /// or keeping the high-water mark in memory only when `state_dir` is `None`
+    pub fn new(state_dir: Option<&Path>, client_id: i32) -> Self {
+        Self {
+            client_id,
+            path: state_dir.map(|dir| dir.join(format!("order_ids_client_{}.json", client_id))),
+            next: Mutex::new(0),
+        }
+    }
+
+    /// Merge the persisted high-water mark, if any
+    pub fn load(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let Some(bytes) = persist::read(path)? else {
+            return Ok(());
+        };
+        let state: OrderIdState = serde_json::from_slice(&bytes)?;
+        debug!(
+            "Loaded order ID high-water mark {} for client {}",
+            state.next_order_id, state.client_id
+        );
+
+        let mut next = self.next.lock().unwrap();
+        *next = (*next).max(state.next_order_id);
+        Ok(())
+    }
+
+    /// Record a `nextValidId` reported by the gateway
+    pub fn observe_next_valid_id(&self, order_id: i32) -> Result<()> {
+        let mut next = self.next.lock().unwrap();
+        if order_id <= *next {
+            return Ok(());
+        }
+        info!("Order IDs advance to nextValidId {}", order_id);
+        *next = order_id;
+        self.persist(*next)
+    }
+
+    /// Hand out the next order ID, persisting the new high-water mark first
+    pub fn allocate(&self) -> Result<i32> {
+        let mut next = self.next.lock().unwrap();
+        let order_id = *next;
+        self.persist(order_id + 1)?;
+        *next = order_id + 1;
+        Ok(order_id)
+    }
+
+    /// Mark an externally chosen ID as used
+    pub fn reserve(&self, order_id: i32) -> Result<()> {
+        self.observe_next_valid_id(order_id + 1)
+    }
+
+    /// The ID `allocate` would return next
+    pub fn peek(&self) -> i32 {
+        *self.next.lock().unwrap()
+    }
+
+    fn persist(&self, next_order_id: i32) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let state = OrderIdState {
+            client_id: self.client_id,
+            next_order_id,
+        };
+        persist::write_atomically(path, &serde_json::to_vec_pretty(&state)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_from_next_valid_id() {
+        let ids = OrderIdAllocator::new(None, 1);
+        ids.observe_next_valid_id(42).unwrap();
+        assert_eq!(ids.allocate().unwrap(), 42);
+        assert_eq!(ids.allocate().unwrap(), 43);
+
+        // A stale nextValidId never moves the allocator backwards
+        ids.observe_next_valid_id(10).unwrap();
+        assert_eq!(ids.allocate().unwrap(), 44);
+
+        ids.reserve(100).unwrap();
+        assert_eq!(ids.peek(), 101);
+    }
+
+    #[test]
+    fn high_water_mark_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let first = OrderIdAllocator::new(Some(dir.path()), 7);
+        first.load().unwrap();
+        first.observe_next_valid_id(1).unwrap();
+        assert_eq!(first.allocate().unwrap(), 1);
+        assert_eq!(first.allocate().unwrap(), 2);
+        drop(first);
+
+        let second = OrderIdAllocator::new(Some(dir.path()), 7);
+        second.load().unwrap();
+        second.observe_next_valid_id(1).unwrap();
+        assert_eq!(second.allocate().unwrap(), 3);
+
+        // Each client ID keeps its own sequence
+        let other = OrderIdAllocator::new(Some(dir.path()), 8);
+        other.load().unwrap();
+        assert_eq!(other.peek(), 0);
+        assert!(dir.path().join("order_ids_client_7.json").exists());
+    }
+
+    #[test]
+    fn corrupt_state_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("order_ids_client_1.json"), "not json").unwrap();
+
+        let ids = OrderIdAllocator::new(Some(dir.path()), 1);
+        assert!(ids.load().is_err());
+    }
+}
@@ -7,6 +7,10 @@ pub mod error;
 pub mod ibkr;
 pub mod mcp;
 pub mod models;
+mod persist;
+pub mod risk;
+#[cfg(feature = "test-util")]
+pub mod testing;
 pub mod utils;
 
 pub use config::Settings;
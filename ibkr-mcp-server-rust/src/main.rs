@@ -2,30 +2,42 @@
 //!
 //! High-performance Interactive Brokers MCP server written in Rust
 
+use clap::{Arg, Command};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use ibkr_mcp_server::config::Transport;
 use ibkr_mcp_server::{MCPServer, Result, Settings};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = cli().get_matches();
+
     // Load environment variables from .env file
     dotenvy::dotenv().ok();
 
-    // Initialize tracing
-    init_tracing();
-
-    // Print banner
-    print_banner();
-
     // Load configuration
-    let settings =
+    let mut settings =
         Settings::new().map_err(|e| ibkr_mcp_server::IBKRMCPError::Config(e.to_string()))?;
+    if let Some(transport) = args.get_one::<String>("transport") {
+        settings.mcp.transport = transport.parse()?;
+    }
+
+    // Initialize tracing
+    init_tracing(settings.mcp.transport);
+
+    // Print banner; stdout belongs to the protocol in stdio mode
+    if settings.mcp.transport == Transport::Http {
+        print_banner();
+    }
 
     info!("Configuration loaded successfully");
     info!("Environment: {}", settings.environment);
     info!("IBKR: {}:{}", settings.ibkr.host, settings.ibkr.port);
-    info!("MCP Server: {}:{}", settings.mcp.host, settings.mcp.port);
+    match settings.mcp.transport {
+        Transport::Http => info!("MCP Server: {}:{}", settings.mcp.host, settings.mcp.port),
+        Transport::Stdio => info!("MCP Server: stdio"),
+    }
 
     // Create and run server
     let server = MCPServer::new(settings);
@@ -54,14 +66,38 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn init_tracing() {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "ibkr_mcp_server=info,tower_http=debug,axum=debug".into()),
+fn cli() -> Command {
+    Command::new("ibkr-mcp-server")
+        .version(ibkr_mcp_server::VERSION)
+        .about("Interactive Brokers MCP server")
+        .arg(
+            Arg::new("transport")
+                .long("transport")
+                .value_name("TRANSPORT")
+                .value_parser(["http", "stdio"])
+                .help("How MCP clients connect; overrides IBKR__MCP__TRANSPORT"),
         )
-        .with(tracing_subscriber::fmt::layer().with_target(false))
-        .init();
+}
+
+fn init_tracing(transport: Transport) {
+    let registry = tracing_subscriber::registry().with(
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| "ibkr_mcp_server=info,tower_http=debug,axum=debug".into()),
+    );
+    match transport {
+        Transport::Http => registry
+            .with(tracing_subscriber::fmt::layer().with_target(false))
+            .init(),
+        // Anything on stdout would corrupt the JSON-RPC stream
+        Transport::Stdio => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_target(false)
+                    .with_ansi(false)
+                    .with_writer(std::io::stderr),
+            )
+            .init(),
+    }
 }
 
 fn print_banner() {
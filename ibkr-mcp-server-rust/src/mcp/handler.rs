@@ -1,17 +1,412 @@
-use crate::error::Result;
-/// Request/Response handler utilities
-use serde_json::Value;
-
-pub fn parse_tool_request(request: &Value) -> Result<(String, Value)> {
-    // Parse tool name and parameters from request
-    Ok(("".into(), Value::Null))
-}
-
-pub fn format_response(success: bool, data: Option<Value>, error: Option<String>) -> Value {
-    serde_json::json!({
-        "success": success,
-        "data": data,
-        "error": error,
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    })
+/// JSON-RPC 2.0 message layer
+///
+/// Incoming messages are parsed into typed requests, notifications and
+/// responses before anything looks at them, so malformed input is answered
+/// with the standard error codes instead of being guessed at. Requests are
+/// routed by method name through a `MethodRegistry`; every transport feeds
+/// it raw JSON and writes back whatever it returns.
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, error, info};
+
+use crate::error::IBKRMCPError;
+
+/// Request ID, echoed in the response
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+/// JSON-RPC error object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn parse_error(detail: impl std::fmt::Display) -> Self {
+        Self::new(Self::PARSE_ERROR, format!("Parse error: {}", detail))
+    }
+
+    pub fn invalid_request(detail: impl std::fmt::Display) -> Self {
+        Self::new(
+            Self::INVALID_REQUEST,
+            format!("Invalid request: {}", detail),
+        )
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            Self::METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )
+    }
+
+    pub fn invalid_params(detail: impl std::fmt::Display) -> Self {
+        Self::new(Self::INVALID_PARAMS, format!("Invalid params: {}", detail))
+    }
+
+    pub fn internal_error(detail: impl std::fmt::Display) -> Self {
+        Self::new(Self::INTERNAL_ERROR, format!("Internal error: {}", detail))
+    }
+}
+
+impl From<IBKRMCPError> for RpcError {
+    fn from(e: IBKRMCPError) -> Self {
+        match e {
+            IBKRMCPError::InvalidParameter(detail) => Self::invalid_params(detail),
+            other => Self::internal_error(other),
+        }
+    }
+}
+
+/// A call that expects a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub id: RequestId,
+    pub method: String,
+    pub params: Option<Value>,
+}
+
+/// A one-way message; never answered, not even with an error
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub method: String,
+    pub params: Option<Value>,
+}
+
+/// Outcome of a request. The ID is `None` only when the request was too
+/// malformed to read one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub id: Option<RequestId>,
+    pub outcome: Result<Value, RpcError>,
+}
+
+impl Response {
+    pub fn result(id: RequestId, result: Value) -> Self {
+        Self {
+            id: Some(id),
+            outcome: Ok(result),
+        }
+    }
+
+    pub fn error(id: Option<RequestId>, error: RpcError) -> Self {
+        Self {
+            id,
+            outcome: Err(error),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match &self.outcome {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": self.id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": self.id, "error": error }),
+        }
+    }
+}
+
+/// One JSON-RPC message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Request(Request),
+    Notification(Notification),
+    /// A client's answer to a server-initiated request
+    Response(Response),
+}
+
+impl Message {
+    /// Validate one message. A message that is not valid JSON-RPC yields the
+    /// error response to send back, echoing its ID when it has a usable one.
+    pub fn parse(value: Value) -> Result<Self, Response> {
+        let Value::Object(mut object) = value else {
+            return Err(Response::error(
+                None,
+                RpcError::invalid_request("expected a JSON object"),
+            ));
+        };
+
+        let id = match object.remove("id") {
+            None => None,
+            Some(id) => match serde_json::from_value::<RequestId>(id) {
+                Ok(id) => Some(id),
+                Err(_) => {
+                    return Err(Response::error(
+                        None,
+                        RpcError::invalid_request("id must be a string or an integer"),
+                    ))
+                }
+            },
+        };
+        let invalid = |detail: &str| {
+            Err(Response::error(
+                id.clone(),
+                RpcError::invalid_request(detail),
+            ))
+        };
+
+        if object.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return invalid("jsonrpc must be \"2.0\"");
+        }
+
+        match object.remove("method") {
+            Some(Value::String(method)) => {
+                let params = object.remove("params");
+                if !matches!(
+                    params,
+                    None | Some(Value::Object(_)) | Some(Value::Array(_))
+                ) {
+                    return invalid("params must be an object or an array");
+                }
+                Ok(match id {
+                    Some(id) => Message::Request(Request { id, method, params }),
+                    None => Message::Notification(Notification { method, params }),
+                })
+            }
+            Some(_) => invalid("method must be a string"),
+            None => {
+                let outcome = match (object.remove("result"), object.remove("error")) {
+                    (Some(result), None) => Ok(result),
+                    (None, Some(error)) => match serde_json::from_value(error) {
+                        Ok(error) => Err(error),
+                        Err(_) => return invalid("malformed error object"),
+                    },
+                    _ => return invalid("expected a method, or exactly one of result and error"),
+                };
+                Ok(Message::Response(Response { id, outcome }))
+            }
+        }
+    }
+}
+
+impl Message {
+    /// Whether `message` needs an answer: requests do, and so does anything
+    /// invalid, which is answered with an error
+    pub fn expects_response(message: &Value) -> bool {
+        !matches!(
+            Message::parse(message.clone()),
+            Ok(Message::Notification(_) | Message::Response(_))
+        )
+    }
+}
+
+/// Deserialize request params into their typed form; missing params read
+/// as an empty object
+pub fn parse_params<T: DeserializeOwned>(params: Option<Value>) -> Result<T, RpcError> {
+    serde_json::from_value(params.unwrap_or_else(|| json!({}))).map_err(RpcError::invalid_params)
+}
+
+/// Handler of one request method, given the shared state and the raw params
+pub type MethodHandler<S> =
+    for<'a> fn(&'a S, Option<Value>) -> BoxFuture<'a, Result<Value, RpcError>>;
+
+/// Request methods by name
+pub struct MethodRegistry<S> {
+    methods: HashMap<&'static str, MethodHandler<S>>,
+}
+
+impl<S: Sync> MethodRegistry<S> {
+    pub fn new() -> Self {
+        Self {
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Route `name` to `handler`
+    pub fn method(mut self, name: &'static str, handler: MethodHandler<S>) -> Self {
+        self.methods.insert(name, handler);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.methods.contains_key(name)
+    }
+
+    /// Handle one message or a batch of them. Returns `None` when nothing
+    /// needs a response.
+    pub async fn handle(&self, state: &S, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(batch) if batch.is_empty() => {
+                Some(Response::error(None, RpcError::invalid_request("empty batch")).to_value())
+            }
+            Value::Array(batch) => {
+                let mut responses = Vec::new();
+                for message in batch {
+                    responses.extend(self.handle_one(state, message).await);
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            message => self.handle_one(state, message).await,
+        }
+    }
+
+    async fn handle_one(&self, state: &S, message: Value) -> Option<Value> {
+        let message = match Message::parse(message) {
+            Ok(message) => message,
+            Err(response) => return Some(response.to_value()),
+        };
+
+        match message {
+            Message::Request(request) => Some(self.call(state, request).await.to_value()),
+            Message::Notification(notification) => {
+                match notification.method.as_str() {
+                    "initialized" | "notifications/initialized" => info!("MCP client initialized"),
+                    method => debug!("Ignoring notification {}", method),
+                }
+                None
+            }
+            Message::Response(response) => {
+                debug!("Ignoring response to request {:?}", response.id);
+                None
+            }
+        }
+    }
+
+    async fn call(&self, state: &S, request: Request) -> Response {
+        let Some(handler) = self.methods.get(request.method.as_str()) else {
+            return Response::error(
+                Some(request.id),
+                RpcError::method_not_found(&request.method),
+            );
+        };
+
+        // A panicking handler fails its own request, not the transport
+        let outcome = AssertUnwindSafe(handler(state, request.params))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| {
+                error!("Handler for {} panicked", request.method);
+                Err(RpcError::internal_error(format!(
+                    "{} failed",
+                    request.method
+                )))
+            });
+        Response {
+            id: Some(request.id),
+            outcome,
+        }
+    }
+}
+
+impl<S: Sync> Default for MethodRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> MethodRegistry<()> {
+        MethodRegistry::new()
+            .method("echo", |_, params| {
+                async move { Ok(params.unwrap_or(Value::Null)) }.boxed()
+            })
+            .method("count", |_, params| {
+                async move {
+                    #[derive(Deserialize)]
+                    struct Params {
+                        items: Vec<Value>,
+                    }
+                    let params: Params = parse_params(params)?;
+                    Ok(json!(params.items.len()))
+                }
+                .boxed()
+            })
+    }
+
+    #[tokio::test]
+    async fn requests_get_typed_errors_and_echoed_ids() {
+        let registry = registry();
+        let call = |message: Value| registry.handle(&(), message);
+
+        let response = call(json!({"jsonrpc": "2.0", "id": "a", "method": "echo", "params": [1]}))
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({"jsonrpc": "2.0", "id": "a", "result": [1]})
+        );
+
+        let response = call(json!({"jsonrpc": "2.0", "id": 7, "method": "nope"}))
+            .await
+            .unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], RpcError::METHOD_NOT_FOUND);
+
+        let response = call(json!({"jsonrpc": "2.0", "id": 8, "method": "count", "params": {}}))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], RpcError::INVALID_PARAMS);
+
+        for invalid in [
+            json!({"id": 9, "method": "echo"}),
+            json!({"jsonrpc": "2.0", "id": 9, "method": 5}),
+            json!({"jsonrpc": "2.0", "id": 9, "method": "echo", "params": "x"}),
+            json!({"jsonrpc": "2.0", "id": 9}),
+        ] {
+            let response = call(invalid).await.unwrap();
+            assert_eq!(response["error"]["code"], RpcError::INVALID_REQUEST);
+            assert_eq!(response["id"], 9);
+        }
+        let response = call(json!({"jsonrpc": "2.0", "id": null, "method": "echo"}))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], RpcError::INVALID_REQUEST);
+        assert_eq!(response["id"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn batches_answer_requests_only() {
+        let registry = registry();
+
+        let response = registry
+            .handle(
+                &(),
+                json!([
+                    {"jsonrpc": "2.0", "id": 1, "method": "echo", "params": {}},
+                    {"jsonrpc": "2.0", "method": "notifications/initialized"},
+                    {"jsonrpc": "2.0", "id": 5, "result": {}},
+                    42
+                ]),
+            )
+            .await
+            .unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[1]["error"]["code"], RpcError::INVALID_REQUEST);
+
+        let notifications = json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}]);
+        assert_eq!(registry.handle(&(), notifications).await, None);
+
+        let response = registry.handle(&(), json!([])).await.unwrap();
+        assert_eq!(response["error"]["code"], RpcError::INVALID_REQUEST);
+    }
 }
@@ -0,0 +1,309 @@
+/// MCP Streamable HTTP transport
+///
+/// One endpoint carries the whole protocol: POST delivers client messages,
+/// GET opens the session's server-to-client SSE stream and DELETE ends the
+/// session. Every request after `initialize` names its session in the
+/// `Mcp-Session-Id` header, and may repeat the negotiated version in
+/// `MCP-Protocol-Version`. The router refuses requests from browser pages
+/// on other origins (`refuse_origin`), so a page cannot reach a server
+/// listening on localhost by rebinding its own domain name.
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::task::JoinError;
+use tracing::{error, info, warn};
+
+use super::handler::{self as rpc, Message, RpcError};
+use super::server::{allows_batches, dispatch_message, ServerState};
+use super::session::{Session, PROTOCOL_VERSION_HEADER, SESSION_HEADER};
+
+/// POST: a request, notification, response or a batch of them. Requests
+/// are answered with JSON, or over an SSE stream when they outlast
+/// `sse_upgrade_after_ms` and the client accepts one.
+pub(super) async fn post_message(
+    State(server): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let message: Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let response = rpc::Response::error(None, RpcError::parse_error(e));
+            return (StatusCode::BAD_REQUEST, Json(response.to_value())).into_response();
+        }
+    };
+    if message["method"] == "initialize" {
+        return initialize(&server, &message).await;
+    }
+    let session = match session(&server, &headers) {
+        Ok(session) => session,
+        Err((status, message)) => return session_error(status, message),
+    };
+    if message.is_array() && !allows_batches(&session.protocol_version) {
+        let error = RpcError::invalid_request(format!(
+            "batches are not supported in protocol version {}",
+            session.protocol_version
+        ));
+        let response = rpc::Response::error(None, error);
+        return (StatusCode::BAD_REQUEST, Json(response.to_value())).into_response();
+    }
+
+    let messages = match &message {
+        Value::Array(batch) => batch.iter().collect(),
+        single => vec![single],
+    };
+    let initialized = messages.iter().any(|m| {
+        matches!(
+            m["method"].as_str(),
+            Some("initialized" | "notifications/initialized")
+        )
+    });
+    let has_requests = messages.is_empty() || messages.iter().any(|m| Message::expects_response(m));
+    if initialized {
+        session.mark_initialized();
+    }
+    let premature = messages
+        .iter()
+        .any(|m| Message::expects_response(m) && m["method"] != "ping");
+    if premature && !session.is_initialized() {
+        return session_error(
+            StatusCode::BAD_REQUEST,
+            "Session not initialized; send notifications/initialized first",
+        );
+    }
+    // Notifications and responses are only acknowledged
+    if !has_requests {
+        dispatch_message(&server, &message).await;
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    // The call keeps running if the client drops an SSE stream mid-way
+    let mut task = tokio::spawn({
+        let server = Arc::clone(&server);
+        let session = Arc::clone(&session);
+        async move {
+            let calls = progress_tokens(&message);
+            for (token, tool) in &calls {
+                session.notify(progress(token, 0, format!("Calling {}", tool)));
+            }
+            let response = dispatch_message(&server, &message).await;
+            for (token, tool) in &calls {
+                session.notify(progress(token, 1, format!("{} finished", tool)));
+            }
+            response
+        }
+    });
+    if accepts_event_stream(&headers) {
+        let upgrade_after = Duration::from_millis(server.settings.mcp.sse_upgrade_after_ms);
+        match tokio::time::timeout(upgrade_after, &mut task).await {
+            Ok(joined) => return respond(joined),
+            Err(_) => {
+                info!("Streaming slow response for session {}", session.id);
+                let events = stream::once(task).filter_map(|joined| async move {
+                    response_message(joined).map(|message| Ok::<_, Infallible>(event(&message)))
+                });
+                return Sse::new(events)
+                    .keep_alive(KeepAlive::default())
+                    .into_response();
+            }
+        }
+    }
+    respond(task.await)
+}
+
+/// GET: the session's stream for messages that are not responses
+pub(super) async fn open_stream(
+    State(server): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Response {
+    if !accepts_event_stream(&headers) {
+        return StatusCode::NOT_ACCEPTABLE.into_response();
+    }
+    let session = match session(&server, &headers) {
+        Ok(session) => session,
+        Err((status, message)) => return session_error(status, message),
+    };
+
+    // Ends when the session does, or when a newer stream replaces this one
+    let events = stream::unfold(session.open_stream(), |mut rx| async move {
+        let message = rx.recv().await?;
+        Some((Ok::<_, Infallible>(event(&message)), rx))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// DELETE: end the session
+pub(super) async fn end_session(
+    State(server): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Response {
+    match session(&server, &headers) {
+        Ok(session) => {
+            server.sessions.remove(&session.id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err((status, message)) => session_error(status, message),
+    }
+}
+
+// Answer initialize and open a session for the client
+async fn initialize(server: &ServerState, message: &Value) -> Response {
+    let Some(response) = dispatch_message(server, message).await else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    let Some(version) = response["result"]["protocolVersion"].as_str() else {
+        return Json(response).into_response();
+    };
+
+    let session = server
+        .sessions
+        .create(version, message["params"].get("clientInfo").cloned());
+    ([(SESSION_HEADER, session.id.clone())], Json(response)).into_response()
+}
+
+// The session named by the request, or the status to refuse it with
+fn session(
+    server: &ServerState,
+    headers: &HeaderMap,
+) -> Result<Arc<Session>, (StatusCode, &'static str)> {
+    let Some(id) = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Missing Mcp-Session-Id header; send initialize first",
+        ));
+    };
+    let session = server.sessions.get(id).ok_or((
+        StatusCode::NOT_FOUND,
+        "Unknown or expired session; send initialize again",
+    ))?;
+    // Clients that send the header must send the version they agreed on
+    let version = headers.get(PROTOCOL_VERSION_HEADER);
+    if version.is_some_and(|v| v.as_bytes() != session.protocol_version.as_bytes()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "MCP-Protocol-Version differs from the version negotiated in initialize",
+        ));
+    }
+    Ok(session)
+}
+
+/// The refusal for a request from a disallowed origin. Requests without an
+/// Origin come from non-browser clients; browsers may call from loopback
+/// pages or from the configured origins.
+pub(super) fn refuse_origin(server: &ServerState, headers: &HeaderMap) -> Option<Response> {
+    let origin = headers.get(header::ORIGIN)?;
+    let allowed = origin
+        .to_str()
+        .ok()
+        .is_some_and(|origin| origin_allowed(&server.settings.mcp.allowed_origins, origin));
+    if allowed {
+        return None;
+    }
+    warn!("Refusing MCP request from origin {:?}", origin);
+    Some(session_error(StatusCode::FORBIDDEN, "Origin not allowed"))
+}
+
+/// Whether browser pages on `origin` may call the server
+pub(super) fn origin_allowed(allowed_origins: &[String], origin: &str) -> bool {
+    is_loopback_origin(origin)
+        || allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/') == origin)
+}
+
+fn is_loopback_origin(origin: &str) -> bool {
+    let Some(authority) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+// Progress tokens of the tool calls in a message, with the tool each names
+fn progress_tokens(message: &Value) -> Vec<(Value, String)> {
+    let calls = match message {
+        Value::Array(batch) => batch.iter().collect(),
+        single => vec![single],
+    };
+    calls
+        .into_iter()
+        .filter(|m| m["method"] == "tools/call")
+        .filter_map(|m| {
+            let token = m["params"]["_meta"].get("progressToken")?;
+            let tool = m["params"]["name"].as_str().unwrap_or_default();
+            Some((token.clone(), tool.to_string()))
+        })
+        .collect()
+}
+
+fn progress(token: &Value, progress: u32, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/progress",
+        "params": {
+            "progressToken": token,
+            "progress": progress,
+            "total": 1,
+            "message": message
+        }
+    })
+}
+
+fn session_error(status: StatusCode, message: &str) -> Response {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": -32000, "message": message }
+    });
+    (status, Json(body)).into_response()
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|accept| accept.contains("text/event-stream"))
+}
+
+fn respond(joined: Result<Option<Value>, JoinError>) -> Response {
+    match response_message(joined) {
+        Some(message) => Json(message).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+// What a dispatch task produced, with a panic reported as an internal error
+fn response_message(joined: Result<Option<Value>, JoinError>) -> Option<Value> {
+    joined.unwrap_or_else(|e| {
+        error!("MCP request handler failed: {}", e);
+        Some(rpc::Response::error(None, RpcError::internal_error(e)).to_value())
+    })
+}
+
+fn event(message: &Value) -> Event {
+    Event::default().event("message").data(message.to_string())
+}
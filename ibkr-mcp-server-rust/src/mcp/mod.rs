@@ -1,6 +1,11 @@
 pub mod handler;
+mod http;
 /// MCP server module
+pub mod order_keys;
 pub mod server;
+pub mod session;
+mod stdio;
+pub mod tickets;
 pub mod tools;
 
 pub use server::MCPServer;
@@ -0,0 +1,268 @@
+/// Client order keys for idempotent order placement
+///
+/// Agents retry tool calls that time out. A `place_order` call carrying a
+/// `client_order_key` is recorded here with the order ID it produced, so a
+/// retry gets that order back instead of placing a duplicate. Keys are kept
+/// in `<state_dir>/client_order_keys.json` until the TTL expires.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    error::{IBKRMCPError, Result},
+    models::{Contract, Order},
+    persist,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct KeyRecord {
+    order_id: i32,
+    /// The order the key was first used for, to catch reuse for another one
+    fingerprint: String,
+    created_at: DateTime<Utc>,
+}
+
+/// Outcome of claiming a key before placing its order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyClaim {
+    /// First use: place the order, then `complete` or `release` the key
+    Claimed,
+    /// The order was already placed under this ID
+    Placed(i32),
+}
+
+pub struct OrderKeyStore {
+    ttl: Duration,
+    path: Option<PathBuf>,
+    keys: Mutex<HashMap<String, KeyRecord>>,
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl OrderKeyStore {
+    /// Store persisting to `<state_dir>/client_order_keys.json`, or kept in
+    /// memory only when `state_dir` is `None`
+    pub fn new(state_dir: Option<&Path>, ttl: Duration) -> Self {
+        let path = state_dir.map(|dir| dir.join("client_order_keys.json"));
+        let keys = match path.as_deref().map(load).transpose() {
+            Ok(keys) => keys.unwrap_or_default(),
+            Err(e) => {
+                warn!("Ignoring unreadable client order keys: {}", e);
+                HashMap::new()
+            }
+        };
+        Self {
+            ttl,
+            path,
+            keys: Mutex::new(keys),
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// The order already placed under `key`, without claiming it
+    pub fn placed(&self, key: &str, contract: &Contract, order: &Order) -> Result<Option<i32>> {
+        let mut keys = self.keys.lock().unwrap();
+        self.purge_expired(&mut keys);
+        placed(&keys, key, contract, order)
+    }
+
+    /// Claim `key` for an order, or find the order it already produced
+    pub fn claim(&self, key: &str, contract: &Contract, order: &Order) -> Result<KeyClaim> {
+        let mut keys = self.keys.lock().unwrap();
+        self.purge_expired(&mut keys);
+
+        if let Some(order_id) = placed(&keys, key, contract, order)? {
+            return Ok(KeyClaim::Placed(order_id));
+        }
+        if !self.in_flight.lock().unwrap().insert(key.to_string()) {
+            return Err(IBKRMCPError::Order(format!(
+                "Order with client_order_key {} is still being placed",
+                key
+            )));
+        }
+        Ok(KeyClaim::Claimed)
+    }
+
+    /// Record the order placed for a claimed key
+    pub fn complete(
+        &self,
+        key: &str,
+        contract: &Contract,
+        order: &Order,
+        order_id: i32,
+    ) -> Result<()> {
+        let record = KeyRecord {
+            order_id,
+            fingerprint: fingerprint(contract, order),
+            created_at: Utc::now(),
+        };
+        let mut keys = self.keys.lock().unwrap();
+        keys.insert(key.to_string(), record);
+        self.in_flight.lock().unwrap().remove(key);
+        self.persist(&keys)
+    }
+
+    /// Give up a claimed key after its order failed, so it can be retried
+    pub fn release(&self, key: &str) {
+        self.in_flight.lock().unwrap().remove(key);
+    }
+
+    fn purge_expired(&self, keys: &mut HashMap<String, KeyRecord>) {
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX);
+        let now = Utc::now();
+        keys.retain(|_, record| record.created_at + ttl > now);
+    }
+
+    fn persist(&self, keys: &HashMap<String, KeyRecord>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        persist::write_atomically(path, &serde_json::to_vec_pretty(keys)?)
+    }
+}
+
+fn load(path: &Path) -> Result<HashMap<String, KeyRecord>> {
+    match persist::read(path)? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn placed(
+    keys: &HashMap<String, KeyRecord>,
+    key: &str,
+    contract: &Contract,
+    order: &Order,
+) -> Result<Option<i32>> {
+    match keys.get(key) {
+        Some(record) => {
+            ensure_same_order(key, &record.fingerprint, contract, order)?;
+            Ok(Some(record.order_id))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Refuse a key presented with an order other than the one, identified by
+/// `used_for`, it was first used for
+pub fn ensure_same_order(
+    key: &str,
+    used_for: &str,
+    contract: &Contract,
+    order: &Order,
+) -> Result<()> {
+    if used_for != fingerprint(contract, order) {
+        return Err(reused(key));
+    }
+    Ok(())
+}
+
+/// Error for a key presented with an order other than the one it placed
+pub fn reused(key: &str) -> IBKRMCPError {
+    IBKRMCPError::InvalidParameter(format!(
+        "client_order_key {} was already used for a different order",
+        key
+    ))
+}
+
+/// What identifies the order a key was used for: the fields TWS reports
+/// back for working and completed orders alike, so an order found at the
+/// broker compares equal to the request that placed it
+pub fn fingerprint(contract: &Contract, order: &Order) -> String {
+    let price = |price: Option<f64>| match price {
+        Some(price) if price != 0.0 => price.to_string(),
+        _ => String::new(),
+    };
+    [
+        contract.symbol.to_uppercase(),
+        contract.sec_type.as_str().to_string(),
+        order.action.as_str().to_string(),
+        order.total_quantity.to_string(),
+        order.order_type.as_str().to_string(),
+        price(order.lmt_price),
+        price(order.aux_price),
+        order.time_in_force.as_str().to_string(),
+    ]
+    .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OrderAction, OrderType, SecType};
+
+    #[test]
+    fn keys_survive_restart_and_reject_reuse() {
+        let dir = tempfile::tempdir().unwrap();
+        let contract = Contract::new("AAPL", SecType::Stock);
+        let order = Order::new(OrderAction::Buy, 10.0, OrderType::Market);
+        let store = OrderKeyStore::new(Some(dir.path()), Duration::from_secs(60));
+
+        assert_eq!(
+            store.claim("k1", &contract, &order).unwrap(),
+            KeyClaim::Claimed
+        );
+        // A concurrent retry must not place a second order
+        assert!(store.claim("k1", &contract, &order).is_err());
+        store.complete("k1", &contract, &order, 42).unwrap();
+
+        let restarted = OrderKeyStore::new(Some(dir.path()), Duration::from_secs(60));
+        assert_eq!(
+            restarted.claim("k1", &contract, &order).unwrap(),
+            KeyClaim::Placed(42)
+        );
+        let other = Order::new(OrderAction::Sell, 10.0, OrderType::Market);
+        assert!(matches!(
+            restarted.claim("k1", &contract, &other),
+            Err(IBKRMCPError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn fingerprints_ignore_fields_the_broker_does_not_report() {
+        let contract = Contract::new("aapl", SecType::Stock);
+        let mut order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit);
+        order.lmt_price = Some(150.0);
+        let mut reported = order.clone();
+        reported.order_ref = Some("k1".to_string());
+        reported.aux_price = Some(0.0);
+        reported.transmit = false;
+
+        let broker = Contract::new("AAPL", SecType::Stock);
+        assert_eq!(
+            fingerprint(&contract, &order),
+            fingerprint(&broker, &reported)
+        );
+        reported.lmt_price = Some(151.0);
+        assert_ne!(
+            fingerprint(&contract, &order),
+            fingerprint(&broker, &reported)
+        );
+    }
+
+    #[test]
+    fn released_and_expired_keys_can_be_claimed_again() {
+        let contract = Contract::new("AAPL", SecType::Stock);
+        let order = Order::new(OrderAction::Buy, 10.0, OrderType::Market);
+
+        let store = OrderKeyStore::new(None, Duration::from_secs(60));
+        store.claim("k1", &contract, &order).unwrap();
+        store.release("k1");
+        assert_eq!(
+            store.claim("k1", &contract, &order).unwrap(),
+            KeyClaim::Claimed
+        );
+
+        let store = OrderKeyStore::new(None, Duration::ZERO);
+        store.claim("k1", &contract, &order).unwrap();
+        store.complete("k1", &contract, &order, 42).unwrap();
+        assert_eq!(
+            store.claim("k1", &contract, &order).unwrap(),
+            KeyClaim::Claimed
+        );
+    }
+}
@@ -1,26 +1,76 @@
 /// MCP Server implementation
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::path::Path;
 use std::sync::Arc;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use std::time::Duration;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer,
+};
 use tracing::{error, info};
 
+use super::handler::{parse_params, MethodRegistry, RpcError};
+use super::http;
+use super::order_keys::OrderKeyStore;
+use super::session::{SessionStore, PROTOCOL_VERSION_HEADER, SESSION_HEADER};
+use super::tickets::TicketStore;
+use super::tools::{self, tool_result, HaltTrading, ResumeTrading, Tool, ToolRegistry};
 use crate::{
-    config::Settings,
+    config::{Settings, Transport},
     error::{IBKRMCPError, Result},
     ibkr::IBKRClient,
+    models::{CallToolResult, MCPResponse},
 };
 
-// Shared state for Axum handlers
-struct ServerState {
-    ibkr_client: Arc<IBKRClient>,
-    settings: Settings,
+/// Protocol versions `initialize` accepts, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Whether clients on `protocol_version` may send JSON-RPC batches;
+/// 2025-06-18 dropped them. Versions are dates, so they compare as strings.
+pub(super) fn allows_batches(protocol_version: &str) -> bool {
+    protocol_version < "2025-06-18"
+}
+
+/// State shared by every transport and tool
+pub struct ServerState {
+    pub(super) ibkr_client: Arc<IBKRClient>,
+    pub(super) settings: Settings,
+    pub(super) tickets: TicketStore,
+    pub(super) order_keys: OrderKeyStore,
+    pub(super) sessions: SessionStore,
+    methods: MethodRegistry<ServerState>,
+    tools: ToolRegistry,
+}
+
+impl ServerState {
+    pub(super) fn new(ibkr_client: Arc<IBKRClient>, settings: Settings) -> Self {
+        Self {
+            ibkr_client,
+            tickets: TicketStore::new(Duration::from_secs(
+                settings.mcp.confirmation.ticket_ttl_secs,
+            )),
+            order_keys: OrderKeyStore::new(
+                settings.ibkr.state_dir.as_deref().map(Path::new),
+                Duration::from_secs(settings.mcp.order_key_ttl_secs),
+            ),
+            sessions: SessionStore::new(Duration::from_secs(settings.mcp.session_ttl_secs)),
+            methods: methods(),
+            tools: tools::registry(),
+            settings,
+        }
+    }
 }
 
 pub struct MCPServer {
@@ -30,7 +80,9 @@ pub struct MCPServer {
 
 impl MCPServer {
     pub fn new(settings: Settings) -> Self {
-        let ibkr_client = Arc::new(IBKRClient::new(settings.ibkr.clone()));
+        let ibkr_client = Arc::new(
+            IBKRClient::new(settings.ibkr.clone()).with_risk_limits(settings.risk.clone()),
+        );
 
         Self {
             ibkr_client,
@@ -38,34 +90,78 @@ impl MCPServer {
         }
     }
 
+    /// Serve MCP clients over the configured transport
     pub async fn run(self) -> Result<()> {
-        let ibkr_client = Arc::clone(&self.ibkr_client);
-        let settings = self.settings.clone();
+        match self.settings.mcp.transport {
+            Transport::Http => self.run_http().await,
+            Transport::Stdio => super::stdio::serve(self.start().await).await,
+        }
+    }
+
+    // Connect to IBKR and build the state every request shares
+    async fn start(&self) -> Arc<ServerState> {
+        let settings = &self.settings;
 
         // Connect to IBKR
         info!("Connecting to IBKR...");
-        if let Err(e) = ibkr_client.connect().await {
+        if let Err(e) = self.ibkr_client.connect().await {
             error!("Failed to connect to IBKR: {}", e);
             info!("Server will start without IBKR connection");
         }
 
-        // Create Arc wrapper for shared state
-        let server_state = Arc::new(ServerState {
-            ibkr_client,
-            settings: settings.clone(),
-        });
+        Arc::new(ServerState::new(
+            Arc::clone(&self.ibkr_client),
+            settings.clone(),
+        ))
+    }
 
-        // Build router
-        let app = Router::new()
+    /// Connect to IBKR and build the HTTP application, for embedding the
+    /// server or serving it on a listener of your own
+    pub async fn router(&self) -> Router {
+        let server_state = self.start().await;
+
+        // Kept out of the CORS layer so browsers never send cross-origin
+        // requests to them
+        let admin = Router::new()
+            .route("/admin/halt", post(admin_halt))
+            .route("/admin/resume", post(admin_resume))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(&server_state),
+                require_admin_token,
+            ));
+
+        Router::new()
             .route("/health", get(health_check))
             .route("/mcp/tools", post(handle_tool_call))
             .route("/mcp/status", get(connection_status))
-            // Standard MCP protocol endpoints
-            .route("/mcp", post(handle_mcp_request))
-            .route("/", post(handle_mcp_request))
+            // Streamable HTTP MCP endpoint
+            .route(
+                "/mcp",
+                post(http::post_message)
+                    .get(http::open_stream)
+                    .delete(http::end_session),
+            )
+            .route(
+                "/",
+                post(http::post_message)
+                    .get(http::open_stream)
+                    .delete(http::end_session),
+            )
+            // Every route that runs tools refuses pages on other origins,
+            // and CORS only lets the allowed ones read responses
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(&server_state),
+                require_allowed_origin,
+            ))
+            .layer(cors(&self.settings.mcp.allowed_origins))
+            .merge(admin)
             .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive())
-            .with_state(server_state);
+            .with_state(server_state)
+    }
+
+    async fn run_http(self) -> Result<()> {
+        let settings = self.settings.clone();
+        let app = self.router().await;
 
         // Start server
         let addr = format!("{}:{}", settings.mcp.host, settings.mcp.port);
@@ -73,7 +169,7 @@ impl MCPServer {
 
         let listener = tokio::net::TcpListener::bind(&addr)
             .await
-            .map_err(|e| IBKRMCPError::Io(e))?;
+            .map_err(IBKRMCPError::Io)?;
 
         info!("🚀 IBKR MCP Server (Rust) listening on {}", addr);
 
@@ -92,357 +188,268 @@ impl MCPServer {
     }
 }
 
-// Handle standard MCP protocol requests
-async fn handle_mcp_request(
-    State(server): State<Arc<ServerState>>,
-    Json(request): Json<Value>,
-) -> impl IntoResponse {
-    info!("Received MCP request: {:?}", request);
-
-    // Extract method from request
-    let method = request["method"].as_str().unwrap_or("");
-
-    let response = match method {
-        "initialize" => {
-            // MCP initialization request
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "result": {
-                    "protocolVersion": "2024-11-05",
-                    "capabilities": {
-                        "tools": {}
-                    },
-                    "serverInfo": {
-                        "name": "ibkr-mcp-server",
-                        "version": crate::VERSION
-                    }
-                }
-            })
-        }
-        "initialized" => {
-            // MCP initialized notification - no response needed for notifications
-            // Just acknowledge we received it
-            info!("MCP client initialized");
-            // For notifications, we should return 204 No Content or an empty success
-            return (StatusCode::NO_CONTENT, Json(json!({})));
-        }
-        "tools/list" => {
-            // Return list of available tools
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "result": {
-                    "tools": [
-                        {
-                            "name": "get_account_summary",
-                            "description": "Get account summary including balance and portfolio information",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {}
-                            }
-                        },
-                        {
-                            "name": "get_positions",
-                            "description": "Get current positions",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {}
-                            }
-                        },
-                        {
-                            "name": "place_order",
-                            "description": "Place a new order",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {
-                                    "symbol": { "type": "string" },
-                                    "action": { "type": "string", "enum": ["BUY", "SELL"] },
-                                    "quantity": { "type": "number" },
-                                    "order_type": { "type": "string", "enum": ["MKT", "LMT", "STP"] }
-                                },
-                                "required": ["symbol", "action", "quantity"]
-                            }
-                        },
-                        {
-                            "name": "get_market_data",
-                            "description": "Get real-time market data for a symbol",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {
-                                    "symbol": { "type": "string" }
-                                },
-                                "required": ["symbol"]
-                            }
-                        },
-                        {
-                            "name": "connection_status",
-                            "description": "Check IBKR connection status",
-                            "inputSchema": {
-                                "type": "object",
-                                "properties": {}
-                            }
-                        }
-                    ]
-                }
-            })
-        }
-        "tools/call" => {
-            // Extract tool name and arguments
-            let params = &request["params"];
-            let tool_name = params["name"].as_str().unwrap_or("");
-            let arguments = &params["arguments"];
-
-            // Process the tool call inline
-            let tool_result = process_tool_call(&server, tool_name, arguments).await;
-
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "result": tool_result
-            })
-        }
-        _ => {
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "error": {
-                    "code": -32601,
-                    "message": format!("Method not found: {}", method)
-                }
-            })
+/// JSON-RPC methods served over every transport
+pub(super) fn methods() -> MethodRegistry<ServerState> {
+    MethodRegistry::<ServerState>::new()
+        .method("initialize", |_, params| initialize(params).boxed())
+        .method("ping", |_, _| async { Ok(json!({})) }.boxed())
+        .method("tools/list", |server, _| {
+            async move {
+                let readonly = server.ibkr_client.is_readonly();
+                let halted = server.ibkr_client.trading_halt().is_some();
+                Ok(json!({ "tools": server.tools.definitions(readonly, halted) }))
+            }
+            .boxed()
+        })
+        .method("tools/call", |server, params| {
+            call_tool(server, params).boxed()
+        })
+        .method("resources/list", |_, _| {
+            async { Ok(json!({ "resources": [] })) }.boxed()
+        })
+        .method("resources/templates/list", |_, _| {
+            async { Ok(json!({ "resourceTemplates": [] })) }.boxed()
+        })
+        .method("resources/read", |_, params| read_resource(params).boxed())
+        .method("prompts/list", |_, _| {
+            async { Ok(json!({ "prompts": [] })) }.boxed()
+        })
+        .method("prompts/get", |_, params| get_prompt(params).boxed())
+}
+
+/// Dispatch one JSON-RPC message or a batch of them. Returns `None` when
+/// nothing needs a response.
+pub(super) async fn dispatch_message(server: &ServerState, message: &Value) -> Option<Value> {
+    info!("Received MCP message: {:?}", message);
+    server.methods.handle(server, message.clone()).await
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    protocol_version: String,
+}
+
+async fn initialize(params: Option<Value>) -> std::result::Result<Value, RpcError> {
+    let params: InitializeParams = parse_params(params)?;
+
+    // Agree on the client's version if we speak it, else offer ours
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| **v == params.protocol_version)
+        .unwrap_or(&PROTOCOL_VERSIONS[0]);
+
+    Ok(json!({
+        "protocolVersion": version,
+        "capabilities": {
+            "tools": { "listChanged": true },
+            "resources": {},
+            "prompts": {}
+        },
+        "serverInfo": {
+            "name": "ibkr-mcp-server",
+            "version": crate::VERSION
         }
-    };
+    }))
+}
 
-    (StatusCode::OK, Json(response))
+#[derive(Deserialize)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Option<Value>,
+}
+
+async fn call_tool(
+    server: &ServerState,
+    params: Option<Value>,
+) -> std::result::Result<Value, RpcError> {
+    let params: CallToolParams = parse_params(params)?;
+    let response = server
+        .tools
+        .call(
+            server,
+            &params.name,
+            params.arguments.unwrap_or(Value::Null),
+        )
+        .await?;
+    Ok(json!(CallToolResult::from(response)))
+}
+
+#[derive(Deserialize)]
+struct ReadResourceParams {
+    uri: String,
+}
+
+// No resources are published yet, so every URI is unknown
+async fn read_resource(params: Option<Value>) -> std::result::Result<Value, RpcError> {
+    let params: ReadResourceParams = parse_params(params)?;
+    let mut error = RpcError::new(-32002, "Resource not found");
+    error.data = Some(json!({ "uri": params.uri }));
+    Err(error)
+}
+
+#[derive(Deserialize)]
+struct GetPromptParams {
+    name: String,
+}
+
+// No prompts are published yet, so every name is unknown
+async fn get_prompt(params: Option<Value>) -> std::result::Result<Value, RpcError> {
+    let params: GetPromptParams = parse_params(params)?;
+    Err(RpcError::invalid_params(format!(
+        "Unknown prompt: {}",
+        params.name
+    )))
 }
 
 // Health check endpoint
-async fn health_check() -> Json<Value> {
+async fn health_check(State(server): State<Arc<ServerState>>) -> Json<Value> {
     Json(json!({
         "status": "healthy",
         "service": "ibkr-mcp-server-rust",
+        "trading": trading_status(&server.ibkr_client),
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
 }
 
+// Tool routes refuse browser pages from origins that are not allowed
+async fn require_allowed_origin(
+    State(server): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    match http::refuse_origin(&server, &headers) {
+        Some(refusal) => refusal,
+        None => next.run(request).await,
+    }
+}
+
+// CORS for browser clients on loopback pages and the configured origins
+fn cors(allowed_origins: &[String]) -> CorsLayer {
+    let allowed_origins = allowed_origins.to_vec();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| http::origin_allowed(&allowed_origins, origin))
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            HeaderName::from_static(SESSION_HEADER),
+            HeaderName::from_static(PROTOCOL_VERSION_HEADER),
+        ])
+        .expose_headers([HeaderName::from_static(SESSION_HEADER)])
+}
+
+// Admin routes require `mcp.admin_token` as a bearer token and are
+// refused outright while no token is configured
+async fn require_admin_token(
+    State(server): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = server
+        .settings
+        .mcp
+        .admin_token
+        .as_deref()
+        .filter(|token| !token.is_empty())
+    else {
+        return (
+            StatusCode::FORBIDDEN,
+            "Admin routes are disabled; set mcp.admin_token to enable them",
+        )
+            .into_response();
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|token| tokens_match(token.as_bytes(), expected.as_bytes())) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Missing or invalid admin token",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+// Compare without exiting at the first differing byte, so response times
+// do not reveal how much of a guess was right
+fn tokens_match(presented: &[u8], expected: &[u8]) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Kill switch endpoints for operators without an MCP client; the body
+// takes the halt_trading parameters. They run through the tool registry,
+// so read-only mode applies as it does to tools/call.
+async fn admin_halt(
+    State(server): State<Arc<ServerState>>,
+    Json(params): Json<Value>,
+) -> Json<MCPResponse<Value>> {
+    admin_tool(&server, HaltTrading::NAME, params).await
+}
+
+async fn admin_resume(State(server): State<Arc<ServerState>>) -> Json<MCPResponse<Value>> {
+    admin_tool(&server, ResumeTrading::NAME, json!({})).await
+}
+
+async fn admin_tool(server: &ServerState, name: &str, params: Value) -> Json<MCPResponse<Value>> {
+    Json(match server.tools.call(server, name, params).await {
+        Ok(response) => response,
+        Err(e) => tool_result(server, Err(IBKRMCPError::InvalidParameter(e.message))),
+    })
+}
+
+/// Kill switch state, as the status outputs report it
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TradingStatus {
+    pub halted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub halted_at: Option<DateTime<Utc>>,
+}
+
+pub(super) fn trading_status(client: &IBKRClient) -> TradingStatus {
+    let halt = client.trading_halt();
+    TradingStatus {
+        halted: halt.is_some(),
+        halted_at: halt.as_ref().map(|halt| halt.halted_at),
+        reason: halt.map(|halt| halt.reason),
+    }
+}
+
 // Connection status endpoint
 async fn connection_status(State(server): State<Arc<ServerState>>) -> Json<Value> {
     let connected = server.ibkr_client.is_connected().await;
 
     Json(json!({
         "connected": connected,
+        "state": server.ibkr_client.connection_state().await,
         "host": server.settings.ibkr.host,
         "port": server.settings.ibkr.port,
         "client_id": server.settings.ibkr.client_id,
+        "backend": server.ibkr_client.backend_kind(),
+        "readonly": server.ibkr_client.is_readonly(),
+        "trading": trading_status(&server.ibkr_client),
+        "sessions": server.sessions.len(),
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
 }
 
-// Helper function to process tool calls
-async fn process_tool_call(server: &ServerState, tool_name: &str, params: &Value) -> Value {
-    match tool_name {
-        "get_account_summary" => match server.ibkr_client.get_account_summary().await {
-            Ok(data) => json!({
-                "success": true,
-                "data": data,
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-            Err(e) => json!({
-                "success": false,
-                "error": e.to_string(),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-        },
-        "get_positions" => match server.ibkr_client.get_positions().await {
-            Ok(positions) => json!({
-                "success": true,
-                "data": positions,
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-            Err(e) => json!({
-                "success": false,
-                "error": e.to_string(),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-        },
-        "place_order" => {
-            // Extract order parameters
-            let symbol = params["symbol"].as_str().unwrap_or("");
-            let sec_type = params["sec_type"].as_str().unwrap_or("STK");
-            let action = params["action"].as_str().unwrap_or("BUY");
-            let quantity = params["quantity"].as_f64().unwrap_or(0.0);
-            let order_type = params["order_type"].as_str().unwrap_or("MKT");
-            let limit_price = params["limit_price"].as_f64();
-
-            // Create contract and order
-            use crate::models::{Contract, Order, OrderAction, OrderType as OT, SecType as ST};
-
-            let contract = Contract::new(
-                symbol,
-                match sec_type {
-                    "STK" => ST::Stock,
-                    "OPT" => ST::Option,
-                    "FUT" => ST::Future,
-                    _ => ST::Stock,
-                },
-            );
-
-            let mut order = Order::new(
-                if action == "BUY" {
-                    OrderAction::Buy
-                } else {
-                    OrderAction::Sell
-                },
-                quantity,
-                match order_type {
-                    "LMT" => OT::Limit,
-                    "STP" => OT::Stop,
-                    _ => OT::Market,
-                },
-            );
-
-            if let Some(price) = limit_price {
-                order = order.with_limit_price(price);
-            }
-
-            match server.ibkr_client.place_order(&contract, &order).await {
-                Ok(order_id) => json!({
-                    "success": true,
-                    "data": {
-                        "order_id": order_id,
-                        "symbol": symbol,
-                        "action": action,
-                        "quantity": quantity
-                    },
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-                Err(e) => json!({
-                    "success": false,
-                    "error": e.to_string(),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-            }
-        }
-        "cancel_order" => {
-            let order_id = params["order_id"].as_i64().unwrap_or(0) as i32;
-
-            match server.ibkr_client.cancel_order(order_id).await {
-                Ok(cancelled) => json!({
-                    "success": true,
-                    "data": {
-                        "order_id": order_id,
-                        "cancelled": cancelled
-                    },
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-                Err(e) => json!({
-                    "success": false,
-                    "error": e.to_string(),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-            }
-        }
-        "get_open_orders" => match server.ibkr_client.get_open_orders().await {
-            Ok(orders) => json!({
-                "success": true,
-                "data": orders,
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-            Err(e) => json!({
-                "success": false,
-                "error": e.to_string(),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-        },
-        "get_market_data" => {
-            let symbol = params["symbol"].as_str().unwrap_or("AAPL");
-            let sec_type = params["sec_type"].as_str().unwrap_or("STK");
-
-            use crate::models::{Contract, SecType as ST};
-            let contract = Contract::new(
-                symbol,
-                match sec_type {
-                    "STK" => ST::Stock,
-                    _ => ST::Stock,
-                },
-            );
-
-            match server.ibkr_client.get_market_data(&contract).await {
-                Ok(data) => json!({
-                    "success": true,
-                    "data": data,
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-                Err(e) => json!({
-                    "success": false,
-                    "error": e.to_string(),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-            }
-        }
-        "get_historical_data" => {
-            let symbol = params["symbol"].as_str().unwrap_or("AAPL");
-            let duration = params["duration"].as_str().unwrap_or("1 D");
-            let bar_size = params["bar_size"].as_str().unwrap_or("1 min");
-            let what_to_show = params["what_to_show"].as_str().unwrap_or("TRADES");
-
-            use crate::models::{Contract, SecType as ST};
-            let contract = Contract::new(symbol, ST::Stock);
-
-            match server
-                .ibkr_client
-                .get_historical_data(&contract, duration, bar_size, what_to_show)
-                .await
-            {
-                Ok(bars) => json!({
-                    "success": true,
-                    "data": bars,
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-                Err(e) => json!({
-                    "success": false,
-                    "error": e.to_string(),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                }),
-            }
-        }
-        "reconnect" => match server.ibkr_client.reconnect().await {
-            Ok(_) => json!({
-                "success": true,
-                "data": {
-                    "reconnected": true
-                },
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-            Err(e) => json!({
-                "success": false,
-                "error": e.to_string(),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            }),
-        },
-        "connection_status" => {
-            let connected = server.ibkr_client.is_connected().await;
-            json!({
-                "success": true,
-                "data": {
-                    "connected": connected,
-                    "host": server.settings.ibkr.host,
-                    "port": server.settings.ibkr.port
-                },
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })
-        }
-        _ => {
-            json!({
-                "success": false,
-                "error": format!("Unknown tool: {}", tool_name),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })
-        }
-    }
-}
-
 // Tool call handler
 async fn handle_tool_call(
     State(server): State<Arc<ServerState>>,
@@ -454,7 +461,10 @@ async fn handle_tool_call(
     let tool_name = request["tool"].as_str().unwrap_or("");
     let params = &request["parameters"];
 
-    let response = process_tool_call(&server, tool_name, params).await;
+    let response = match server.tools.call(&server, tool_name, params.clone()).await {
+        Ok(response) => response,
+        Err(e) => tool_result::<Value>(&server, Err(IBKRMCPError::InvalidParameter(e.message))),
+    };
 
     (StatusCode::OK, Json(response))
 }
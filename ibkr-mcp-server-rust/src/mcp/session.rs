@@ -0,0 +1,185 @@
+/// Streamable HTTP sessions
+///
+/// `initialize` over HTTP opens a session whose ID the client echoes in the
+/// `Mcp-Session-Id` header of every later request. A session remembers what
+/// was negotiated and owns the `GET /mcp` SSE stream the server uses for
+/// messages that are not the response to a request. Sessions end with
+/// `DELETE /mcp` or after the idle TTL.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// Header carrying the session ID
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol version on later requests
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+pub struct Session {
+    pub id: String,
+    /// Protocol version agreed on in `initialize`
+    pub protocol_version: String,
+    /// `clientInfo` sent with `initialize`
+    pub client_info: Option<Value>,
+    initialized: Mutex<bool>,
+    last_seen: Mutex<Instant>,
+    stream: Mutex<Option<mpsc::UnboundedSender<Value>>>,
+}
+
+impl Session {
+    /// Whether the client has sent `notifications/initialized`
+    pub fn is_initialized(&self) -> bool {
+        *self.initialized.lock().unwrap()
+    }
+
+    pub fn mark_initialized(&self) {
+        *self.initialized.lock().unwrap() = true;
+    }
+
+    /// Open the server-to-client stream. A newer stream replaces an older
+    /// one, so no message is delivered twice.
+    pub fn open_stream(&self) -> mpsc::UnboundedReceiver<Value> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.stream.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    /// Send a message on the open stream; false if no client is listening
+    pub fn notify(&self, message: Value) -> bool {
+        let mut stream = self.stream.lock().unwrap();
+        match stream.as_ref().map(|tx| tx.send(message)) {
+            Some(Ok(())) => true,
+            Some(Err(_)) => {
+                // The client went away
+                *stream = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn close_stream(&self) {
+        self.stream.lock().unwrap().take();
+    }
+}
+
+pub struct SessionStore {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open a session after a successful `initialize`
+    pub fn create(&self, protocol_version: &str, client_info: Option<Value>) -> Arc<Session> {
+        // 128 random bits: the ID is the only credential a session has
+        let id = format!("{:032x}", rand::random::<u128>());
+        let session = Arc::new(Session {
+            id: id.clone(),
+            protocol_version: protocol_version.to_string(),
+            client_info,
+            initialized: Mutex::new(false),
+            last_seen: Mutex::new(Instant::now()),
+            stream: Mutex::new(None),
+        });
+
+        let mut sessions = self.sessions.lock().unwrap();
+        self.purge_expired(&mut sessions);
+        sessions.insert(id.clone(), Arc::clone(&session));
+        info!("Opened MCP session {}", id);
+        session
+    }
+
+    /// Look up a live session and mark it as used
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.purge_expired(&mut sessions);
+        let session = sessions.get(id)?;
+        *session.last_seen.lock().unwrap() = Instant::now();
+        Some(Arc::clone(session))
+    }
+
+    /// End a session, closing its stream; false if it did not exist
+    pub fn remove(&self, id: &str) -> bool {
+        let removed = self.sessions.lock().unwrap().remove(id);
+        if let Some(session) = &removed {
+            session.close_stream();
+            info!("Closed MCP session {}", id);
+        }
+        removed.is_some()
+    }
+
+    /// Send a message to every session with an open stream
+    pub fn broadcast(&self, message: &Value) {
+        for session in self.sessions.lock().unwrap().values() {
+            session.notify(message.clone());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn purge_expired(&self, sessions: &mut HashMap<String, Arc<Session>>) {
+        sessions.retain(|_, session| {
+            let live = session.last_seen.lock().unwrap().elapsed() < self.ttl;
+            if !live {
+                session.close_stream();
+            }
+            live
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sessions_end_on_remove_or_idle_timeout() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let session = store.create("2025-03-26", None);
+        assert!(store.get(&session.id).is_some());
+
+        let mut stream = session.open_stream();
+        assert!(session.notify(json!({"method": "ping"})));
+        assert_eq!(stream.try_recv().unwrap()["method"], "ping");
+
+        assert!(store.remove(&session.id));
+        assert!(!store.remove(&session.id));
+        assert!(store.get(&session.id).is_none());
+        // Removing the session closed its stream
+        assert!(!session.notify(json!({})));
+
+        let store = SessionStore::new(Duration::ZERO);
+        let session = store.create("2025-03-26", None);
+        assert!(store.get(&session.id).is_none());
+    }
+
+    #[test]
+    fn newer_stream_replaces_older_one() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let session = store.create("2025-03-26", None);
+
+        let mut first = session.open_stream();
+        let mut second = session.open_stream();
+        store.broadcast(&json!({"method": "ping"}));
+        assert!(first.try_recv().is_err());
+        assert!(second.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,94 @@
+/// MCP stdio transport
+///
+/// Desktop MCP clients launch the server as a subprocess and exchange
+/// newline-delimited JSON-RPC over its stdin and stdout. stdout carries
+/// protocol messages only; logs go to stderr.
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use super::handler::{Response, RpcError};
+use super::server::{allows_batches, dispatch_message, ServerState};
+use crate::error::{IBKRMCPError, Result};
+
+/// Serve requests from stdin until it is closed. Requests are handled
+/// concurrently, so a slow tool call does not hold up the others.
+pub(super) async fn serve(server: Arc<ServerState>) -> Result<()> {
+    info!("Serving MCP over stdio");
+
+    // One writer keeps concurrent responses from interleaving
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = rx.recv().await {
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    // The version initialize agreed on, which decides whether batches are allowed
+    let protocol_version = Arc::new(Mutex::new(None::<String>));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let server = Arc::clone(&server);
+        let tx = tx.clone();
+        let protocol_version = Arc::clone(&protocol_version);
+        tokio::spawn(async move {
+            if let Some(response) = handle_line(&server, &protocol_version, &line).await {
+                // The writer only stops once every sender is gone
+                let _ = tx.send(response.to_string());
+            }
+        });
+    }
+
+    // The writer drains what in-flight requests still send, then exits
+    info!("stdin closed, finishing in-flight requests");
+    drop(tx);
+    writer
+        .await
+        .map_err(|e| IBKRMCPError::Protocol(e.to_string()))??;
+    Ok(())
+}
+
+// One line of input: a request, a notification or a batch of them
+async fn handle_line(
+    server: &ServerState,
+    protocol_version: &Mutex<Option<String>>,
+    line: &str,
+) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(e) => {
+            return Some(Response::error(None, RpcError::parse_error(e)).to_value());
+        }
+    };
+    if message.is_array() {
+        let negotiated = protocol_version.lock().unwrap().clone();
+        if let Some(version) = negotiated.filter(|v| !allows_batches(v)) {
+            let error = RpcError::invalid_request(format!(
+                "batches are not supported in protocol version {}",
+                version
+            ));
+            return Some(Response::error(None, error).to_value());
+        }
+    }
+
+    let response = dispatch_message(server, &message).await;
+    if message["method"] == "initialize" {
+        if let Some(version) = response
+            .as_ref()
+            .and_then(|r| r["result"]["protocolVersion"].as_str())
+        {
+            *protocol_version.lock().unwrap() = Some(version.to_string());
+        }
+    }
+    response
+}
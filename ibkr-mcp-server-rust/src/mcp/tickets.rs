@@ -0,0 +1,300 @@
+/// Pending order tickets for two-phase confirmation
+///
+/// With confirmation enabled, `place_order` parks the normalized order here
+/// and returns the ticket; only `confirm_order` transmits it. Tickets that
+/// are neither confirmed nor rejected within the TTL are discarded. An order
+/// carrying a client order key holds the key while its ticket is pending,
+/// so a retried `place_order` gets the same ticket back.
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::order_keys::{self, fingerprint};
+use crate::{
+    error::{IBKRMCPError, Result},
+    models::{Contract, Order, OrderPreview},
+};
+
+/// An order waiting for confirmation
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct OrderTicket {
+    pub ticket_id: String,
+    pub contract: Contract,
+    pub order: Order,
+    pub preview: Option<OrderPreview>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct TicketStore {
+    ttl: Duration,
+    tickets: Mutex<Tickets>,
+}
+
+#[derive(Default)]
+struct Tickets {
+    issued: HashMap<String, OrderTicket>,
+    /// Tickets whose order is being transmitted by `confirm_order`
+    confirming: HashSet<String>,
+}
+
+impl TicketStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            tickets: Mutex::new(Tickets::default()),
+        }
+    }
+
+    /// Park an order and return its ticket, or the pending ticket already
+    /// issued for its client order key
+    pub fn issue(
+        &self,
+        contract: Contract,
+        order: Order,
+        preview: Option<OrderPreview>,
+    ) -> Result<OrderTicket> {
+        let created_at = Utc::now();
+        let mut tickets = self.tickets.lock().unwrap();
+        tickets.purge_expired(created_at);
+
+        if let Some(key) = order.order_ref.as_deref() {
+            let pending = tickets
+                .issued
+                .values()
+                .find(|t| t.order.order_ref.as_deref() == Some(key));
+            if let Some(pending) = pending {
+                let used_for = fingerprint(&pending.contract, &pending.order);
+                order_keys::ensure_same_order(key, &used_for, &contract, &order)?;
+                if tickets.confirming.contains(&pending.ticket_id) {
+                    return Err(confirming(&pending.ticket_id));
+                }
+                return Ok(pending.clone());
+            }
+        }
+
+        let ticket = OrderTicket {
+            ticket_id: format!("tkt_{:016x}", rand::random::<u64>()),
+            contract,
+            order,
+            preview,
+            created_at,
+            expires_at: created_at
+                + chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX),
+        };
+        tickets
+            .issued
+            .insert(ticket.ticket_id.clone(), ticket.clone());
+        Ok(ticket)
+    }
+
+    /// Mark a live ticket as being confirmed. While the confirmation is
+    /// held, other attempts to confirm or reject the ticket fail, so its
+    /// order is transmitted once.
+    pub fn begin_confirm(&self, ticket_id: &str) -> Result<Confirmation<'_>> {
+        let mut tickets = self.tickets.lock().unwrap();
+        let ticket = tickets.live(ticket_id)?;
+        tickets.confirming.insert(ticket_id.to_string());
+        Ok(Confirmation {
+            store: self,
+            ticket,
+            done: false,
+        })
+    }
+
+    /// Remove and return a live ticket that is not being confirmed.
+    /// Expired tickets are removed and reported as such.
+    pub fn take(&self, ticket_id: &str) -> Result<OrderTicket> {
+        let mut tickets = self.tickets.lock().unwrap();
+        let ticket = tickets.live(ticket_id)?;
+        tickets.issued.remove(ticket_id);
+        Ok(ticket)
+    }
+
+    /// Tickets still awaiting a decision, oldest first
+    pub fn pending(&self) -> Vec<OrderTicket> {
+        let mut tickets = self.tickets.lock().unwrap();
+        tickets.purge_expired(Utc::now());
+        let mut pending: Vec<_> = tickets
+            .issued
+            .values()
+            .filter(|t| !tickets.confirming.contains(&t.ticket_id))
+            .cloned()
+            .collect();
+        pending.sort_by_key(|t| t.created_at);
+        pending
+    }
+}
+
+/// A ticket being confirmed. Dropping it without `done` keeps the ticket,
+/// so a confirmation whose order could not be transmitted can be retried
+/// or rejected until the ticket expires.
+#[must_use = "dropping a confirmation gives the ticket back"]
+pub struct Confirmation<'a> {
+    store: &'a TicketStore,
+    pub ticket: OrderTicket,
+    done: bool,
+}
+
+impl Confirmation<'_> {
+    /// Discard the ticket: its order was transmitted
+    pub fn done(mut self) {
+        self.done = true;
+        let mut tickets = self.store.tickets.lock().unwrap();
+        tickets.confirming.remove(&self.ticket.ticket_id);
+        tickets.issued.remove(&self.ticket.ticket_id);
+    }
+}
+
+impl Drop for Confirmation<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let mut tickets = self.store.tickets.lock().unwrap();
+            tickets.confirming.remove(&self.ticket.ticket_id);
+        }
+    }
+}
+
+impl Tickets {
+    // A ticket that can still be confirmed or rejected
+    fn live(&mut self, ticket_id: &str) -> Result<OrderTicket> {
+        if self.confirming.contains(ticket_id) {
+            return Err(confirming(ticket_id));
+        }
+        match self.issued.get(ticket_id) {
+            Some(ticket) if ticket.expires_at <= Utc::now() => {
+                let expired = IBKRMCPError::Ticket(format!(
+                    "Ticket {} expired at {}",
+                    ticket_id,
+                    ticket.expires_at.to_rfc3339()
+                ));
+                self.issued.remove(ticket_id);
+                Err(expired)
+            }
+            Some(ticket) => Ok(ticket.clone()),
+            None => Err(IBKRMCPError::Ticket(format!(
+                "Unknown ticket {}",
+                ticket_id
+            ))),
+        }
+    }
+
+    // Tickets being confirmed stay until the confirmation ends
+    fn purge_expired(&mut self, now: DateTime<Utc>) {
+        let confirming = &self.confirming;
+        self.issued
+            .retain(|id, ticket| ticket.expires_at > now || confirming.contains(id));
+    }
+}
+
+fn confirming(ticket_id: &str) -> IBKRMCPError {
+    IBKRMCPError::Ticket(format!(
+        "Ticket {} is already being confirmed; confirmation in progress",
+        ticket_id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OrderAction, OrderType, SecType};
+
+    fn ticket(store: &TicketStore) -> OrderTicket {
+        store
+            .issue(
+                Contract::new("AAPL", SecType::Stock),
+                Order::new(OrderAction::Buy, 10.0, OrderType::Market),
+                None,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn tickets_are_taken_once() {
+        let store = TicketStore::new(Duration::from_secs(60));
+        let issued = ticket(&store);
+        assert_eq!(store.pending(), vec![issued.clone()]);
+
+        assert_eq!(store.take(&issued.ticket_id).unwrap(), issued);
+        assert!(matches!(
+            store.take(&issued.ticket_id),
+            Err(IBKRMCPError::Ticket(_))
+        ));
+        assert!(store.pending().is_empty());
+    }
+
+    #[test]
+    fn tickets_are_confirmed_once() {
+        let store = TicketStore::new(Duration::from_secs(60));
+        let issued = ticket(&store);
+
+        let confirmation = store.begin_confirm(&issued.ticket_id).unwrap();
+        assert_eq!(confirmation.ticket, issued);
+        let err = store.begin_confirm(&issued.ticket_id).err().unwrap();
+        assert!(
+            err.to_string().contains("confirmation in progress"),
+            "{}",
+            err
+        );
+        let err = store.take(&issued.ticket_id).unwrap_err();
+        assert!(
+            err.to_string().contains("confirmation in progress"),
+            "{}",
+            err
+        );
+        assert!(store.pending().is_empty());
+
+        // A ticket whose order failed can be confirmed again
+        drop(confirmation);
+        assert_eq!(store.pending(), vec![issued.clone()]);
+        store.begin_confirm(&issued.ticket_id).unwrap().done();
+        assert!(matches!(
+            store.begin_confirm(&issued.ticket_id),
+            Err(IBKRMCPError::Ticket(_))
+        ));
+    }
+
+    #[test]
+    fn keyed_orders_get_one_ticket() {
+        let store = TicketStore::new(Duration::from_secs(60));
+        let contract = Contract::new("AAPL", SecType::Stock);
+        let mut order = Order::new(OrderAction::Buy, 10.0, OrderType::Market);
+        order.order_ref = Some("k1".to_string());
+
+        let issued = store.issue(contract.clone(), order.clone(), None).unwrap();
+        let retried = store.issue(contract.clone(), order.clone(), None).unwrap();
+        assert_eq!(retried, issued);
+        assert_eq!(store.pending().len(), 1);
+
+        order.action = OrderAction::Sell;
+        assert!(matches!(
+            store.issue(contract.clone(), order.clone(), None),
+            Err(IBKRMCPError::InvalidParameter(_))
+        ));
+
+        // A ticket being confirmed holds the key without being handed out
+        let confirmation = store.begin_confirm(&issued.ticket_id).unwrap();
+        assert!(store.issue(contract.clone(), retried.order, None).is_err());
+
+        // A confirmed ticket no longer holds the key
+        confirmation.done();
+        assert_ne!(
+            store.issue(contract, order, None).unwrap().ticket_id,
+            issued.ticket_id
+        );
+    }
+
+    #[test]
+    fn expired_tickets_cannot_be_confirmed() {
+        let store = TicketStore::new(Duration::ZERO);
+        let issued = ticket(&store);
+
+        let err = store.take(&issued.ticket_id).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+        assert!(store.pending().is_empty());
+    }
+}
@@ -1,10 +0,0 @@
-use crate::error::Result;
-/// MCP tools implementation
-/// This module will contain the specific tool implementations
-use crate::ibkr::IBKRClient;
-use serde_json::Value;
-
-pub async fn execute_tool(client: &IBKRClient, tool_name: &str, params: &Value) -> Result<Value> {
-    // This will be expanded with specific tool implementations
-    Ok(Value::Null)
-}
@@ -0,0 +1,38 @@
+/// Account tools
+use async_trait::async_trait;
+
+use super::{NoArgs, Tool};
+use crate::error::Result;
+use crate::mcp::server::ServerState;
+use crate::models::{AccountValue, Position};
+
+pub struct GetAccountSummary;
+
+#[async_trait]
+impl Tool for GetAccountSummary {
+    type Args = NoArgs;
+    type Output = Vec<AccountValue>;
+
+    const NAME: &'static str = "get_account_summary";
+    const DESCRIPTION: &'static str =
+        "Get account summary including balance and portfolio information";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<Vec<AccountValue>> {
+        server.ibkr_client.get_account_summary().await
+    }
+}
+
+pub struct GetPositions;
+
+#[async_trait]
+impl Tool for GetPositions {
+    type Args = NoArgs;
+    type Output = Vec<Position>;
+
+    const NAME: &'static str = "get_positions";
+    const DESCRIPTION: &'static str = "Get current positions";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<Vec<Position>> {
+        server.ibkr_client.get_positions().await
+    }
+}
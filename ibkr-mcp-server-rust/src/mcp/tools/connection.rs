@@ -0,0 +1,72 @@
+/// Broker connection tools
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::{NoArgs, Tool};
+use crate::config::BackendKind;
+use crate::error::Result;
+use crate::ibkr::ConnectionState;
+use crate::mcp::server::{trading_status, ServerState, TradingStatus};
+
+/// Broker link and trading state
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ConnectionReport {
+    pub connected: bool,
+    pub state: ConnectionState,
+    pub host: String,
+    pub port: u16,
+    pub backend: BackendKind,
+    pub readonly: bool,
+    pub trading: TradingStatus,
+    /// Accounts the login may trade in
+    pub accounts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Reconnected {
+    pub reconnected: bool,
+}
+
+pub struct ConnectionStatus;
+
+#[async_trait]
+impl Tool for ConnectionStatus {
+    type Args = NoArgs;
+    type Output = ConnectionReport;
+
+    const NAME: &'static str = "connection_status";
+    const DESCRIPTION: &'static str = "Check IBKR connection status (connecting, connected, degraded or disconnected), whether the server is read-only and whether trading is halted";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<ConnectionReport> {
+        let client = &server.ibkr_client;
+        Ok(ConnectionReport {
+            connected: client.is_connected().await,
+            state: client.connection_state().await,
+            host: server.settings.ibkr.host.clone(),
+            port: server.settings.ibkr.port,
+            backend: client.backend_kind(),
+            readonly: client.is_readonly(),
+            trading: trading_status(client),
+            accounts: client.managed_accounts().await,
+        })
+    }
+}
+
+pub struct Reconnect;
+
+#[async_trait]
+impl Tool for Reconnect {
+    type Args = NoArgs;
+    type Output = Reconnected;
+
+    const NAME: &'static str = "reconnect";
+    const DESCRIPTION: &'static str =
+        "Drop the IBKR connection and connect again, e.g. after the gateway restarted";
+    const MUTATING: bool = true;
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<Reconnected> {
+        server.ibkr_client.reconnect().await?;
+        Ok(Reconnected { reconnected: true })
+    }
+}
@@ -0,0 +1,91 @@
+/// Market data tools
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::{non_empty, Tool};
+use crate::error::Result;
+use crate::mcp::server::ServerState;
+use crate::models::{BarData, Contract, MarketSnapshot, SecType};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MarketDataArgs {
+    pub symbol: String,
+    /// STK unless given
+    pub sec_type: Option<SecType>,
+}
+
+impl MarketDataArgs {
+    fn contract(&self) -> Result<Contract> {
+        let symbol = non_empty("symbol", &self.symbol)?;
+        Ok(Contract::new(
+            symbol,
+            self.sec_type.clone().unwrap_or(SecType::Stock),
+        ))
+    }
+}
+
+pub struct GetMarketData;
+
+#[async_trait]
+impl Tool for GetMarketData {
+    type Args = MarketDataArgs;
+    type Output = MarketSnapshot;
+
+    const NAME: &'static str = "get_market_data";
+    const DESCRIPTION: &'static str = "Get real-time market data for a symbol";
+
+    async fn call(&self, server: &ServerState, args: MarketDataArgs) -> Result<MarketSnapshot> {
+        server.ibkr_client.get_market_data(&args.contract()?).await
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct HistoricalDataArgs {
+    #[serde(flatten)]
+    pub contract: MarketDataArgs,
+    /// How far back, e.g. "1 D", "2 W", "6 M"
+    #[serde(default = "default_duration")]
+    pub duration: String,
+    /// e.g. "1 min", "5 mins", "1 hour", "1 day"
+    #[serde(default = "default_bar_size")]
+    pub bar_size: String,
+    /// TRADES, MIDPOINT, BID, ASK, ...
+    #[serde(default = "default_what_to_show")]
+    pub what_to_show: String,
+}
+
+fn default_duration() -> String {
+    "1 D".to_string()
+}
+
+fn default_bar_size() -> String {
+    "1 min".to_string()
+}
+
+fn default_what_to_show() -> String {
+    "TRADES".to_string()
+}
+
+pub struct GetHistoricalData;
+
+#[async_trait]
+impl Tool for GetHistoricalData {
+    type Args = HistoricalDataArgs;
+    type Output = Vec<BarData>;
+
+    const NAME: &'static str = "get_historical_data";
+    const DESCRIPTION: &'static str = "Get historical price bars for a symbol";
+
+    async fn call(&self, server: &ServerState, args: HistoricalDataArgs) -> Result<Vec<BarData>> {
+        server
+            .ibkr_client
+            .get_historical_data(
+                &args.contract.contract()?,
+                &args.duration,
+                &args.bar_size,
+                &args.what_to_show,
+            )
+            .await
+    }
+}
@@ -0,0 +1,507 @@
+/// MCP tools
+///
+/// Every tool declares a typed argument struct. Its JSON Schema is derived
+/// from the Rust type and published by `tools/list`, and `tools/call`
+/// arguments are deserialized into the same type, so the advertised schema
+/// and what a tool accepts cannot drift apart. Outputs work the same way:
+/// the `outputSchema` is derived from the `MCPResponse` a tool returns as
+/// its structured content, and debug builds check every result against it.
+use async_trait::async_trait;
+use schemars::gen::SchemaSettings;
+use schemars::schema::{InstanceType, NumberValidation, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+#[cfg(debug_assertions)]
+use tracing::error;
+
+use super::handler::RpcError;
+use super::server::ServerState;
+use crate::error::{IBKRMCPError, Result};
+use crate::models::MCPResponse;
+
+mod account;
+mod connection;
+mod market_data;
+mod orders;
+mod trading;
+#[cfg(any(debug_assertions, test))]
+mod validate;
+
+pub use orders::{OcaLeg, OrderArgs, OrderFields};
+pub use trading::{HaltTrading, HaltTradingArgs, ResumeTrading};
+
+/// A tool callable through `tools/call`
+#[async_trait]
+pub trait Tool: Send + Sync + 'static {
+    /// Arguments; their schema is the tool's `inputSchema`
+    type Args: DeserializeOwned + JsonSchema + Send;
+    /// Result data; its response's schema is the tool's `outputSchema`
+    type Output: Serialize + JsonSchema + Send;
+
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// Changes orders or the broker session; refused in read-only mode
+    const MUTATING: bool = false;
+    /// Offered in read-only mode although mutating: the kill switch only
+    /// ever stops trading
+    const ALLOWED_READONLY: bool = false;
+    /// Opens or amends orders; hidden from `tools/list` while trading is
+    /// halted
+    const ENTERS_ORDERS: bool = false;
+
+    async fn call(&self, server: &ServerState, args: Self::Args) -> Result<Self::Output>;
+}
+
+/// Arguments of tools that take none
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct NoArgs {}
+
+/// A positive IBKR order ID; a missing or malformed ID must never fall back
+/// to some default order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "i64")]
+pub struct OrderId(pub i32);
+
+impl TryFrom<i64> for OrderId {
+    type Error = String;
+
+    fn try_from(id: i64) -> std::result::Result<Self, Self::Error> {
+        i32::try_from(id)
+            .ok()
+            .filter(|id| *id > 0)
+            .map(OrderId)
+            .ok_or_else(|| format!("order_id must be a positive integer, got {}", id))
+    }
+}
+
+impl JsonSchema for OrderId {
+    fn schema_name() -> String {
+        "OrderId".to_string()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::Integer.into()),
+            number: Some(Box::new(NumberValidation {
+                minimum: Some(1.0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+// Object-safe face of `Tool`, so tools of different types share a registry
+#[async_trait]
+trait RegisteredTool: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn refused_readonly(&self) -> bool;
+    fn enters_orders(&self) -> bool;
+    fn definition(&self) -> Value;
+    async fn call_json(
+        &self,
+        server: &ServerState,
+        arguments: Value,
+    ) -> std::result::Result<Result<Value>, RpcError>;
+}
+
+#[async_trait]
+impl<T: Tool> RegisteredTool for T {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn refused_readonly(&self) -> bool {
+        T::MUTATING && !T::ALLOWED_READONLY
+    }
+
+    fn enters_orders(&self) -> bool {
+        T::ENTERS_ORDERS
+    }
+
+    fn definition(&self) -> Value {
+        json!({
+            "name": T::NAME,
+            "description": T::DESCRIPTION,
+            "inputSchema": schema_for::<T::Args>(false),
+            "outputSchema": schema_for::<MCPResponse<T::Output>>(true),
+            "annotations": { "readOnlyHint": !T::MUTATING }
+        })
+    }
+
+    async fn call_json(
+        &self,
+        server: &ServerState,
+        arguments: Value,
+    ) -> std::result::Result<Result<Value>, RpcError> {
+        let args: T::Args = serde_json::from_value(arguments)
+            .map_err(|e| RpcError::invalid_params(format!("{}: {}", T::NAME, e)))?;
+        Ok(self
+            .call(server, args)
+            .await
+            .and_then(|output| Ok(serde_json::to_value(output)?)))
+    }
+}
+
+/// Tools by name, in the order `tools/list` shows them
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn RegisteredTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Tool>(mut self, tool: T) -> Self {
+        assert!(!self.contains(T::NAME), "tool {} registered twice", T::NAME);
+        self.tools.push(Box::new(tool));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name() == name)
+    }
+
+    /// `tools/list` entries; mutating tools other than the kill switch are
+    /// hidden in read-only mode, and tools entering orders while trading is
+    /// halted
+    pub fn definitions(&self, readonly: bool, halted: bool) -> Vec<Value> {
+        self.tools
+            .iter()
+            .filter(|tool| !(readonly && tool.refused_readonly()))
+            .filter(|tool| !(halted && tool.enters_orders()))
+            .map(|tool| tool.definition())
+            .collect()
+    }
+
+    /// Run a tool. Unknown tools and arguments that do not match the schema
+    /// are protocol errors; failures of the tool itself are in the response.
+    pub async fn call(
+        &self,
+        server: &ServerState,
+        name: &str,
+        arguments: Value,
+    ) -> std::result::Result<MCPResponse<Value>, RpcError> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.name() == name)
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown tool: {}", name)))?;
+
+        // The client refuses these too; checking here also keeps read-only
+        // mode from issuing confirmation tickets
+        if tool.refused_readonly() && server.ibkr_client.is_readonly() {
+            return Ok(tool_result(
+                server,
+                Err::<Value, _>(IBKRMCPError::ReadOnly(name.to_string())),
+            ));
+        }
+
+        let arguments = match arguments {
+            Value::Null => json!({}),
+            Value::Object(_) => arguments,
+            _ => return Err(RpcError::invalid_params("arguments must be an object")),
+        };
+        let result = tool.call_json(server, arguments).await?;
+        let response = tool_result(server, result);
+        // The tool has already acted, so a mismatch is reported rather than
+        // turning its result into a failure the agent would retry
+        #[cfg(debug_assertions)]
+        {
+            let violations = output_violations(tool.as_ref(), &response);
+            if !violations.is_empty() {
+                error!(
+                    "{} result does not match its outputSchema: {:?}",
+                    name, violations
+                );
+            }
+        }
+        Ok(response)
+    }
+}
+
+// Where a response breaks the tool's outputSchema
+#[cfg(any(debug_assertions, test))]
+fn output_violations(tool: &dyn RegisteredTool, response: &MCPResponse<Value>) -> Vec<String> {
+    let content = serde_json::to_value(response).expect("responses always serialize");
+    validate::violations(&tool.definition()["outputSchema"], &content)
+}
+
+/// Every tool this server offers
+pub(super) fn registry() -> ToolRegistry {
+    ToolRegistry::new()
+        .register(account::GetAccountSummary)
+        .register(account::GetPositions)
+        .register(orders::PlaceOrder)
+        .register(orders::ConfirmOrder)
+        .register(orders::RejectOrder)
+        .register(orders::ListPendingOrders)
+        .register(trading::GetRiskLimits)
+        .register(orders::PreviewOrder)
+        .register(orders::PlaceBracketOrder)
+        .register(orders::PlaceOcaGroup)
+        .register(orders::CancelOrder)
+        .register(orders::CancelOrders)
+        .register(orders::CancelOcaGroup)
+        .register(orders::ModifyOrder)
+        .register(orders::GetOpenOrders)
+        .register(orders::GetOrderStatus)
+        .register(market_data::GetMarketData)
+        .register(market_data::GetHistoricalData)
+        .register(connection::ConnectionStatus)
+        .register(connection::Reconnect)
+        .register(HaltTrading)
+        .register(ResumeTrading)
+}
+
+/// Wrap a tool outcome in the response naming the backend that produced it
+pub(super) fn tool_result<T>(server: &ServerState, result: Result<T>) -> MCPResponse<T> {
+    MCPResponse::from_result(result, server.ibkr_client.backend_kind())
+}
+
+// Self-contained schema for a tool's arguments or output: subschemas are
+// inlined. Optional arguments are simply not required, while optional
+// output fields may also be null, as they serialize that way.
+fn schema_for<T: JsonSchema>(nullable: bool) -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.option_add_null_type = nullable;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<T>())
+        .expect("JSON Schemas always serialize");
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("title");
+    }
+    schema
+}
+
+/// Empty strings count as missing for required text arguments
+fn non_empty<'a>(name: &str, value: &'a str) -> Result<&'a str> {
+    if value.trim().is_empty() {
+        return Err(IBKRMCPError::InvalidParameter(format!(
+            "{} is required",
+            name
+        )));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_match_what_tools_accept() {
+        let tools = registry().definitions(false, false);
+        let tool = |name: &str| tools.iter().find(|t| t["name"] == name).unwrap().clone();
+
+        let place_order = tool("place_order")["inputSchema"].clone();
+        assert_eq!(place_order["type"], "object");
+        for field in [
+            "symbol",
+            "sec_type",
+            "limit_price",
+            "client_order_key",
+            "algo_params",
+        ] {
+            assert!(place_order["properties"].get(field).is_some(), "{}", field);
+        }
+        let mut required: Vec<_> = place_order["required"].as_array().unwrap().clone();
+        required.sort_by_key(|v| v.to_string());
+        assert_eq!(
+            required,
+            vec![json!("action"), json!("quantity"), json!("symbol")]
+        );
+        assert_eq!(
+            place_order["properties"]["action"]["enum"],
+            json!(["BUY", "SELL"])
+        );
+
+        let cancel = tool("cancel_order")["inputSchema"].clone();
+        assert_eq!(cancel["properties"]["order_id"]["minimum"], 1.0);
+        assert_eq!(tool("cancel_order")["annotations"]["readOnlyHint"], false);
+        assert_eq!(tool("get_positions")["annotations"]["readOnlyHint"], true);
+
+        // Outputs are the response structure around each tool's data
+        let positions = tool("get_positions")["outputSchema"].clone();
+        assert_eq!(positions["type"], "object");
+        // Optional output fields may be null, as they serialize that way
+        assert_eq!(
+            positions["properties"]["data"]["type"],
+            json!(["array", "null"])
+        );
+        let mut required: Vec<_> = positions["required"].as_array().unwrap().clone();
+        required.sort_by_key(|v| v.to_string());
+        assert_eq!(
+            required,
+            vec![json!("backend"), json!("success"), json!("timestamp")]
+        );
+        assert!(tool("place_order")["outputSchema"]["properties"]
+            .get("violations")
+            .is_some());
+
+        // Every tool describes its data rather than allowing any value
+        for definition in &tools {
+            let data = &definition["outputSchema"]["properties"]["data"];
+            assert!(
+                data.is_object() && data != &json!({}),
+                "{}",
+                definition["name"]
+            );
+        }
+        let limits = tool("get_risk_limits")["outputSchema"]["properties"]["data"].clone();
+        assert!(limits["properties"]["usage"]["properties"]
+            .get("daily_notional")
+            .is_some());
+        // place_order returns the order or, in confirmation mode, a ticket
+        let placed = tool("place_order")["outputSchema"]["properties"]["data"].clone();
+        let placed = placed.to_string();
+        assert!(placed.contains("client_order_key") && placed.contains("pending_confirmation"));
+
+        // No references left for clients to resolve
+        assert!(!serde_json::to_string(&tools).unwrap().contains("$ref"));
+
+        let readonly = registry().definitions(true, false);
+        assert!(readonly
+            .iter()
+            .filter(|t| t["name"] != "halt_trading")
+            .all(|t| t["annotations"]["readOnlyHint"] == true));
+        assert!(readonly.iter().any(|t| t["name"] == "preview_order"));
+        assert!(readonly.iter().any(|t| t["name"] == "halt_trading"));
+        assert!(!readonly.iter().any(|t| t["name"] == "resume_trading"));
+
+        let halted = registry().definitions(false, true);
+        let names: Vec<_> = halted.iter().map(|t| t["name"].clone()).collect();
+        assert!(!names.contains(&json!("place_order")));
+        assert!(!names.contains(&json!("modify_order")));
+        assert!(names.contains(&json!("cancel_order")));
+        assert!(names.contains(&json!("resume_trading")));
+    }
+
+    // Run a tool that must succeed, failing on output its schema refuses
+    async fn call(server: &ServerState, name: &str, arguments: Value) -> Value {
+        let tools = registry();
+        let tool = tools.tools.iter().find(|t| t.name() == name).unwrap();
+        let response = tools.call(server, name, arguments).await.unwrap();
+        assert!(response.success, "{}: {:?}", name, response.error);
+        let violations = output_violations(tool.as_ref(), &response);
+        assert!(violations.is_empty(), "{}: {:?}", name, violations);
+        response.data.unwrap()
+    }
+
+    async fn memory_server(confirmation: bool) -> ServerState {
+        let mut settings = crate::Settings::new().unwrap();
+        settings.ibkr.backend = crate::config::BackendKind::Memory;
+        settings.ibkr.state_dir = None;
+        settings.mcp.confirmation.enabled = confirmation;
+        let client = crate::IBKRClient::new(settings.ibkr.clone());
+        client.connect().await.unwrap();
+        ServerState::new(std::sync::Arc::new(client), settings)
+    }
+
+    #[tokio::test]
+    async fn every_tool_output_matches_its_schema() {
+        let server = memory_server(false).await;
+        let order = json!({
+            "symbol": "AAPL",
+            "action": "BUY",
+            "quantity": 10,
+            "order_type": "LMT",
+            "limit_price": 150.0
+        });
+
+        call(&server, "get_account_summary", json!({})).await;
+        call(&server, "get_positions", json!({})).await;
+        call(&server, "connection_status", json!({})).await;
+        call(&server, "get_market_data", json!({ "symbol": "AAPL" })).await;
+        call(&server, "get_historical_data", json!({ "symbol": "AAPL" })).await;
+        call(&server, "get_risk_limits", json!({})).await;
+        call(&server, "preview_order", order.clone()).await;
+        let placed = call(&server, "place_order", order.clone()).await;
+        let order_id = placed["order_id"].clone();
+        call(&server, "get_open_orders", json!({})).await;
+        call(&server, "get_order_status", json!({ "order_id": order_id })).await;
+        let changes = json!({ "order_id": order_id, "limit_price": 151.0 });
+        call(&server, "modify_order", changes).await;
+        call(&server, "cancel_order", json!({ "order_id": order_id })).await;
+        let mut bracket = order.clone();
+        bracket["take_profit_price"] = json!(160.0);
+        bracket["stop_loss_price"] = json!(140.0);
+        call(&server, "place_bracket_order", bracket).await;
+        let legs = json!({
+            "symbol": "AAPL",
+            "orders": [
+                { "action": "SELL", "quantity": 10, "order_type": "LMT", "limit_price": 190.0 },
+                { "action": "SELL", "quantity": 10, "order_type": "STP", "stop_price": 140.0 }
+            ]
+        });
+        let group = call(&server, "place_oca_group", legs).await;
+        let group = json!({ "oca_group": group["oca_group"] });
+        call(&server, "cancel_oca_group", group).await;
+        call(&server, "cancel_orders", json!({ "all": true })).await;
+        call(&server, "halt_trading", json!({ "reason": "test" })).await;
+        call(&server, "resume_trading", json!({})).await;
+
+        // Confirmation mode answers with tickets instead
+        let server = memory_server(true).await;
+        let parked = call(&server, "place_order", order.clone()).await;
+        let ticket = json!({ "ticket_id": parked["ticket"]["ticket_id"] });
+        call(&server, "list_pending_orders", json!({})).await;
+        call(&server, "confirm_order", ticket).await;
+        let parked = call(&server, "place_order", order).await;
+        let ticket = json!({ "ticket_id": parked["ticket"]["ticket_id"] });
+        call(&server, "reject_order", ticket).await;
+    }
+
+    #[tokio::test]
+    async fn readonly_servers_can_still_halt() {
+        let mut settings = crate::Settings::new().unwrap();
+        settings.ibkr.backend = crate::config::BackendKind::Memory;
+        settings.ibkr.state_dir = None;
+        settings.ibkr.readonly = true;
+        let client = crate::IBKRClient::new(settings.ibkr.clone());
+        client.connect().await.unwrap();
+        let server = ServerState::new(std::sync::Arc::new(client), settings);
+
+        let halt = json!({ "reason": "test", "flatten_positions": true });
+        let report = call(&server, "halt_trading", halt).await;
+        assert!(server.ibkr_client.trading_halt().is_some());
+        // Closing positions still sends orders, so it stays refused
+        assert_eq!(report["flatten_orders"], json!([]));
+        assert!(report["errors"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("flatten_positions"));
+
+        let resumed = registry()
+            .call(&server, "resume_trading", json!({}))
+            .await
+            .unwrap();
+        assert!(!resumed.success);
+    }
+
+    #[test]
+    fn order_ids_must_be_positive() {
+        assert_eq!(
+            serde_json::from_value::<OrderId>(json!(7)).unwrap(),
+            OrderId(7)
+        );
+        for invalid in [
+            json!(0),
+            json!(-3),
+            json!(1.5),
+            json!("7"),
+            json!(1_i64 << 40),
+        ] {
+            assert!(serde_json::from_value::<OrderId>(invalid).is_err());
+        }
+    }
+}
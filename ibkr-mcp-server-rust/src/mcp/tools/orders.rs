@@ -0,0 +1,798 @@
+/// Order tools: placing, confirming, amending and cancelling orders
+use std::time::Duration;
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{non_empty, NoArgs, OrderId, Tool};
+use crate::error::{IBKRMCPError, Result};
+use crate::ibkr::{CancelOutcome, OrderFilter, TrackedOrder};
+use crate::mcp::order_keys::{self, fingerprint, KeyClaim};
+use crate::mcp::server::ServerState;
+use crate::mcp::tickets::OrderTicket;
+use crate::models::{
+    AlgoParams, AlgoStrategy, BracketOrder, Contract, OcaType, Order, OrderAction, OrderChanges,
+    OrderPreview, OrderType, SecType, TimeInForce,
+};
+
+/// Order fields shared by every order tool
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct OrderFields {
+    pub action: OrderAction,
+    pub quantity: f64,
+    /// MKT unless given
+    pub order_type: Option<OrderType>,
+    /// Required for LMT and STP LMT
+    pub limit_price: Option<f64>,
+    /// Required for STP and STP LMT
+    pub stop_price: Option<f64>,
+    /// TRAIL / TRAIL LIMIT: trailing distance in price units
+    pub trailing_amount: Option<f64>,
+    /// TRAIL / TRAIL LIMIT: trailing distance in percent, instead of trailing_amount
+    pub trailing_percent: Option<f64>,
+    /// Initial stop price of a trailing order; required for TRAIL LIMIT
+    pub trail_stop_price: Option<f64>,
+    /// TRAIL LIMIT: limit price distance from the stop, instead of limit_price
+    pub limit_price_offset: Option<f64>,
+    /// DAY unless given
+    pub time_in_force: Option<TimeInForce>,
+    /// Required for GTD: "YYYYMMDD HH:MM:SS [time zone]"
+    pub good_till_date: Option<String>,
+    /// Do not activate before "YYYYMMDD HH:MM:SS [time zone]"
+    pub good_after_time: Option<String>,
+    /// Allow execution outside regular trading hours (not with IOC)
+    #[serde(default)]
+    pub outside_rth: bool,
+    /// Hide the order from the book (limit orders only)
+    #[serde(default)]
+    pub hidden: bool,
+    pub algo_strategy: Option<AlgoStrategy>,
+    /// Adaptive: adaptive_priority. Twap: strategy_type. Vwap: max_pct_vol
+    /// (0.01-0.5), no_take_liq. ArrivalPx: risk_aversion, max_pct_vol,
+    /// force_completion. Twap/Vwap/ArrivalPx also take start_time, end_time
+    /// ("HH:MM:SS [time zone]") and allow_past_end_time
+    #[serde(default)]
+    pub algo_params: AlgoParams,
+}
+
+impl OrderFields {
+    /// The order these fields describe, validated for its order type
+    pub fn into_order(self) -> Result<Order> {
+        let order_type = self.order_type.unwrap_or(OrderType::Market);
+        let trailing = matches!(order_type, OrderType::Trail | OrderType::TrailLimit);
+        let misplaced = if trailing {
+            vec![("stop_price", self.stop_price.is_some())]
+        } else {
+            vec![
+                ("trailing_amount", self.trailing_amount.is_some()),
+                ("trailing_percent", self.trailing_percent.is_some()),
+                ("trail_stop_price", self.trail_stop_price.is_some()),
+                ("limit_price_offset", self.limit_price_offset.is_some()),
+            ]
+        };
+        if let Some((field, _)) = misplaced.iter().find(|(_, set)| *set) {
+            return Err(IBKRMCPError::InvalidParameter(format!(
+                "{} does not apply to {} orders",
+                field,
+                order_type.as_str()
+            )));
+        }
+
+        let mut order = Order::new(self.action, self.quantity, order_type);
+        order.lmt_price = self.limit_price;
+        order.aux_price = if trailing {
+            self.trailing_amount
+        } else {
+            self.stop_price
+        };
+        order.trailing_percent = self.trailing_percent;
+        order.trail_stop_price = self.trail_stop_price;
+        order.lmt_price_offset = self.limit_price_offset;
+        if let Some(tif) = self.time_in_force {
+            order.time_in_force = tif;
+        }
+        order.good_till_date = self.good_till_date;
+        order.good_after_time = self.good_after_time;
+        order.outside_rth = self.outside_rth;
+        order.hidden = self.hidden;
+        order.algo_strategy = self.algo_strategy;
+        order.algo_params = self.algo_params;
+        order.validate()?;
+        Ok(order)
+    }
+}
+
+/// A contract and an order for it
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct OrderArgs {
+    pub symbol: String,
+    /// STK unless given
+    pub sec_type: Option<SecType>,
+    #[serde(flatten)]
+    pub order: OrderFields,
+}
+
+impl OrderArgs {
+    pub fn into_contract_and_order(self) -> Result<(Contract, Order)> {
+        let symbol = non_empty("symbol", &self.symbol)?;
+        let contract = Contract::new(symbol, self.sec_type.unwrap_or(SecType::Stock));
+        Ok((contract, self.order.into_order()?))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlaceOrderArgs {
+    #[serde(flatten)]
+    pub order: OrderArgs,
+    /// Idempotency key, sent to IBKR as orderRef: a retried call with the
+    /// same key returns the order already placed instead of placing it again
+    pub client_order_key: Option<String>,
+}
+
+/// An order transmitted to IBKR
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlacedOrder {
+    pub order_id: i32,
+    pub symbol: String,
+    pub action: OrderAction,
+    pub quantity: f64,
+    pub order_type: OrderType,
+    pub order: Order,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_key: Option<String>,
+    /// Whether the key had already placed this order, which was returned
+    /// instead of placing it again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate: Option<bool>,
+    /// The ticket confirm_order transmitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_id: Option<String>,
+}
+
+impl PlacedOrder {
+    fn new(order_id: i32, contract: &Contract, order: &Order) -> Self {
+        Self {
+            order_id,
+            symbol: contract.symbol.clone(),
+            action: order.action.clone(),
+            quantity: order.total_quantity,
+            order_type: order.order_type.clone(),
+            order: order.clone(),
+            client_order_key: None,
+            duplicate: None,
+            ticket_id: None,
+        }
+    }
+
+    fn keyed(mut self, key: &str, duplicate: bool) -> Self {
+        self.client_order_key = Some(key.to_string());
+        self.duplicate = Some(duplicate);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketStatus {
+    PendingConfirmation,
+}
+
+/// An order parked for confirm_order or reject_order
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PendingTicket {
+    pub status: TicketStatus,
+    pub ticket: OrderTicket,
+}
+
+/// place_order transmits the order, or parks it in confirmation mode
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum PlaceOrderOutcome {
+    Placed(Box<PlacedOrder>),
+    Pending(Box<PendingTicket>),
+}
+
+pub struct PlaceOrder;
+
+#[async_trait]
+impl Tool for PlaceOrder {
+    type Args = PlaceOrderArgs;
+    type Output = PlaceOrderOutcome;
+
+    const NAME: &'static str = "place_order";
+    const DESCRIPTION: &'static str = "Place a new order. With confirmation mode on, returns a pending ticket (normalized order, preview, expiry) for confirm_order / reject_order instead. Pass client_order_key to make retries safe";
+    const MUTATING: bool = true;
+    const ENTERS_ORDERS: bool = true;
+
+    async fn call(&self, server: &ServerState, args: PlaceOrderArgs) -> Result<PlaceOrderOutcome> {
+        let (contract, mut order) = args.order.into_contract_and_order()?;
+        // Retries with the same key return the order already placed
+        if let Some(key) = args.client_order_key {
+            non_empty("client_order_key", &key)?;
+            order.order_ref = Some(key);
+        }
+        if server.settings.mcp.confirmation.enabled {
+            // A retry after the ticket was confirmed gets the order back
+            if let Some(key) = order.order_ref.as_deref() {
+                if let Some(order_id) = placed_order(server, key, &contract, &order).await? {
+                    let placed = PlacedOrder::new(order_id, &contract, &order).keyed(key, true);
+                    return Ok(PlaceOrderOutcome::Placed(Box::new(placed)));
+                }
+            }
+            // Park the order; confirm_order transmits it. Orders the risk
+            // limits would refuse never get a ticket.
+            server.ibkr_client.ensure_trading_allowed("place_order")?;
+            server.ibkr_client.check_risk(&contract, &order).await?;
+            let preview = server.ibkr_client.preview_order(&contract, &order).await?;
+            let ticket = server.tickets.issue(contract, order, Some(preview))?;
+            info!("Issued ticket {} pending confirmation", ticket.ticket_id);
+            return Ok(PlaceOrderOutcome::Pending(Box::new(PendingTicket {
+                status: TicketStatus::PendingConfirmation,
+                ticket,
+            })));
+        }
+        let placed = place_and_describe(server, &contract, &order).await?;
+        Ok(PlaceOrderOutcome::Placed(Box::new(placed)))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TicketArgs {
+    pub ticket_id: String,
+}
+
+pub struct ConfirmOrder;
+
+#[async_trait]
+impl Tool for ConfirmOrder {
+    type Args = TicketArgs;
+    type Output = PlacedOrder;
+
+    const NAME: &'static str = "confirm_order";
+    const DESCRIPTION: &'static str =
+        "Transmit an order parked by place_order while confirmation mode is on";
+    const MUTATING: bool = true;
+    const ENTERS_ORDERS: bool = true;
+
+    async fn call(&self, server: &ServerState, args: TicketArgs) -> Result<PlacedOrder> {
+        // Marked first so concurrent confirmations transmit the order once
+        let confirmation = server.tickets.begin_confirm(&args.ticket_id)?;
+        let ticket = &confirmation.ticket;
+        info!("Confirming ticket {}", ticket.ticket_id);
+        match place_and_describe(server, &ticket.contract, &ticket.order).await {
+            Ok(placed) => {
+                let ticket_id = ticket.ticket_id.clone();
+                confirmation.done();
+                Ok(PlacedOrder {
+                    ticket_id: Some(ticket_id),
+                    ..placed
+                })
+            }
+            Err(e) => {
+                warn!(
+                    "Ticket {} kept after a failed confirmation: {}",
+                    ticket.ticket_id, e
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RejectedTicket {
+    pub ticket_id: String,
+    pub rejected: bool,
+}
+
+pub struct RejectOrder;
+
+#[async_trait]
+impl Tool for RejectOrder {
+    type Args = TicketArgs;
+    type Output = RejectedTicket;
+
+    const NAME: &'static str = "reject_order";
+    const DESCRIPTION: &'static str = "Discard an order ticket without transmitting it";
+
+    async fn call(&self, server: &ServerState, args: TicketArgs) -> Result<RejectedTicket> {
+        let ticket = server.tickets.take(&args.ticket_id)?;
+        info!("Rejected ticket {}", ticket.ticket_id);
+        Ok(RejectedTicket {
+            ticket_id: ticket.ticket_id,
+            rejected: true,
+        })
+    }
+}
+
+pub struct ListPendingOrders;
+
+#[async_trait]
+impl Tool for ListPendingOrders {
+    type Args = NoArgs;
+    type Output = Vec<OrderTicket>;
+
+    const NAME: &'static str = "list_pending_orders";
+    const DESCRIPTION: &'static str = "List order tickets awaiting confirm_order or reject_order";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<Vec<OrderTicket>> {
+        Ok(server.tickets.pending())
+    }
+}
+
+/// An order priced by a what-if submission
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PreviewedOrder {
+    pub symbol: String,
+    pub order: Order,
+    pub preview: OrderPreview,
+}
+
+pub struct PreviewOrder;
+
+#[async_trait]
+impl Tool for PreviewOrder {
+    type Args = OrderArgs;
+    type Output = PreviewedOrder;
+
+    const NAME: &'static str = "preview_order";
+    const DESCRIPTION: &'static str = "Price an order with a what-if submission: margin before/change/after, equity with loan, estimated commission and warnings. Nothing is transmitted";
+
+    async fn call(&self, server: &ServerState, args: OrderArgs) -> Result<PreviewedOrder> {
+        let (contract, order) = args.into_contract_and_order()?;
+        let preview = server.ibkr_client.preview_order(&contract, &order).await?;
+        Ok(PreviewedOrder {
+            symbol: contract.symbol,
+            order,
+            preview,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlaceBracketOrderArgs {
+    /// The entry order; MKT or LMT
+    #[serde(flatten)]
+    pub entry: OrderArgs,
+    pub take_profit_price: f64,
+    pub stop_loss_price: f64,
+}
+
+/// The legs of a placed bracket and their state
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlacedBracket {
+    pub parent_order_id: i32,
+    pub take_profit_order_id: i32,
+    pub stop_loss_order_id: i32,
+    pub orders: Vec<TrackedOrder>,
+}
+
+pub struct PlaceBracketOrder;
+
+#[async_trait]
+impl Tool for PlaceBracketOrder {
+    type Args = PlaceBracketOrderArgs;
+    type Output = PlacedBracket;
+
+    const NAME: &'static str = "place_bracket_order";
+    const DESCRIPTION: &'static str = "Place an entry order with attached take-profit and stop-loss exits; all three are transmitted together";
+    const MUTATING: bool = true;
+    const ENTERS_ORDERS: bool = true;
+
+    async fn call(
+        &self,
+        server: &ServerState,
+        args: PlaceBracketOrderArgs,
+    ) -> Result<PlacedBracket> {
+        let (contract, entry) = args.entry.into_contract_and_order()?;
+        let bracket = BracketOrder::new(entry, args.take_profit_price, args.stop_loss_price)?;
+
+        let ids = server
+            .ibkr_client
+            .place_bracket_order(&contract, bracket)
+            .await?;
+        let mut orders = Vec::new();
+        for order_id in [
+            ids.parent_order_id,
+            ids.take_profit_order_id,
+            ids.stop_loss_order_id,
+        ] {
+            orders.push(server.ibkr_client.get_order_status(order_id).await?);
+        }
+        Ok(PlacedBracket {
+            parent_order_id: ids.parent_order_id,
+            take_profit_order_id: ids.take_profit_order_id,
+            stop_loss_order_id: ids.stop_loss_order_id,
+            orders,
+        })
+    }
+}
+
+/// One order of an OCA group
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct OcaLeg {
+    /// The group's symbol unless given
+    pub symbol: Option<String>,
+    pub sec_type: Option<SecType>,
+    #[serde(flatten)]
+    pub order: OrderFields,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlaceOcaGroupArgs {
+    /// Group name; generated unless given
+    pub oca_group: Option<String>,
+    #[serde(default)]
+    pub oca_type: OcaType,
+    /// Symbol of legs that do not name their own
+    pub symbol: Option<String>,
+    pub sec_type: Option<SecType>,
+    #[validate(length(min = 2))]
+    pub orders: Vec<OcaLeg>,
+}
+
+/// The orders of a placed OCA group and their state
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlacedOcaGroup {
+    pub oca_group: String,
+    pub oca_type: OcaType,
+    pub order_ids: Vec<i32>,
+    pub orders: Vec<TrackedOrder>,
+}
+
+pub struct PlaceOcaGroup;
+
+#[async_trait]
+impl Tool for PlaceOcaGroup {
+    type Args = PlaceOcaGroupArgs;
+    type Output = PlacedOcaGroup;
+
+    const NAME: &'static str = "place_oca_group";
+    const DESCRIPTION: &'static str = "Place several orders in one-cancels-all group: when one fills the others are cancelled (or reduced)";
+    const MUTATING: bool = true;
+    const ENTERS_ORDERS: bool = true;
+
+    async fn call(&self, server: &ServerState, args: PlaceOcaGroupArgs) -> Result<PlacedOcaGroup> {
+        let group = args
+            .oca_group
+            .unwrap_or_else(|| format!("oca_{}", chrono::Utc::now().timestamp_millis()));
+        let legs = args
+            .orders
+            .into_iter()
+            .map(|leg| {
+                // Legs share the top-level contract unless they name their own
+                OrderArgs {
+                    symbol: leg
+                        .symbol
+                        .or_else(|| args.symbol.clone())
+                        .unwrap_or_default(),
+                    sec_type: leg.sec_type.or_else(|| args.sec_type.clone()),
+                    order: leg.order,
+                }
+                .into_contract_and_order()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let order_ids = server
+            .ibkr_client
+            .place_oca_group(&group, args.oca_type, legs)
+            .await?;
+        let mut orders = Vec::with_capacity(order_ids.len());
+        for &order_id in &order_ids {
+            orders.push(server.ibkr_client.get_order_status(order_id).await?);
+        }
+        Ok(PlacedOcaGroup {
+            oca_group: group,
+            oca_type: args.oca_type,
+            order_ids,
+            orders,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OrderIdArgs {
+    pub order_id: OrderId,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CancelledOrder {
+    pub order_id: i32,
+    pub cancelled: bool,
+}
+
+pub struct CancelOrder;
+
+#[async_trait]
+impl Tool for CancelOrder {
+    type Args = OrderIdArgs;
+    type Output = CancelledOrder;
+
+    const NAME: &'static str = "cancel_order";
+    const DESCRIPTION: &'static str = "Cancel one working order by ID";
+    const MUTATING: bool = true;
+
+    async fn call(&self, server: &ServerState, args: OrderIdArgs) -> Result<CancelledOrder> {
+        let OrderId(order_id) = args.order_id;
+        let cancelled = server.ibkr_client.cancel_order(order_id).await?;
+        Ok(CancelledOrder {
+            order_id,
+            cancelled,
+        })
+    }
+}
+
+/// Filters for cancel_orders; an order must match all that are given
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CancelOrdersArgs {
+    pub symbol: Option<String>,
+    pub action: Option<OrderAction>,
+    pub order_type: Option<OrderType>,
+    /// Only orders first seen at least this many seconds ago
+    pub older_than_secs: Option<u64>,
+    /// Cancel every working order; cannot be combined with filters
+    #[serde(default)]
+    pub all: bool,
+}
+
+impl CancelOrdersArgs {
+    fn into_filter(self) -> Result<OrderFilter> {
+        let filter = OrderFilter {
+            symbol: self.symbol,
+            action: self.action,
+            order_type: self.order_type,
+            older_than: self.older_than_secs.map(Duration::from_secs),
+        };
+
+        // Cancelling everything has to be asked for explicitly
+        match (filter.is_empty(), self.all) {
+            (true, false) => Err(IBKRMCPError::InvalidParameter(
+                "Give at least one filter (symbol, action, order_type, older_than_secs) or all=true"
+                    .to_string(),
+            )),
+            (false, true) => Err(IBKRMCPError::InvalidParameter(
+                "all=true cannot be combined with filters".to_string(),
+            )),
+            _ => Ok(filter),
+        }
+    }
+}
+
+/// Outcome of a bulk cancel, per matched order
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CancelledOrders {
+    pub matched: usize,
+    pub cancelled: usize,
+    pub orders: Vec<CancelOutcome>,
+}
+
+pub struct CancelOrders;
+
+#[async_trait]
+impl Tool for CancelOrders {
+    type Args = CancelOrdersArgs;
+    type Output = CancelledOrders;
+
+    const NAME: &'static str = "cancel_orders";
+    const DESCRIPTION: &'static str = "Cancel working orders in bulk: every order matching all given filters, or every working order with all=true. Reports the outcome per order";
+    const MUTATING: bool = true;
+
+    async fn call(&self, server: &ServerState, args: CancelOrdersArgs) -> Result<CancelledOrders> {
+        let filter = args.into_filter()?;
+        let orders = server.ibkr_client.cancel_orders(&filter).await?;
+        Ok(CancelledOrders {
+            matched: orders.len(),
+            cancelled: orders.iter().filter(|o| o.cancelled).count(),
+            orders,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OcaGroupArgs {
+    pub oca_group: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CancelledOcaGroup {
+    pub oca_group: String,
+    pub cancelled_order_ids: Vec<i32>,
+}
+
+pub struct CancelOcaGroup;
+
+#[async_trait]
+impl Tool for CancelOcaGroup {
+    type Args = OcaGroupArgs;
+    type Output = CancelledOcaGroup;
+
+    const NAME: &'static str = "cancel_oca_group";
+    const DESCRIPTION: &'static str = "Cancel every working order in an OCA group";
+    const MUTATING: bool = true;
+
+    async fn call(&self, server: &ServerState, args: OcaGroupArgs) -> Result<CancelledOcaGroup> {
+        let group = non_empty("oca_group", &args.oca_group)?;
+        let cancelled = server.ibkr_client.cancel_oca_group(group).await?;
+        Ok(CancelledOcaGroup {
+            oca_group: group.to_string(),
+            cancelled_order_ids: cancelled,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ModifyOrderArgs {
+    pub order_id: OrderId,
+    #[serde(flatten)]
+    pub changes: OrderChanges,
+}
+
+pub struct ModifyOrder;
+
+#[async_trait]
+impl Tool for ModifyOrder {
+    type Args = ModifyOrderArgs;
+    type Output = TrackedOrder;
+
+    const NAME: &'static str = "modify_order";
+    const DESCRIPTION: &'static str =
+        "Amend the quantity, prices or time in force of a working order without cancelling it";
+    const MUTATING: bool = true;
+    const ENTERS_ORDERS: bool = true;
+
+    async fn call(&self, server: &ServerState, args: ModifyOrderArgs) -> Result<TrackedOrder> {
+        let OrderId(order_id) = args.order_id;
+        server
+            .ibkr_client
+            .modify_order(order_id, &args.changes)
+            .await
+    }
+}
+
+pub struct GetOpenOrders;
+
+#[async_trait]
+impl Tool for GetOpenOrders {
+    type Args = NoArgs;
+    type Output = Vec<TrackedOrder>;
+
+    const NAME: &'static str = "get_open_orders";
+    const DESCRIPTION: &'static str =
+        "List working orders with status, fills, average price and commission";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<Vec<TrackedOrder>> {
+        server.ibkr_client.get_open_orders().await
+    }
+}
+
+pub struct GetOrderStatus;
+
+#[async_trait]
+impl Tool for GetOrderStatus {
+    type Args = OrderIdArgs;
+    type Output = TrackedOrder;
+
+    const NAME: &'static str = "get_order_status";
+    const DESCRIPTION: &'static str =
+        "Get the lifecycle of one order: status, fills, average price and commission";
+
+    async fn call(&self, server: &ServerState, args: OrderIdArgs) -> Result<TrackedOrder> {
+        let OrderId(order_id) = args.order_id;
+        server.ibkr_client.get_order_status(order_id).await
+    }
+}
+
+// Transmit an order and describe it for the place_order result
+async fn place_and_describe(
+    server: &ServerState,
+    contract: &Contract,
+    order: &Order,
+) -> Result<PlacedOrder> {
+    let Some(key) = order.order_ref.as_deref() else {
+        let order_id = server.ibkr_client.place_order(contract, order).await?;
+        return Ok(PlacedOrder::new(order_id, contract, order));
+    };
+
+    let (order_id, duplicate) = place_once(server, key, contract, order).await?;
+    Ok(PlacedOrder::new(order_id, contract, order).keyed(key, duplicate))
+}
+
+// Place an order at most once per client order key, returning its ID and
+// whether it already existed. Keys the store does not know are looked up by
+// orderRef at the broker, which covers a restart between placing an order
+// and recording its key.
+async fn place_once(
+    server: &ServerState,
+    key: &str,
+    contract: &Contract,
+    order: &Order,
+) -> Result<(i32, bool)> {
+    if let KeyClaim::Placed(order_id) = server.order_keys.claim(key, contract, order)? {
+        info!("client_order_key {} already placed order {}", key, order_id);
+        return Ok((order_id, true));
+    }
+
+    let placed = async {
+        match find_by_order_ref(server, key, contract, order).await? {
+            Some(order_id) => Ok((order_id, true)),
+            None => Ok((
+                server.ibkr_client.place_order(contract, order).await?,
+                false,
+            )),
+        }
+    }
+    .await;
+
+    match placed {
+        Ok((order_id, duplicate)) => {
+            // The order stands even if the key cannot be saved; a retry
+            // still finds it by orderRef at the broker
+            if let Err(e) = server.order_keys.complete(key, contract, order, order_id) {
+                warn!("Failed to record client_order_key {}: {}", key, e);
+            }
+            Ok((order_id, duplicate))
+        }
+        Err(e) => {
+            server.order_keys.release(key);
+            Err(e)
+        }
+    }
+}
+
+// The order a client order key already placed, from the key store or else
+// among today's open and completed orders and executions at the broker
+async fn placed_order(
+    server: &ServerState,
+    key: &str,
+    contract: &Contract,
+    order: &Order,
+) -> Result<Option<i32>> {
+    match server.order_keys.placed(key, contract, order)? {
+        Some(order_id) => Ok(Some(order_id)),
+        None => find_by_order_ref(server, key, contract, order).await,
+    }
+}
+
+async fn find_by_order_ref(
+    server: &ServerState,
+    key: &str,
+    contract: &Contract,
+    order: &Order,
+) -> Result<Option<i32>> {
+    let Some(found) = server
+        .ibkr_client
+        .find_orders_by_ref(key)
+        .await?
+        .into_iter()
+        .next()
+    else {
+        return Ok(None);
+    };
+    let same = match &found.order {
+        Some(placed) => fingerprint(&found.contract, placed) == fingerprint(contract, order),
+        // Executions alone do not report prices or time in force
+        None => {
+            found.contract.symbol.eq_ignore_ascii_case(&contract.symbol)
+                && found.contract.sec_type == contract.sec_type
+                && found.action == order.action
+        }
+    };
+    if !same {
+        return Err(order_keys::reused(key));
+    }
+    match found.order_id {
+        Some(order_id) => {
+            info!(
+                "client_order_key {} found at the broker as order {}",
+                key, order_id
+            );
+            Ok(Some(order_id))
+        }
+        None => Err(IBKRMCPError::Order(format!(
+            "client_order_key {} already placed order permId {} ({}) in an earlier session",
+            key, found.perm_id, found.status
+        ))),
+    }
+}
@@ -0,0 +1,118 @@
+/// Risk limit and kill switch tools
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{non_empty, NoArgs, Tool};
+use crate::config::RiskConfig;
+use crate::error::Result;
+use crate::ibkr::{HaltReport, TradingHalt};
+use crate::mcp::server::ServerState;
+use crate::risk::RiskUsage;
+
+/// The configured limits and what today's orders used of them
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RiskLimits {
+    pub limits: RiskConfig,
+    pub usage: RiskUsage,
+    /// Notional still allowed today, when `max_daily_notional` is set
+    pub remaining_daily_notional: Option<f64>,
+}
+
+pub struct GetRiskLimits;
+
+#[async_trait]
+impl Tool for GetRiskLimits {
+    type Args = NoArgs;
+    type Output = RiskLimits;
+
+    const NAME: &'static str = "get_risk_limits";
+    const DESCRIPTION: &'static str = "Show the pre-trade risk limits every order is checked against, and the notional already submitted today";
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<RiskLimits> {
+        let risk = server.ibkr_client.risk();
+        let usage = risk.usage();
+        let remaining = risk
+            .config()
+            .max_daily_notional
+            .map(|max| (max - usage.daily_notional).max(0.0));
+        Ok(RiskLimits {
+            limits: risk.config().clone(),
+            usage,
+            remaining_daily_notional: remaining,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct HaltTradingArgs {
+    pub reason: String,
+    /// Cancel every working order with reqGlobalCancel
+    #[serde(default)]
+    pub cancel_orders: bool,
+    /// Close every position with market orders
+    #[serde(default)]
+    pub flatten_positions: bool,
+}
+
+pub struct HaltTrading;
+
+#[async_trait]
+impl Tool for HaltTrading {
+    type Args = HaltTradingArgs;
+    type Output = HaltReport;
+
+    const NAME: &'static str = "halt_trading";
+    const DESCRIPTION: &'static str = "Kill switch: block all new and amended orders until resume_trading, optionally cancelling every working order (reqGlobalCancel) and closing every position at market. Survives restarts";
+    const MUTATING: bool = true;
+    const ALLOWED_READONLY: bool = true;
+
+    async fn call(&self, server: &ServerState, args: HaltTradingArgs) -> Result<HaltReport> {
+        let reason = non_empty("reason", &args.reason)?;
+        let report = server
+            .ibkr_client
+            .halt_trading(reason, args.cancel_orders, args.flatten_positions)
+            .await?;
+        tools_changed(server);
+        Ok(report)
+    }
+}
+
+/// Outcome of resume_trading; `released` is the halt that was lifted
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ResumeReport {
+    pub resumed: bool,
+    pub released: Option<TradingHalt>,
+}
+
+pub struct ResumeTrading;
+
+#[async_trait]
+impl Tool for ResumeTrading {
+    type Args = NoArgs;
+    type Output = ResumeReport;
+
+    const NAME: &'static str = "resume_trading";
+    const DESCRIPTION: &'static str = "Lift a trading halt engaged by halt_trading";
+    const MUTATING: bool = true;
+
+    async fn call(&self, server: &ServerState, _: NoArgs) -> Result<ResumeReport> {
+        let released = server.ibkr_client.resume_trading()?;
+        if released.is_some() {
+            tools_changed(server);
+        }
+        Ok(ResumeReport {
+            resumed: released.is_some(),
+            released,
+        })
+    }
+}
+
+// Halting and resuming change which tools `tools/list` offers
+fn tools_changed(server: &ServerState) {
+    server.sessions.broadcast(&json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/tools/list_changed"
+    }));
+}
@@ -0,0 +1,172 @@
+/// Checks tool results against their published `outputSchema`
+///
+/// Debug builds log every `structuredContent` that breaks its schema, and
+/// the tool tests fail on it, so a tool whose output drifts is caught
+/// before a client sees it. Only the JSON Schema keywords schemars emits
+/// are understood.
+use serde_json::Value;
+
+/// Where and how `value` breaks `schema`; empty when it conforms
+pub(super) fn violations(schema: &Value, value: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    check(schema, value, "$", &mut found);
+    found
+}
+
+fn check(schema: &Value, value: &Value, path: &str, found: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            found.push(format!("{}: no value is allowed", path));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(types) = schema.get("type") {
+        let allowed = match types {
+            Value::Array(types) => types.iter().any(|t| has_type(t, value)),
+            t => has_type(t, value),
+        };
+        if !allowed {
+            found.push(format!("{}: {} is not of type {}", path, value, types));
+            return;
+        }
+    }
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            found.push(format!("{}: {} is not one of {:?}", path, value, options));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            found.push(format!("{}: {} is not {}", path, value, expected));
+        }
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                found.push(format!("{}: {} is below {}", path, number, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                found.push(format!("{}: {} is above {}", path, number, maximum));
+            }
+        }
+    }
+
+    if let Value::Object(object) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    found.push(format!("{}: missing {}", path, name));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, field) in object {
+            let path = format!("{}.{}", path, name);
+            match properties.and_then(|p| p.get(name)) {
+                Some(property) => check(property, field, &path, found),
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        check(additional, field, &path, found);
+                    }
+                }
+            }
+        }
+    }
+    if let Value::Array(items) = value {
+        match schema.get("items") {
+            Some(Value::Array(positional)) => {
+                for (i, (item, schema)) in items.iter().zip(positional).enumerate() {
+                    check(schema, item, &format!("{}[{}]", path, i), found);
+                }
+            }
+            Some(schema) => {
+                for (i, item) in items.iter().enumerate() {
+                    check(schema, item, &format!("{}[{}]", path, i), found);
+                }
+            }
+            None => {}
+        }
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for schema in all {
+            check(schema, value, path, found);
+        }
+    }
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if !any
+            .iter()
+            .any(|schema| violations(schema, value).is_empty())
+        {
+            found.push(format!("{}: {} matches none of anyOf", path, value));
+        }
+    }
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        let matching = one
+            .iter()
+            .filter(|schema| violations(schema, value).is_empty())
+            .count();
+        if matching != 1 {
+            found.push(format!(
+                "{}: {} matches {} of oneOf, not exactly one",
+                path, value, matching
+            ));
+        }
+    }
+}
+
+fn has_type(instance_type: &Value, value: &Value) -> bool {
+    match instance_type.as_str() {
+        Some("null") => value.is_null(),
+        Some("boolean") => value.is_boolean(),
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("number") => value.is_number(),
+        Some("integer") => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reports_where_values_break_the_schema() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "side"],
+            "properties": {
+                "id": { "type": "integer", "minimum": 1 },
+                "side": { "type": "string", "enum": ["BUY", "SELL"] },
+                "fills": { "type": "array", "items": { "type": "number" } },
+                "note": { "anyOf": [{ "type": "string" }, { "type": "null" }] }
+            }
+        });
+        assert!(violations(&schema, &json!({ "id": 3, "side": "BUY", "note": null })).is_empty());
+
+        let found = violations(
+            &schema,
+            &json!({ "id": 0, "side": "HOLD", "fills": [1.5, "x"], "note": 7 }),
+        );
+        assert_eq!(found.len(), 4, "{:?}", found);
+        assert!(
+            found.iter().any(|v| v.starts_with("$.fills[1]")),
+            "{:?}",
+            found
+        );
+        assert!(violations(&schema, &json!({ "id": 1 }))
+            .iter()
+            .any(|v| v == "$: missing side"));
+    }
+}
@@ -0,0 +1,28 @@
+/// Account model
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One `accountSummary` row
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct AccountValue {
+    pub account: String,
+    pub tag: String,
+    pub value: String,
+    pub currency: String,
+}
+
+impl AccountValue {
+    pub fn new(
+        account: impl Into<String>,
+        tag: impl Into<String>,
+        value: impl Into<String>,
+        currency: impl Into<String>,
+    ) -> Self {
+        Self {
+            account: account.into(),
+            tag: tag.into(),
+            value: value.into(),
+            currency: currency.into(),
+        }
+    }
+}
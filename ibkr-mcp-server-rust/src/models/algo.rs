@@ -0,0 +1,388 @@
+/// IB algo strategies and their parameters
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::error::IBKRMCPError;
+use crate::models::OrderType;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum AlgoStrategy {
+    Adaptive,
+    Twap,
+    Vwap,
+    ArrivalPx,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum AdaptivePriority {
+    Urgent,
+    Normal,
+    Patient,
+}
+
+/// How a TWAP slice is priced
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum TwapStrategyType {
+    Marketable,
+    #[serde(rename = "Matching Midpoint")]
+    MatchingMidpoint,
+    #[serde(rename = "Matching Same Side")]
+    MatchingSameSide,
+    #[serde(rename = "Matching Last")]
+    MatchingLast,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub enum RiskAversion {
+    #[serde(rename = "Get Done")]
+    GetDone,
+    Aggressive,
+    Neutral,
+    Passive,
+}
+
+/// Parameters of an algo order; which ones apply depends on the strategy
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlgoParams {
+    /// Adaptive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_priority: Option<AdaptivePriority>,
+
+    /// TWAP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_type: Option<TwapStrategyType>,
+
+    /// VWAP and Arrival Price: participation cap, 0.01 to 0.5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pct_vol: Option<f64>,
+
+    /// Arrival Price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_aversion: Option<RiskAversion>,
+
+    /// `HH:MM:SS [time zone]` or `YYYYMMDD HH:MM:SS [time zone]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_past_end_time: Option<bool>,
+
+    /// VWAP: never take liquidity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_take_liq: Option<bool>,
+
+    /// Arrival Price: finish by the end time even if it moves the market
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_completion: Option<bool>,
+}
+
+impl AlgoStrategy {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlgoStrategy::Adaptive => "Adaptive",
+            AlgoStrategy::Twap => "Twap",
+            AlgoStrategy::Vwap => "Vwap",
+            AlgoStrategy::ArrivalPx => "ArrivalPx",
+        }
+    }
+
+    /// Parameters this strategy understands
+    fn accepts(&self) -> &'static [&'static str] {
+        match self {
+            AlgoStrategy::Adaptive => &["adaptive_priority"],
+            AlgoStrategy::Twap => &[
+                "strategy_type",
+                "start_time",
+                "end_time",
+                "allow_past_end_time",
+            ],
+            AlgoStrategy::Vwap => &[
+                "max_pct_vol",
+                "start_time",
+                "end_time",
+                "allow_past_end_time",
+                "no_take_liq",
+            ],
+            AlgoStrategy::ArrivalPx => &[
+                "max_pct_vol",
+                "risk_aversion",
+                "start_time",
+                "end_time",
+                "allow_past_end_time",
+                "force_completion",
+            ],
+        }
+    }
+}
+
+impl FromStr for AlgoStrategy {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "adaptive" => Ok(AlgoStrategy::Adaptive),
+            "twap" => Ok(AlgoStrategy::Twap),
+            "vwap" => Ok(AlgoStrategy::Vwap),
+            "arrivalpx" | "arrival_price" => Ok(AlgoStrategy::ArrivalPx),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown algo_strategy: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl AlgoParams {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Names of the parameters that are set
+    fn present(&self) -> Vec<&'static str> {
+        [
+            ("adaptive_priority", self.adaptive_priority.is_some()),
+            ("strategy_type", self.strategy_type.is_some()),
+            ("max_pct_vol", self.max_pct_vol.is_some()),
+            ("risk_aversion", self.risk_aversion.is_some()),
+            ("start_time", self.start_time.is_some()),
+            ("end_time", self.end_time.is_some()),
+            ("allow_past_end_time", self.allow_past_end_time.is_some()),
+            ("no_take_liq", self.no_take_liq.is_some()),
+            ("force_completion", self.force_completion.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+
+    /// Check these parameters against `strategy` and the order they ride on
+    pub fn validate(&self, strategy: AlgoStrategy, order_type: &OrderType) -> crate::Result<()> {
+        let invalid = |message: String| Err(IBKRMCPError::InvalidParameter(message));
+
+        if let Some(name) = self
+            .present()
+            .into_iter()
+            .find(|name| !strategy.accepts().contains(name))
+        {
+            return invalid(format!(
+                "{} does not apply to the {} algo",
+                name,
+                strategy.as_str()
+            ));
+        }
+
+        match strategy {
+            AlgoStrategy::Adaptive => {
+                if !matches!(order_type, OrderType::Market | OrderType::Limit) {
+                    return invalid("The Adaptive algo only works with MKT and LMT orders".into());
+                }
+                if self.adaptive_priority.is_none() {
+                    return invalid("The Adaptive algo requires adaptive_priority".into());
+                }
+            }
+            AlgoStrategy::Twap if self.strategy_type.is_none() => {
+                return invalid("The Twap algo requires strategy_type".into());
+            }
+            AlgoStrategy::Vwap if self.max_pct_vol.is_none() => {
+                return invalid("The Vwap algo requires max_pct_vol".into());
+            }
+            AlgoStrategy::ArrivalPx if self.risk_aversion.is_none() => {
+                return invalid("The ArrivalPx algo requires risk_aversion".into());
+            }
+            _ => {}
+        }
+
+        if let Some(pct) = self.max_pct_vol {
+            if !(0.01..=0.5).contains(&pct) {
+                return invalid(format!(
+                    "max_pct_vol must be between 0.01 and 0.5, got {}",
+                    pct
+                ));
+            }
+        }
+        for (name, time) in [
+            ("start_time", &self.start_time),
+            ("end_time", &self.end_time),
+        ] {
+            if let Some(time) = time {
+                if !is_algo_time(time) {
+                    return invalid(format!(
+                        "{} must look like \"HH:MM:SS [time zone]\" or \"YYYYMMDD HH:MM:SS [time zone]\", got \"{}\"",
+                        name, time
+                    ));
+                }
+            }
+        }
+        if let (Some(start), Some(end)) = (&self.start_time, &self.end_time) {
+            // Only comparable when both use the same layout and zone
+            let (start_at, start_zone) = split_zone(start);
+            let (end_at, end_zone) = split_zone(end);
+            if start_at.len() == end_at.len() && start_zone == end_zone && start_at >= end_at {
+                return invalid("start_time must be before end_time".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// TWS `algoParams` tag/value pairs
+    pub fn to_tag_values(&self) -> Vec<(String, String)> {
+        let flag = |b: bool| if b { "1" } else { "0" }.to_string();
+
+        let mut tags = Vec::new();
+        let mut add = |tag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                tags.push((tag.to_string(), value));
+            }
+        };
+        add(
+            "adaptivePriority",
+            self.adaptive_priority.as_ref().map(label),
+        );
+        add("strategyType", self.strategy_type.as_ref().map(label));
+        add("maxPctVol", self.max_pct_vol.map(|v| v.to_string()));
+        add("riskAversion", self.risk_aversion.as_ref().map(label));
+        add("startTime", self.start_time.clone());
+        add("endTime", self.end_time.clone());
+        add("allowPastEndTime", self.allow_past_end_time.map(flag));
+        add("noTakeLiq", self.no_take_liq.map(flag));
+        add("forceCompletion", self.force_completion.map(flag));
+        tags
+    }
+
+    /// Inverse of [`AlgoParams::to_tag_values`]; unknown tags are ignored
+    pub fn from_tag_values(tags: &[(String, String)]) -> crate::Result<Self> {
+        let flag = |value: &str| value == "1" || value.eq_ignore_ascii_case("true");
+        let mut params = Self::default();
+        for (tag, value) in tags {
+            match tag.as_str() {
+                "adaptivePriority" => params.adaptive_priority = Some(parse_label(value)?),
+                "strategyType" => params.strategy_type = Some(parse_label(value)?),
+                "maxPctVol" => {
+                    params.max_pct_vol = Some(value.parse().map_err(|_| {
+                        IBKRMCPError::InvalidParameter(format!("Invalid maxPctVol: {}", value))
+                    })?)
+                }
+                "riskAversion" => params.risk_aversion = Some(parse_label(value)?),
+                "startTime" => params.start_time = Some(value.clone()),
+                "endTime" => params.end_time = Some(value.clone()),
+                "allowPastEndTime" => params.allow_past_end_time = Some(flag(value)),
+                "noTakeLiq" => params.no_take_liq = Some(flag(value)),
+                "forceCompletion" => params.force_completion = Some(flag(value)),
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+// The serde names of the parameter enums are also their TWS values
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn parse_label<T: DeserializeOwned>(value: &str) -> crate::Result<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| IBKRMCPError::InvalidParameter(format!("Invalid algo value: {}", value)))
+}
+
+fn split_zone(value: &str) -> (&str, &str) {
+    // The zone follows the seconds of the time component
+    let time_end = value.find(':').map(|i| i + 6).unwrap_or(value.len());
+    value
+        .get(..time_end)
+        .map(|at| (at, value[time_end..].trim()))
+        .unwrap_or((value, ""))
+}
+
+fn is_algo_time(value: &str) -> bool {
+    let (at, zone) = split_zone(value);
+    let zone_ok = zone.is_empty() || value[at.len()..].starts_with(' ');
+    let at_ok = chrono::NaiveTime::parse_from_str(at, "%H:%M:%S").is_ok()
+        || chrono::NaiveDateTime::parse_from_str(at, "%Y%m%d %H:%M:%S").is_ok();
+    at_ok && zone_ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_are_checked_per_strategy() {
+        let adaptive = AlgoParams {
+            adaptive_priority: Some(AdaptivePriority::Patient),
+            ..Default::default()
+        };
+        assert!(adaptive
+            .validate(AlgoStrategy::Adaptive, &OrderType::Limit)
+            .is_ok());
+        assert!(adaptive
+            .validate(AlgoStrategy::Adaptive, &OrderType::Stop)
+            .is_err());
+        assert!(adaptive
+            .validate(AlgoStrategy::Vwap, &OrderType::Limit)
+            .is_err());
+        assert!(AlgoParams::default()
+            .validate(AlgoStrategy::Adaptive, &OrderType::Market)
+            .is_err());
+
+        let mut vwap = AlgoParams {
+            max_pct_vol: Some(0.1),
+            start_time: Some("09:30:00 US/Eastern".into()),
+            end_time: Some("16:00:00 US/Eastern".into()),
+            no_take_liq: Some(true),
+            ..Default::default()
+        };
+        assert!(vwap.validate(AlgoStrategy::Vwap, &OrderType::Limit).is_ok());
+        vwap.max_pct_vol = Some(0.8);
+        assert!(vwap
+            .validate(AlgoStrategy::Vwap, &OrderType::Limit)
+            .is_err());
+        vwap.max_pct_vol = Some(0.1);
+        vwap.end_time = Some("09:00:00 US/Eastern".into());
+        assert!(vwap
+            .validate(AlgoStrategy::Vwap, &OrderType::Limit)
+            .is_err());
+        vwap.end_time = Some("4pm".into());
+        assert!(vwap
+            .validate(AlgoStrategy::Vwap, &OrderType::Limit)
+            .is_err());
+        vwap.end_time = None;
+        assert!(vwap
+            .validate(AlgoStrategy::ArrivalPx, &OrderType::Limit)
+            .is_err());
+    }
+
+    #[test]
+    fn tag_values_round_trip() {
+        let params = AlgoParams {
+            strategy_type: Some(TwapStrategyType::MatchingMidpoint),
+            start_time: Some("20260320 09:30:00 US/Eastern".into()),
+            allow_past_end_time: Some(false),
+            ..Default::default()
+        };
+        let tags = params.to_tag_values();
+        assert!(tags.contains(&("strategyType".to_string(), "Matching Midpoint".to_string())));
+        assert!(tags.contains(&("allowPastEndTime".to_string(), "0".to_string())));
+        assert_eq!(AlgoParams::from_tag_values(&tags).unwrap(), params);
+
+        let arrival = AlgoParams {
+            max_pct_vol: Some(0.25),
+            risk_aversion: Some(RiskAversion::GetDone),
+            force_completion: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            AlgoParams::from_tag_values(&arrival.to_tag_values()).unwrap(),
+            arrival
+        );
+    }
+}
@@ -1,7 +1,11 @@
 /// Contract model
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::error::IBKRMCPError;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct Contract {
     pub symbol: String,
     pub sec_type: SecType,
@@ -44,7 +48,7 @@ fn default_currency() -> String {
     "USD".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum SecType {
     #[serde(rename = "STK")]
@@ -67,6 +71,45 @@ pub enum SecType {
     Commodity,
 }
 
+impl SecType {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecType::Stock => "STK",
+            SecType::Option => "OPT",
+            SecType::Future => "FUT",
+            SecType::Forex => "CASH",
+            SecType::Index => "IND",
+            SecType::CFD => "CFD",
+            SecType::Bond => "BOND",
+            SecType::Warrant => "WAR",
+            SecType::Commodity => "CMDTY",
+        }
+    }
+}
+
+impl FromStr for SecType {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "STK" => Ok(SecType::Stock),
+            "OPT" => Ok(SecType::Option),
+            "FUT" => Ok(SecType::Future),
+            "CASH" => Ok(SecType::Forex),
+            "IND" => Ok(SecType::Index),
+            "CFD" => Ok(SecType::CFD),
+            "BOND" => Ok(SecType::Bond),
+            "WAR" => Ok(SecType::Warrant),
+            "CMDTY" => Ok(SecType::Commodity),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown sec_type: {}",
+                s
+            ))),
+        }
+    }
+}
+
 impl Default for Contract {
     fn default() -> Self {
         Self {
@@ -1,6 +1,7 @@
 use super::Contract;
 use chrono::{DateTime, Utc};
 /// Market data models
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,7 +19,27 @@ pub struct TickData {
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Latest quote of a contract; prices TWS did not report are `None`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct MarketSnapshot {
+    pub symbol: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bid: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ask: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct BarData {
     pub date: DateTime<Utc>,
     pub open: f64,
@@ -1,12 +1,19 @@
 /// Data models for IBKR MCP Server
+pub mod account;
+pub mod algo;
 pub mod contract;
 pub mod market_data;
 pub mod order;
 pub mod position;
 pub mod response;
 
+pub use account::AccountValue;
+pub use algo::{AdaptivePriority, AlgoParams, AlgoStrategy, RiskAversion, TwapStrategyType};
 pub use contract::{Contract, SecType};
-pub use market_data::{BarData, MarketDataRequest, TickData};
-pub use order::{Order, OrderAction, OrderStatus, OrderType, TimeInForce};
+pub use market_data::{BarData, MarketDataRequest, MarketSnapshot, TickData};
+pub use order::{
+    BracketOrder, BracketOrderIds, OcaType, Order, OrderAction, OrderChanges, OrderPreview,
+    OrderStatus, OrderType, TimeInForce,
+};
 pub use position::Position;
-pub use response::MCPResponse;
+pub use response::{CallToolResult, Content, MCPResponse};
@@ -1,7 +1,12 @@
 /// Order model
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::error::IBKRMCPError;
+use crate::models::{AlgoParams, AlgoStrategy};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct Order {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub order_id: Option<i32>,
@@ -9,6 +14,10 @@ pub struct Order {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_id: Option<i32>,
 
+    /// Account to trade in; TWS uses the login's default account if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+
     pub action: OrderAction,
     pub total_quantity: f64,
     pub order_type: OrderType,
@@ -16,9 +25,22 @@ pub struct Order {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lmt_price: Option<f64>,
 
+    /// Stop price for STP / STP LMT, trailing amount for TRAIL / TRAIL LIMIT
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aux_price: Option<f64>,
 
+    /// Trailing distance as a percentage, instead of `aux_price`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailing_percent: Option<f64>,
+
+    /// Initial stop price of a trailing order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail_stop_price: Option<f64>,
+
+    /// Distance of the limit price from the stop for TRAIL LIMIT
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lmt_price_offset: Option<f64>,
+
     #[serde(default = "default_tif")]
     pub time_in_force: TimeInForce,
 
@@ -33,13 +55,93 @@ pub struct Order {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub good_till_date: Option<String>,
+
+    /// Parent order ID for attached (e.g. bracket) child orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i32>,
+
+    /// When false, TWS holds the order until a later order in the same
+    /// group is transmitted
+    #[serde(default = "default_transmit")]
+    pub transmit: bool,
+
+    /// Free-form tag TWS keeps with the order and reports back (`orderRef`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_ref: Option<String>,
+
+    /// One-cancels-all group shared by sibling orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oca_group: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oca_type: Option<OcaType>,
+
+    /// IB algo that works the order, e.g. Adaptive or VWAP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algo_strategy: Option<AlgoStrategy>,
+
+    #[serde(default, skip_serializing_if = "AlgoParams::is_empty")]
+    pub algo_params: AlgoParams,
+
+    /// Ask TWS for margin and commission estimates without transmitting
+    #[serde(default)]
+    pub what_if: bool,
 }
 
 fn default_tif() -> TimeInForce {
     TimeInForce::Day
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+fn default_transmit() -> bool {
+    true
+}
+
+/// Margin and commission impact of an order, as estimated by a what-if
+/// submission. Values TWS leaves unset are `None`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct OrderPreview {
+    pub init_margin_before: Option<f64>,
+    pub maint_margin_before: Option<f64>,
+    pub equity_with_loan_before: Option<f64>,
+    pub init_margin_change: Option<f64>,
+    pub maint_margin_change: Option<f64>,
+    pub equity_with_loan_change: Option<f64>,
+    pub init_margin_after: Option<f64>,
+    pub maint_margin_after: Option<f64>,
+    pub equity_with_loan_after: Option<f64>,
+    pub commission: Option<f64>,
+    pub min_commission: Option<f64>,
+    pub max_commission: Option<f64>,
+    pub commission_currency: Option<String>,
+    pub warning_text: Option<String>,
+}
+
+/// Amendments to a working order; unset fields keep their current value
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct OrderChanges {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<TimeInForce>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub good_till_date: Option<String>,
+}
+
+impl OrderChanges {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum OrderAction {
     #[serde(rename = "BUY")]
@@ -48,7 +150,7 @@ pub enum OrderAction {
     Sell,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum OrderType {
     #[serde(rename = "MKT")]
     Market,
@@ -64,7 +166,7 @@ pub enum OrderType {
     TrailLimit,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum OrderStatus {
     PendingSubmit,
     PendingCancel,
@@ -77,7 +179,20 @@ pub enum OrderStatus {
     Rejected,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// What happens to the rest of an OCA group when one order fills
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OcaType {
+    /// Cancel the other orders; overfills are blocked
+    #[default]
+    CancelWithBlock,
+    /// Reduce the other orders by the filled quantity; overfills are blocked
+    ReduceWithBlock,
+    /// Reduce the other orders by the filled quantity, without blocking
+    ReduceNonBlock,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum TimeInForce {
     #[serde(rename = "DAY")]
@@ -90,21 +205,214 @@ pub enum TimeInForce {
     Gtd,
 }
 
+impl OrderAction {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderAction::Buy => "BUY",
+            OrderAction::Sell => "SELL",
+        }
+    }
+}
+
+impl FromStr for OrderAction {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BUY" => Ok(OrderAction::Buy),
+            "SELL" => Ok(OrderAction::Sell),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown action: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl OrderType {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::Market => "MKT",
+            OrderType::Limit => "LMT",
+            OrderType::Stop => "STP",
+            OrderType::StopLimit => "STP LMT",
+            OrderType::Trail => "TRAIL",
+            OrderType::TrailLimit => "TRAIL LIMIT",
+        }
+    }
+}
+
+impl FromStr for OrderType {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "MKT" => Ok(OrderType::Market),
+            "LMT" => Ok(OrderType::Limit),
+            "STP" => Ok(OrderType::Stop),
+            "STP LMT" => Ok(OrderType::StopLimit),
+            "TRAIL" => Ok(OrderType::Trail),
+            "TRAIL LIMIT" => Ok(OrderType::TrailLimit),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown order_type: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl OrderStatus {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::PendingSubmit => "PendingSubmit",
+            OrderStatus::PendingCancel => "PendingCancel",
+            OrderStatus::PreSubmitted => "PreSubmitted",
+            OrderStatus::Submitted => "Submitted",
+            OrderStatus::Cancelled => "Cancelled",
+            OrderStatus::Filled => "Filled",
+            OrderStatus::Inactive => "Inactive",
+            OrderStatus::PendingReject => "PendingReject",
+            OrderStatus::Rejected => "Rejected",
+        }
+    }
+
+    /// TWS accepts a `placeOrder` with this order's ID as an amendment
+    pub fn is_modifiable(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::PendingSubmit | OrderStatus::PreSubmitted | OrderStatus::Submitted
+        )
+    }
+
+    /// No further status changes are expected
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Cancelled
+                | OrderStatus::Filled
+                | OrderStatus::Inactive
+                | OrderStatus::Rejected
+        )
+    }
+
+    /// Whether a report of `next` may replace this status. Terminal states
+    /// are final, and a working order never falls back to `PendingSubmit`,
+    /// so late or replayed `orderStatus` messages cannot undo progress.
+    pub fn can_transition_to(&self, next: &OrderStatus) -> bool {
+        if self.is_terminal() {
+            return self == next;
+        }
+        !matches!(
+            (self, next),
+            (
+                OrderStatus::PreSubmitted | OrderStatus::Submitted | OrderStatus::PendingCancel,
+                OrderStatus::PendingSubmit
+            )
+        )
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "PendingSubmit" | "ApiPending" => Ok(OrderStatus::PendingSubmit),
+            "PendingCancel" => Ok(OrderStatus::PendingCancel),
+            "PreSubmitted" => Ok(OrderStatus::PreSubmitted),
+            "Submitted" => Ok(OrderStatus::Submitted),
+            "Cancelled" | "ApiCancelled" => Ok(OrderStatus::Cancelled),
+            "Filled" => Ok(OrderStatus::Filled),
+            "Inactive" => Ok(OrderStatus::Inactive),
+            "PendingReject" => Ok(OrderStatus::PendingReject),
+            "Rejected" => Ok(OrderStatus::Rejected),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown order status: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl OcaType {
+    /// TWS wire representation
+    pub fn code(&self) -> i32 {
+        match self {
+            OcaType::CancelWithBlock => 1,
+            OcaType::ReduceWithBlock => 2,
+            OcaType::ReduceNonBlock => 3,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            1 => Some(OcaType::CancelWithBlock),
+            2 => Some(OcaType::ReduceWithBlock),
+            3 => Some(OcaType::ReduceNonBlock),
+            _ => None,
+        }
+    }
+}
+
+impl TimeInForce {
+    /// TWS wire representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeInForce::Day => "DAY",
+            TimeInForce::Gtc => "GTC",
+            TimeInForce::Ioc => "IOC",
+            TimeInForce::Gtd => "GTD",
+        }
+    }
+}
+
+impl FromStr for TimeInForce {
+    type Err = IBKRMCPError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DAY" => Ok(TimeInForce::Day),
+            "GTC" => Ok(TimeInForce::Gtc),
+            "IOC" => Ok(TimeInForce::Ioc),
+            "GTD" => Ok(TimeInForce::Gtd),
+            _ => Err(IBKRMCPError::InvalidParameter(format!(
+                "Unknown time_in_force: {}",
+                s
+            ))),
+        }
+    }
+}
+
 impl Order {
     pub fn new(action: OrderAction, quantity: f64, order_type: OrderType) -> Self {
         Self {
             order_id: None,
             client_id: None,
+            account: None,
             action,
             total_quantity: quantity,
             order_type,
             lmt_price: None,
             aux_price: None,
+            trailing_percent: None,
+            trail_stop_price: None,
+            lmt_price_offset: None,
             time_in_force: TimeInForce::Day,
             outside_rth: false,
             hidden: false,
             good_after_time: None,
             good_till_date: None,
+            parent_id: None,
+            transmit: true,
+            order_ref: None,
+            oca_group: None,
+            oca_type: None,
+            algo_strategy: None,
+            algo_params: AlgoParams::default(),
+            what_if: false,
         }
     }
 
@@ -118,8 +426,435 @@ impl Order {
         self
     }
 
+    pub fn with_trailing_amount(mut self, amount: f64) -> Self {
+        self.aux_price = Some(amount);
+        self
+    }
+
+    pub fn with_trailing_percent(mut self, percent: f64) -> Self {
+        self.trailing_percent = Some(percent);
+        self
+    }
+
+    pub fn with_trail_stop_price(mut self, price: f64) -> Self {
+        self.trail_stop_price = Some(price);
+        self
+    }
+
+    pub fn with_algo(mut self, strategy: AlgoStrategy, params: AlgoParams) -> Self {
+        self.algo_strategy = Some(strategy);
+        self.algo_params = params;
+        self
+    }
+
     pub fn with_tif(mut self, tif: TimeInForce) -> Self {
         self.time_in_force = tif;
         self
     }
+
+    pub fn with_oca_group(mut self, group: impl Into<String>, oca_type: OcaType) -> Self {
+        self.oca_group = Some(group.into());
+        self.oca_type = Some(oca_type);
+        self
+    }
+
+    pub fn with_order_ref(mut self, order_ref: impl Into<String>) -> Self {
+        self.order_ref = Some(order_ref.into());
+        self
+    }
+
+    /// Check that the fields this order type needs are present and sane
+    pub fn validate(&self) -> crate::Result<()> {
+        let missing = |field: &str| {
+            Err(IBKRMCPError::InvalidParameter(format!(
+                "{} orders require {}",
+                self.order_type.as_str(),
+                field
+            )))
+        };
+
+        if !self.total_quantity.is_finite() || self.total_quantity <= 0.0 {
+            return Err(IBKRMCPError::InvalidParameter(
+                "quantity must be positive".to_string(),
+            ));
+        }
+        for (name, value) in [
+            ("limit price", self.lmt_price),
+            ("stop price", self.aux_price),
+            ("trailing percent", self.trailing_percent),
+            ("trail stop price", self.trail_stop_price),
+            ("limit price offset", self.lmt_price_offset),
+        ] {
+            if value.is_some_and(|v| !v.is_finite() || v <= 0.0) {
+                return Err(IBKRMCPError::InvalidParameter(format!(
+                    "{} must be positive",
+                    name
+                )));
+            }
+        }
+
+        self.validate_timing()?;
+        match self.algo_strategy {
+            Some(strategy) => self.algo_params.validate(strategy, &self.order_type)?,
+            None if !self.algo_params.is_empty() => {
+                return Err(IBKRMCPError::InvalidParameter(
+                    "algo_params given without an algo_strategy".to_string(),
+                ))
+            }
+            None => {}
+        }
+
+        match self.order_type {
+            OrderType::Market => {}
+            OrderType::Limit if self.lmt_price.is_none() => return missing("a limit price"),
+            OrderType::Limit => {}
+            OrderType::Stop if self.aux_price.is_none() => return missing("a stop price"),
+            OrderType::Stop => {}
+            OrderType::StopLimit => {
+                if self.aux_price.is_none() {
+                    return missing("a stop price");
+                }
+                if self.lmt_price.is_none() {
+                    return missing("a limit price");
+                }
+            }
+            OrderType::Trail | OrderType::TrailLimit => {
+                match (self.aux_price, self.trailing_percent) {
+                    (None, None) => return missing("a trailing amount or trailing percent"),
+                    (Some(_), Some(_)) => {
+                        return Err(IBKRMCPError::InvalidParameter(
+                            "Give either a trailing amount or a trailing percent, not both"
+                                .to_string(),
+                        ))
+                    }
+                    _ => {}
+                }
+                if self.order_type == OrderType::TrailLimit {
+                    if self.trail_stop_price.is_none() {
+                        return missing("a trail stop price");
+                    }
+                    match (self.lmt_price, self.lmt_price_offset) {
+                        (None, None) => return missing("a limit price or limit price offset"),
+                        (Some(_), Some(_)) => {
+                            return Err(IBKRMCPError::InvalidParameter(
+                                "Give either a limit price or a limit price offset, not both"
+                                    .to_string(),
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Time in force, session and activation-time combinations TWS accepts
+    fn validate_timing(&self) -> crate::Result<()> {
+        match (&self.time_in_force, &self.good_till_date) {
+            (TimeInForce::Gtd, None) => {
+                return Err(IBKRMCPError::InvalidParameter(
+                    "good_till_date is required for GTD orders".to_string(),
+                ))
+            }
+            (TimeInForce::Gtd, Some(date)) => validate_tws_time("good_till_date", date)?,
+            (tif, Some(_)) => {
+                return Err(IBKRMCPError::InvalidParameter(format!(
+                    "good_till_date only applies to GTD orders, not {}",
+                    tif.as_str()
+                )))
+            }
+            (_, None) => {}
+        }
+        if let Some(time) = &self.good_after_time {
+            validate_tws_time("good_after_time", time)?;
+        }
+        // Most venues only run immediate-or-cancel orders in the regular session
+        if self.outside_rth && self.time_in_force == TimeInForce::Ioc {
+            return Err(IBKRMCPError::InvalidParameter(
+                "IOC orders cannot be executed outside regular trading hours".to_string(),
+            ));
+        }
+        if self.hidden && self.lmt_price.is_none() {
+            return Err(IBKRMCPError::InvalidParameter(
+                "hidden orders must carry a limit price".to_string(),
+            ));
+        }
+        if let (Some(after), Some(till)) = (&self.good_after_time, &self.good_till_date) {
+            if tws_time_prefix(after) >= tws_time_prefix(till) {
+                return Err(IBKRMCPError::InvalidParameter(
+                    "good_after_time must be before good_till_date".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// This order with `changes` applied
+    pub fn with_changes(mut self, changes: &OrderChanges) -> Self {
+        if let Some(quantity) = changes.quantity {
+            self.total_quantity = quantity;
+        }
+        if let Some(price) = changes.limit_price {
+            self.lmt_price = Some(price);
+        }
+        if let Some(price) = changes.stop_price {
+            self.aux_price = Some(price);
+        }
+        if let Some(tif) = &changes.time_in_force {
+            // An expiry only survives as long as the order stays GTD
+            if *tif != TimeInForce::Gtd {
+                self.good_till_date = None;
+            }
+            self.time_in_force = tif.clone();
+        }
+        if let Some(date) = &changes.good_till_date {
+            self.good_till_date = Some(date.clone());
+        }
+        self
+    }
+}
+
+/// TWS times are `YYYYMMDD HH:MM:SS`, optionally followed by a time zone
+/// (`20260320 15:59:00 US/Eastern`)
+fn validate_tws_time(name: &str, value: &str) -> crate::Result<()> {
+    let valid = value.get(..17).is_some_and(|timestamp| {
+        let zone = &value[17..];
+        chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d %H:%M:%S").is_ok()
+            && (zone.is_empty() || (zone.starts_with(' ') && !zone.trim().is_empty()))
+    });
+    if !valid {
+        return Err(IBKRMCPError::InvalidParameter(format!(
+            "{} must look like \"YYYYMMDD HH:MM:SS [time zone]\", got \"{}\"",
+            name, value
+        )));
+    }
+    Ok(())
+}
+
+fn tws_time_prefix(value: &str) -> &str {
+    value.get(..17).unwrap_or(value)
+}
+
+/// Entry order with attached take-profit and stop-loss exits
+///
+/// The parent and take-profit are sent with `transmit = false` and the
+/// stop-loss with `transmit = true`, so TWS releases all three together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BracketOrder {
+    pub parent: Order,
+    pub take_profit: Order,
+    pub stop_loss: Order,
+}
+
+/// Order IDs assigned to the legs of a bracket
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BracketOrderIds {
+    pub parent_order_id: i32,
+    pub take_profit_order_id: i32,
+    pub stop_loss_order_id: i32,
+}
+
+impl BracketOrder {
+    pub fn new(entry: Order, take_profit_price: f64, stop_loss_price: f64) -> crate::Result<Self> {
+        let is_buy = entry.action == OrderAction::Buy;
+        let (above, below) = if is_buy {
+            (take_profit_price, stop_loss_price)
+        } else {
+            (stop_loss_price, take_profit_price)
+        };
+        if above <= below {
+            return Err(IBKRMCPError::InvalidParameter(format!(
+                "take_profit_price must be {} stop_loss_price for a {} bracket",
+                if is_buy { "above" } else { "below" },
+                entry.action.as_str()
+            )));
+        }
+        if let Some(entry_price) = entry.lmt_price {
+            if !(below < entry_price && entry_price < above) {
+                return Err(IBKRMCPError::InvalidParameter(format!(
+                    "entry limit price {} must lie between the stop-loss and take-profit prices",
+                    entry_price
+                )));
+            }
+        }
+
+        let exit_action = if is_buy {
+            OrderAction::Sell
+        } else {
+            OrderAction::Buy
+        };
+        let quantity = entry.total_quantity;
+        let tif = entry.time_in_force.clone();
+        let good_till_date = entry.good_till_date.clone();
+
+        let mut parent = entry;
+        parent.transmit = false;
+
+        let mut take_profit = Order::new(exit_action.clone(), quantity, OrderType::Limit)
+            .with_limit_price(take_profit_price)
+            .with_tif(tif.clone());
+        take_profit.transmit = false;
+        take_profit.good_till_date = good_till_date.clone();
+
+        let mut stop_loss = Order::new(exit_action, quantity, OrderType::Stop)
+            .with_stop_price(stop_loss_price)
+            .with_tif(tif);
+        stop_loss.good_till_date = good_till_date;
+
+        Ok(Self {
+            parent,
+            take_profit,
+            stop_loss,
+        })
+    }
+
+    /// Link both exits to the parent once its ID is known
+    pub fn with_parent_id(mut self, parent_id: i32) -> Self {
+        self.parent.order_id = Some(parent_id);
+        self.take_profit.parent_id = Some(parent_id);
+        self.stop_loss.parent_id = Some(parent_id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_requires_fields_per_order_type() {
+        let order = |order_type| Order::new(OrderAction::Sell, 10.0, order_type);
+
+        assert!(order(OrderType::Market).validate().is_ok());
+        assert!(order(OrderType::Limit).validate().is_err());
+        assert!(order(OrderType::Stop)
+            .with_stop_price(95.0)
+            .validate()
+            .is_ok());
+        assert!(order(OrderType::StopLimit)
+            .with_stop_price(95.0)
+            .validate()
+            .is_err());
+        assert!(order(OrderType::StopLimit)
+            .with_stop_price(95.0)
+            .with_limit_price(94.5)
+            .validate()
+            .is_ok());
+
+        assert!(order(OrderType::Trail).validate().is_err());
+        assert!(order(OrderType::Trail)
+            .with_trailing_percent(2.0)
+            .validate()
+            .is_ok());
+        assert!(order(OrderType::Trail)
+            .with_trailing_amount(1.5)
+            .with_trailing_percent(2.0)
+            .validate()
+            .is_err());
+
+        let trail_limit = order(OrderType::TrailLimit).with_trailing_amount(1.5);
+        assert!(trail_limit.clone().validate().is_err());
+        let mut trail_limit = trail_limit.with_trail_stop_price(98.0);
+        assert!(trail_limit.validate().is_err());
+        trail_limit.lmt_price_offset = Some(0.1);
+        assert!(trail_limit.validate().is_ok());
+        for offset in [f64::NAN, -0.1] {
+            let mut invalid = trail_limit.clone();
+            invalid.lmt_price_offset = Some(offset);
+            assert!(invalid.validate().is_err());
+        }
+        assert!(trail_limit.with_limit_price(97.9).validate().is_err());
+
+        assert!(Order::new(OrderAction::Buy, 0.0, OrderType::Market)
+            .validate()
+            .is_err());
+        assert!(order(OrderType::Limit)
+            .with_limit_price(-1.0)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn validate_checks_time_in_force_combinations() {
+        let limit = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(100.0);
+
+        let mut gtd = limit.clone().with_tif(TimeInForce::Gtd);
+        assert!(gtd.validate().is_err());
+        gtd.good_till_date = Some("20260320 15:59:00 US/Eastern".into());
+        assert!(gtd.validate().is_ok());
+        gtd.good_till_date = Some("next friday".into());
+        assert!(gtd.validate().is_err());
+
+        let mut day = limit.clone();
+        day.good_till_date = Some("20260320 15:59:00".into());
+        assert!(day.validate().is_err());
+
+        let mut ioc = limit.clone().with_tif(TimeInForce::Ioc);
+        ioc.outside_rth = true;
+        assert!(ioc.validate().is_err());
+        ioc.time_in_force = TimeInForce::Gtc;
+        assert!(ioc.validate().is_ok());
+
+        let mut window = limit.with_tif(TimeInForce::Gtd);
+        window.good_after_time = Some("20260321 09:30:00".into());
+        window.good_till_date = Some("20260320 16:00:00".into());
+        assert!(window.validate().is_err());
+        window.good_after_time = Some("20260319 09:30:00".into());
+        assert!(window.validate().is_ok());
+
+        let mut hidden = Order::new(OrderAction::Sell, 10.0, OrderType::Market);
+        hidden.hidden = true;
+        assert!(hidden.validate().is_err());
+        let mut hidden = Order::new(OrderAction::Sell, 10.0, OrderType::Stop).with_stop_price(95.0);
+        hidden.hidden = true;
+        assert!(hidden.validate().is_err());
+        let mut hidden = hidden.with_limit_price(94.5);
+        hidden.order_type = OrderType::StopLimit;
+        assert!(hidden.validate().is_ok());
+    }
+
+    #[test]
+    fn changing_tif_drops_stale_expiry() {
+        let mut gtd =
+            Order::new(OrderAction::Buy, 1.0, OrderType::Market).with_tif(TimeInForce::Gtd);
+        gtd.good_till_date = Some("20260320 15:59:00".into());
+        let changed = gtd.with_changes(&OrderChanges {
+            time_in_force: Some(TimeInForce::Gtc),
+            ..Default::default()
+        });
+        assert_eq!(changed.good_till_date, None);
+        assert!(changed.validate().is_ok());
+    }
+
+    #[test]
+    fn bracket_legs_are_linked_and_staged() {
+        let entry = Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(150.0);
+        let bracket = BracketOrder::new(entry, 160.0, 145.0)
+            .unwrap()
+            .with_parent_id(7);
+
+        assert!(!bracket.parent.transmit);
+        assert!(!bracket.take_profit.transmit);
+        assert!(bracket.stop_loss.transmit);
+
+        assert_eq!(bracket.take_profit.action, OrderAction::Sell);
+        assert_eq!(bracket.take_profit.lmt_price, Some(160.0));
+        assert_eq!(bracket.stop_loss.order_type, OrderType::Stop);
+        assert_eq!(bracket.stop_loss.aux_price, Some(145.0));
+        assert_eq!(bracket.stop_loss.total_quantity, 100.0);
+        assert_eq!(bracket.take_profit.parent_id, Some(7));
+        assert_eq!(bracket.stop_loss.parent_id, Some(7));
+    }
+
+    #[test]
+    fn bracket_prices_must_straddle_entry() {
+        let buy = Order::new(OrderAction::Buy, 1.0, OrderType::Limit).with_limit_price(150.0);
+        assert!(BracketOrder::new(buy.clone(), 145.0, 160.0).is_err());
+        assert!(BracketOrder::new(buy, 155.0, 151.0).is_err());
+
+        let sell = Order::new(OrderAction::Sell, 1.0, OrderType::Market);
+        assert!(BracketOrder::new(sell.clone(), 140.0, 160.0).is_ok());
+        assert!(BracketOrder::new(sell, 160.0, 140.0).is_err());
+    }
 }
@@ -1,8 +1,9 @@
 use super::Contract;
 /// Position model
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Position {
     pub account: String,
     pub contract: Contract,
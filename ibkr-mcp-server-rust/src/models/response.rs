@@ -1,8 +1,16 @@
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 /// Response models
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::config::BackendKind;
+use crate::error::{IBKRMCPError, Result};
+use crate::risk::RiskViolation;
+
+/// Outcome of a tool call. It is the `structuredContent` of a `tools/call`
+/// result, and its schema is the tool's `outputSchema`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MCPResponse<T> {
     pub success: bool,
 
@@ -15,33 +23,139 @@ pub struct MCPResponse<T> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 
-    #[serde(default = "chrono::Utc::now")]
+    /// Every limit a risk-rejected order broke
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<RiskViolation>>,
+
+    /// Backend that produced the result, so mock data is never mistaken for
+    /// live data
+    pub backend: BackendKind,
+
     pub timestamp: DateTime<Utc>,
 }
 
 impl<T> MCPResponse<T> {
-    pub fn success(data: T) -> Self {
+    pub fn success(data: T, backend: BackendKind) -> Self {
         Self {
             success: true,
             message: None,
             data: Some(data),
             error: None,
+            violations: None,
+            backend,
             timestamp: Utc::now(),
         }
     }
 
-    pub fn error(error: impl Into<String>) -> Self {
+    pub fn error(error: impl Into<String>, backend: BackendKind) -> Self {
         Self {
             success: false,
             message: None,
             data: None,
             error: Some(error.into()),
+            violations: None,
+            backend,
             timestamp: Utc::now(),
         }
     }
 
+    pub fn from_result(result: Result<T>, backend: BackendKind) -> Self {
+        match result {
+            Ok(data) => Self::success(data, backend),
+            Err(e) => {
+                let mut response = Self::error(e.to_string(), backend);
+                if let IBKRMCPError::RiskRejected(violations) = e {
+                    response.violations = Some(violations);
+                }
+                response
+            }
+        }
+    }
+
     pub fn with_message(mut self, message: impl Into<String>) -> Self {
         self.message = Some(message.into());
         self
     }
 }
+
+/// A `tools/call` result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    pub content: Vec<Content>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+
+    /// The tool ran and failed, as opposed to a protocol error
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+/// A content block of a tool result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    Text { text: String },
+}
+
+impl<T: Serialize> From<MCPResponse<T>> for CallToolResult {
+    /// The response becomes the structured content, and its JSON text the
+    /// content block for clients that only read `content`
+    fn from(response: MCPResponse<T>) -> Self {
+        let (structured, is_error) = match serde_json::to_value(&response) {
+            Ok(structured) => (structured, !response.success),
+            Err(e) => (
+                serde_json::to_value(MCPResponse::<()>::error(
+                    format!("Tool output could not be serialized: {}", e),
+                    response.backend,
+                ))
+                .expect("error responses always serialize"),
+                true,
+            ),
+        };
+        Self {
+            content: vec![Content::Text {
+                text: structured.to_string(),
+            }],
+            structured_content: Some(structured),
+            is_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::RiskRule;
+
+    #[test]
+    fn tool_failures_are_error_results() {
+        let result: CallToolResult = MCPResponse::success(vec![1, 2], BackendKind::Memory).into();
+        assert!(!result.is_error);
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["data"], serde_json::json!([1, 2]));
+        assert_eq!(structured["backend"], "memory");
+        let Content::Text { text } = &result.content[0];
+        assert_eq!(serde_json::from_str::<Value>(text).unwrap(), structured);
+
+        let violation = RiskViolation {
+            rule: RiskRule::MaxOrderQuantity,
+            message: "too big".to_string(),
+            limit: Some(100.0),
+            actual: Some(500.0),
+        };
+        let rejected = Err(IBKRMCPError::RiskRejected(vec![violation]));
+        let result: CallToolResult =
+            MCPResponse::<()>::from_result(rejected, BackendKind::Live).into();
+        assert!(result.is_error);
+        let structured = result.structured_content.as_ref().unwrap();
+        assert_eq!(structured["success"], false);
+        assert_eq!(structured["violations"][0]["rule"], "max_order_quantity");
+        assert!(structured.get("data").is_none());
+
+        let wire = serde_json::to_value(&result).unwrap();
+        assert_eq!(wire["isError"], true);
+        assert_eq!(wire["content"][0]["type"], "text");
+    }
+}
@@ -0,0 +1,53 @@
+/// State files under `state_dir`
+///
+/// Order IDs, client order keys and the trading halt must survive a crash
+/// mid-write, so every state file is replaced whole rather than rewritten
+/// in place.
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Replace the file at `path` with `bytes`, creating its directory. The
+/// bytes go to a temporary file that is then renamed over `path`, so a
+/// crash never leaves a truncated file behind.
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The contents of the file at `path`, or `None` when there is none yet
+pub(crate) fn read(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_replace_the_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("keys.json");
+        assert_eq!(read(&path).unwrap(), None);
+
+        write_atomically(&path, b"first, longer contents").unwrap();
+        write_atomically(&path, b"second").unwrap();
+        assert_eq!(read(&path).unwrap().as_deref(), Some(&b"second"[..]));
+        // Nothing but the state file is left behind
+        let files: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec!["keys.json"]);
+    }
+}
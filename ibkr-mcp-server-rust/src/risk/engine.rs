@@ -0,0 +1,500 @@
+use std::sync::Mutex;
+
+use chrono::{NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RiskConfig,
+    error::{IBKRMCPError, Result},
+    models::{Contract, Order, OrderAction, OrderType},
+};
+
+/// The limit an order broke
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskRule {
+    SymbolNotAllowed,
+    SecTypeNotAllowed,
+    MaxOrderQuantity,
+    MaxOrderNotional,
+    MaxPosition,
+    MaxDailyNotional,
+    PriceCollar,
+    /// A limit needs the last price and none was available
+    MarketPriceUnavailable,
+}
+
+/// One reason an order was rejected
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct RiskViolation {
+    pub rule: RiskRule,
+    pub message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<f64>,
+}
+
+impl RiskViolation {
+    fn new(rule: RiskRule, message: String, limit: Option<f64>, actual: Option<f64>) -> Self {
+        Self {
+            rule,
+            message,
+            limit,
+            actual,
+        }
+    }
+}
+
+/// Market state an order is checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskContext {
+    /// Last trade price of the contract
+    pub last_price: Option<f64>,
+    /// Current signed position in the contract
+    pub position: f64,
+    /// Signed quantity still working in orders on the same side as the
+    /// one checked, which would add to the position when they fill
+    pub working: f64,
+    /// Notional already counted today for the order being replaced, when
+    /// an existing order is modified
+    pub replaced_notional: f64,
+}
+
+/// Notional submitted so far in the current UTC day
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema, PartialEq)]
+pub struct RiskUsage {
+    pub date: NaiveDate,
+    pub daily_notional: f64,
+}
+
+pub struct RiskEngine {
+    config: RiskConfig,
+    usage: Mutex<RiskUsage>,
+}
+
+impl RiskEngine {
+    pub fn new(config: RiskConfig) -> Self {
+        Self {
+            config,
+            usage: Mutex::new(RiskUsage {
+                date: Utc::now().date_naive(),
+                daily_notional: 0.0,
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &RiskConfig {
+        &self.config
+    }
+
+    /// Whether checking `order` needs the contract's last price
+    pub fn needs_last_price(&self, order: &Order) -> bool {
+        let sizes_by_notional =
+            self.config.max_order_notional.is_some() || self.config.max_daily_notional.is_some();
+        (sizes_by_notional && order_price(order).is_none())
+            || (self.config.price_collar_pct.is_some() && !collared_prices(order).is_empty())
+    }
+
+    /// Whether checking an order needs the current position
+    pub fn needs_position(&self) -> bool {
+        self.config.max_position.is_some()
+    }
+
+    /// Check an order against every configured limit. An order that passes
+    /// reserves the notional it adds to the daily total in the same step,
+    /// so concurrent orders cannot both pass against the same headroom;
+    /// the reservation is released unless it is committed once the order
+    /// was accepted.
+    pub fn check(
+        &self,
+        contract: &Contract,
+        order: &Order,
+        ctx: &RiskContext,
+    ) -> Result<RiskReservation<'_>> {
+        let config = &self.config;
+        let mut violations = Vec::new();
+        let symbol = contract.symbol.to_uppercase();
+
+        if !config.allowed_symbols.is_empty()
+            && !config
+                .allowed_symbols
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(&symbol))
+        {
+            violations.push(RiskViolation::new(
+                RiskRule::SymbolNotAllowed,
+                format!("{} is not in the allowed symbols", symbol),
+                None,
+                None,
+            ));
+        }
+        if !config.allowed_sec_types.is_empty()
+            && !config.allowed_sec_types.contains(&contract.sec_type)
+        {
+            violations.push(RiskViolation::new(
+                RiskRule::SecTypeNotAllowed,
+                format!(
+                    "{} is not an allowed security type",
+                    contract.sec_type.as_str()
+                ),
+                None,
+                None,
+            ));
+        }
+
+        let max_quantity = config
+            .symbol_max_quantity
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(&symbol))
+            .map(|(_, &max)| max)
+            .or(config.max_order_quantity);
+        if let Some(max) = max_quantity {
+            if order.total_quantity > max {
+                violations.push(RiskViolation::new(
+                    RiskRule::MaxOrderQuantity,
+                    format!(
+                        "Quantity {} exceeds the {} allowed per order in {}",
+                        order.total_quantity, max, symbol
+                    ),
+                    Some(max),
+                    Some(order.total_quantity),
+                ));
+            }
+        }
+
+        if let Some(max) = config.max_position {
+            let signed = match order.action {
+                OrderAction::Buy => order.total_quantity,
+                OrderAction::Sell => -order.total_quantity,
+            };
+            let committed = ctx.position + ctx.working;
+            let after = committed + signed;
+            // Orders that shrink the position are always allowed
+            if after.abs() > max && after.abs() > committed.abs() {
+                violations.push(RiskViolation::new(
+                    RiskRule::MaxPosition,
+                    format!(
+                        "Position in {} would be {} once this and the working orders fill, beyond the limit of {}",
+                        symbol, after, max
+                    ),
+                    Some(max),
+                    Some(after),
+                ));
+            }
+        }
+
+        // Held until the reservation is made
+        let mut usage = self.usage.lock().unwrap();
+        roll_over(&mut usage);
+        let notional = self.notional(contract, order, ctx.last_price);
+        let added = notional.map_or(0.0, |notional| (notional - ctx.replaced_notional).max(0.0));
+        let sizes_by_notional =
+            config.max_order_notional.is_some() || config.max_daily_notional.is_some();
+        match notional {
+            Some(notional) => {
+                if let Some(max) = config.max_order_notional {
+                    if notional > max {
+                        violations.push(RiskViolation::new(
+                            RiskRule::MaxOrderNotional,
+                            format!(
+                                "Order notional {:.2} exceeds the limit of {:.2}",
+                                notional, max
+                            ),
+                            Some(max),
+                            Some(notional),
+                        ));
+                    }
+                }
+                if let Some(max) = config.max_daily_notional {
+                    let used = usage.daily_notional;
+                    let total = used + added;
+                    if total > max {
+                        violations.push(RiskViolation::new(
+                            RiskRule::MaxDailyNotional,
+                            format!(
+                                "Daily notional would reach {:.2} ({:.2} already used), beyond the limit of {:.2}",
+                                total, used, max
+                            ),
+                            Some(max),
+                            Some(total),
+                        ));
+                    }
+                }
+            }
+            None if sizes_by_notional => violations.push(RiskViolation::new(
+                RiskRule::MarketPriceUnavailable,
+                format!(
+                    "No last price for {} to size the {} order by notional",
+                    symbol,
+                    order.order_type.as_str()
+                ),
+                None,
+                None,
+            )),
+            None => {}
+        }
+
+        let prices = collared_prices(order);
+        if let (Some(pct), false) = (config.price_collar_pct, prices.is_empty()) {
+            match ctx.last_price {
+                Some(last) if last > 0.0 => {
+                    for (name, price) in prices {
+                        let deviation = (price - last).abs() / last * 100.0;
+                        if deviation > pct {
+                            violations.push(RiskViolation::new(
+                                RiskRule::PriceCollar,
+                                format!(
+                                    "{} {} is {:.2}% from the last price {}, beyond the {}% collar",
+                                    name, price, deviation, last, pct
+                                ),
+                                Some(pct),
+                                Some(deviation),
+                            ));
+                        }
+                    }
+                }
+                _ => violations.push(RiskViolation::new(
+                    RiskRule::MarketPriceUnavailable,
+                    format!("No last price for {} to check the price collar", symbol),
+                    None,
+                    None,
+                )),
+            }
+        }
+
+        if !violations.is_empty() {
+            return Err(IBKRMCPError::RiskRejected(violations));
+        }
+        usage.daily_notional += added;
+        Ok(RiskReservation {
+            engine: self,
+            date: usage.date,
+            notional: added,
+            committed: false,
+        })
+    }
+
+    /// Quantity × price × multiplier, priced at the order's own price or,
+    /// for orders without one, the last price
+    pub fn notional(
+        &self,
+        contract: &Contract,
+        order: &Order,
+        last_price: Option<f64>,
+    ) -> Option<f64> {
+        let multiplier = contract.multiplier.unwrap_or(1) as f64;
+        order_price(order)
+            .or(last_price)
+            .map(|price| order.total_quantity * price * multiplier)
+    }
+
+    // Give back a reservation for an order that was never accepted
+    fn release(&self, date: NaiveDate, notional: f64) {
+        let mut usage = self.usage.lock().unwrap();
+        if usage.date == date {
+            usage.daily_notional = (usage.daily_notional - notional).max(0.0);
+        }
+    }
+
+    /// Notional submitted today
+    pub fn usage(&self) -> RiskUsage {
+        let mut usage = self.usage.lock().unwrap();
+        roll_over(&mut usage);
+        *usage
+    }
+}
+
+/// Notional an order that passed the risk checks holds against the daily
+/// limit. Dropping it without `commit` releases the notional.
+#[must_use = "dropping a reservation releases it"]
+pub struct RiskReservation<'a> {
+    engine: &'a RiskEngine,
+    date: NaiveDate,
+    notional: f64,
+    committed: bool,
+}
+
+impl RiskReservation<'_> {
+    /// Notional the order adds to the daily total
+    pub fn notional(&self) -> f64 {
+        self.notional
+    }
+
+    /// Keep the notional counted: the order was accepted
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for RiskReservation<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.engine.release(self.date, self.notional);
+        }
+    }
+}
+
+fn roll_over(usage: &mut RiskUsage) {
+    let today = Utc::now().date_naive();
+    if usage.date != today {
+        usage.date = today;
+        usage.daily_notional = 0.0;
+    }
+}
+
+// The price an order can trade at, when it names one; trailing amounts in
+// `aux_price` are offsets rather than prices
+fn order_price(order: &Order) -> Option<f64> {
+    order.lmt_price.or(match order.order_type {
+        OrderType::Stop | OrderType::StopLimit => order.aux_price,
+        OrderType::Trail | OrderType::TrailLimit => order.trail_stop_price,
+        OrderType::Market | OrderType::Limit => None,
+    })
+}
+
+// Prices the collar keeps near the market: the limit price and the price
+// that triggers a stop or trailing order
+fn collared_prices(order: &Order) -> Vec<(&'static str, f64)> {
+    let trigger = match order.order_type {
+        OrderType::Stop | OrderType::StopLimit => order.aux_price,
+        OrderType::Trail | OrderType::TrailLimit => order.trail_stop_price,
+        OrderType::Market | OrderType::Limit => None,
+    };
+    [("Limit price", order.lmt_price), ("Stop price", trigger)]
+        .into_iter()
+        .filter_map(|(name, price)| Some((name, price?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SecType;
+
+    fn rules(result: Result<RiskReservation<'_>>) -> Vec<RiskRule> {
+        match result {
+            Err(IBKRMCPError::RiskRejected(violations)) => {
+                violations.into_iter().map(|v| v.rule).collect()
+            }
+            other => panic!(
+                "expected a risk rejection, got {:?}",
+                other.map(|reservation| reservation.notional())
+            ),
+        }
+    }
+
+    #[test]
+    fn reports_every_violated_limit() {
+        let engine = RiskEngine::new(RiskConfig {
+            max_order_notional: Some(10_000.0),
+            max_order_quantity: Some(1_000.0),
+            symbol_max_quantity: [("aapl".to_string(), 50.0)].into(),
+            allowed_sec_types: vec![SecType::Stock],
+            price_collar_pct: Some(5.0),
+            ..RiskConfig::default()
+        });
+        let aapl = Contract::new("AAPL", SecType::Stock);
+        let ctx = RiskContext {
+            last_price: Some(150.0),
+            ..RiskContext::default()
+        };
+
+        let order = Order::new(OrderAction::Buy, 40.0, OrderType::Limit).with_limit_price(151.0);
+        assert_eq!(
+            engine.check(&aapl, &order, &ctx).unwrap().notional(),
+            40.0 * 151.0
+        );
+
+        let order = Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(170.0);
+        assert_eq!(
+            rules(engine.check(&aapl, &order, &ctx)),
+            vec![
+                RiskRule::MaxOrderQuantity,
+                RiskRule::MaxOrderNotional,
+                RiskRule::PriceCollar
+            ]
+        );
+        // The collar covers the price that triggers a stop, too
+        let stop = Order::new(OrderAction::Sell, 40.0, OrderType::Stop).with_stop_price(100.0);
+        assert_eq!(
+            rules(engine.check(&aapl, &stop, &ctx)),
+            vec![RiskRule::PriceCollar]
+        );
+        let trail = Order::new(OrderAction::Sell, 40.0, OrderType::Trail)
+            .with_trailing_amount(1.0)
+            .with_trail_stop_price(149.0);
+        assert!(engine.check(&aapl, &trail, &ctx).is_ok());
+
+        let future = Contract::new("ES", SecType::Future);
+        let order = Order::new(OrderAction::Buy, 1.0, OrderType::Market);
+        assert_eq!(
+            rules(engine.check(&future, &order, &RiskContext::default())),
+            vec![
+                RiskRule::SecTypeNotAllowed,
+                RiskRule::MarketPriceUnavailable
+            ]
+        );
+    }
+
+    #[test]
+    fn position_and_daily_limits() {
+        let engine = RiskEngine::new(RiskConfig {
+            max_position: Some(100.0),
+            max_daily_notional: Some(20_000.0),
+            ..RiskConfig::default()
+        });
+        let aapl = Contract::new("AAPL", SecType::Stock);
+        let ctx = RiskContext {
+            last_price: Some(100.0),
+            position: 80.0,
+            ..RiskContext::default()
+        };
+
+        let buy = Order::new(OrderAction::Buy, 30.0, OrderType::Market);
+        assert_eq!(
+            rules(engine.check(&aapl, &buy, &ctx)),
+            vec![RiskRule::MaxPosition]
+        );
+        // Reducing an oversized position is always allowed
+        let sell = Order::new(OrderAction::Sell, 30.0, OrderType::Market);
+        let over = RiskContext {
+            position: 150.0,
+            ..ctx
+        };
+        assert!(engine.check(&aapl, &sell, &over).is_ok());
+        // Working buys count as if they had filled
+        let working = RiskContext {
+            position: 50.0,
+            working: 40.0,
+            ..ctx
+        };
+        let buy = Order::new(OrderAction::Buy, 20.0, OrderType::Market);
+        assert_eq!(
+            rules(engine.check(&aapl, &buy, &working)),
+            vec![RiskRule::MaxPosition]
+        );
+
+        // Reservations count until released, commits stay counted
+        let reserved = engine.check(&aapl, &sell, &ctx).unwrap();
+        assert_eq!(engine.usage().daily_notional, 3_000.0);
+        drop(reserved);
+        assert_eq!(engine.usage().daily_notional, 0.0);
+        engine.check(&aapl, &sell, &ctx).unwrap().commit();
+        assert_eq!(engine.usage().daily_notional, 3_000.0);
+        let big = Order::new(OrderAction::Sell, 180.0, OrderType::Market);
+        assert_eq!(
+            rules(engine.check(&aapl, &big, &ctx)),
+            vec![RiskRule::MaxDailyNotional]
+        );
+        // Amending an order only counts the increase
+        let amended = RiskContext {
+            replaced_notional: 3_000.0,
+            ..ctx
+        };
+        assert!(engine.check(&aapl, &big, &amended).is_ok());
+    }
+}
@@ -0,0 +1,8 @@
+/// Pre-trade risk controls
+///
+/// Every order `IBKRClient` transmits is checked against the limits in
+/// `Settings.risk` first; orders that break any limit are rejected with the
+/// full list of violations.
+pub mod engine;
+
+pub use engine::{RiskContext, RiskEngine, RiskReservation, RiskRule, RiskUsage, RiskViolation};
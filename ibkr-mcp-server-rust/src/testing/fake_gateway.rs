@@ -0,0 +1,781 @@
+/// In-process fake IB Gateway
+///
+/// Listens on a local port, speaks the TWS wire protocol through
+/// [`GatewayCodec`], and answers requests from a mutable script. Tests can
+/// push unsolicited messages, drop every live session, and slow replies
+/// down to exercise reconnects and timeouts.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_util::codec::Framed;
+use tracing::debug;
+
+use crate::{
+    config::{IBKRConfig, ReconnectConfig},
+    ibkr::codec::{
+        tick, CommissionReport, Execution, GatewayCodec, IncomingMessage, OrderState,
+        OrderStatusUpdate, OutgoingMessage, MAX_CLIENT_VERSION,
+    },
+    models::{BarData, Contract, OcaType, Order, OrderAction, OrderPreview},
+};
+
+/// Snapshot quote served for a symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub bid: f64,
+    pub ask: f64,
+    pub last: f64,
+    pub volume: f64,
+}
+
+impl Quote {
+    pub fn new(bid: f64, ask: f64, last: f64, volume: f64) -> Self {
+        Self {
+            bid,
+            ask,
+            last,
+            volume,
+        }
+    }
+}
+
+/// How the gateway reacts to `placeOrder`
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderBehavior {
+    /// Acknowledge and leave the order working
+    Accept,
+    /// Acknowledge, then fill completely at `price`
+    Fill { price: f64, commission: f64 },
+    /// Reject through the error channel
+    Reject { code: i32, message: String },
+}
+
+/// An order the gateway considers working
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeOrder {
+    pub order_id: i32,
+    pub contract: Contract,
+    pub order: Order,
+    pub status: String,
+}
+
+/// Everything the gateway answers with; mutate through [`FakeGateway`]
+#[derive(Debug, Clone)]
+pub struct GatewayScript {
+    pub server_version: i32,
+    pub accounts: Vec<String>,
+    pub next_valid_id: i32,
+    /// (account, tag, value, currency)
+    pub account_values: Vec<(String, String, String, String)>,
+    /// (account, contract, position, avg cost)
+    pub positions: Vec<(String, Contract, f64, f64)>,
+    pub quotes: HashMap<String, Quote>,
+    pub historical_bars: Vec<BarData>,
+    /// (code, message) sent through the error channel ahead of the data
+    /// for every market data and historical data request
+    pub market_data_notices: Vec<(i32, String)>,
+    pub order_behavior: OrderBehavior,
+    /// Answer to every what-if `placeOrder`
+    pub what_if_preview: OrderPreview,
+    pub reply_delay: Duration,
+}
+
+impl Default for GatewayScript {
+    fn default() -> Self {
+        Self {
+            server_version: MAX_CLIENT_VERSION,
+            accounts: vec!["DU123456".to_string()],
+            next_valid_id: 1,
+            account_values: Vec::new(),
+            positions: Vec::new(),
+            quotes: HashMap::new(),
+            historical_bars: Vec::new(),
+            market_data_notices: Vec::new(),
+            order_behavior: OrderBehavior::Accept,
+            what_if_preview: OrderPreview::default(),
+            reply_delay: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Control {
+    Disconnect,
+    Push(Box<IncomingMessage>),
+}
+
+#[derive(Default)]
+struct GatewayState {
+    script: GatewayScript,
+    open_orders: Vec<FakeOrder>,
+    /// Filled and cancelled orders, reported by `reqCompletedOrders`
+    completed_orders: Vec<FakeOrder>,
+    /// (order ID, contract, execution) of every fill, for `reqExecutions`
+    executions: Vec<(i32, Contract, Execution)>,
+    received: Vec<OutgoingMessage>,
+    connections: usize,
+    exec_seq: u32,
+    /// Highest order ID placed so far; TWS never accepts a new order at or below it
+    max_order_id: i32,
+}
+
+impl GatewayState {
+    /// What TWS reports as `nextValidId`: past every ID already used
+    fn next_valid_id(&self) -> i32 {
+        self.script.next_valid_id.max(self.max_order_id + 1)
+    }
+}
+
+/// Scriptable stand-in for TWS / IB Gateway
+pub struct FakeGateway {
+    addr: SocketAddr,
+    state: Arc<Mutex<GatewayState>>,
+    control: broadcast::Sender<Control>,
+    accept_loop: JoinHandle<()>,
+}
+
+impl FakeGateway {
+    /// Start a gateway with the default script on an ephemeral port
+    pub async fn start() -> Self {
+        Self::with_script(GatewayScript::default()).await
+    }
+
+    pub async fn with_script(script: GatewayScript) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind fake gateway");
+        let addr = listener.local_addr().expect("fake gateway address");
+
+        let state = Arc::new(Mutex::new(GatewayState {
+            script,
+            ..Default::default()
+        }));
+        let (control, _) = broadcast::channel(256);
+
+        let accept_loop = tokio::spawn(accept_loop(listener, Arc::clone(&state), control.clone()));
+
+        Self {
+            addr,
+            state,
+            control,
+            accept_loop,
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Client configuration pointing at this gateway
+    pub fn config(&self) -> IBKRConfig {
+        IBKRConfig {
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            timeout: 5,
+            // Tests opt into persistence with their own directory
+            state_dir: None,
+            // Reconnect quickly so tests never wait on production backoff
+            reconnect: ReconnectConfig {
+                initial_backoff_ms: 20,
+                max_backoff_ms: 200,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Edit the script; changes apply to subsequent requests
+    pub fn script(&self, f: impl FnOnce(&mut GatewayScript)) {
+        f(&mut self.state.lock().unwrap().script);
+    }
+
+    pub fn add_account_value(&self, account: &str, tag: &str, value: &str, currency: &str) {
+        self.script(|s| {
+            s.account_values.push((
+                account.to_string(),
+                tag.to_string(),
+                value.to_string(),
+                currency.to_string(),
+            ))
+        });
+    }
+
+    pub fn add_position(&self, account: &str, contract: Contract, position: f64, avg_cost: f64) {
+        self.script(|s| {
+            s.positions
+                .push((account.to_string(), contract, position, avg_cost))
+        });
+    }
+
+    pub fn set_quote(&self, symbol: &str, quote: Quote) {
+        self.script(|s| {
+            s.quotes.insert(symbol.to_string(), quote);
+        });
+    }
+
+    pub fn set_historical_bars(&self, bars: Vec<BarData>) {
+        self.script(|s| s.historical_bars = bars);
+    }
+
+    pub fn add_market_data_notice(&self, code: i32, message: &str) {
+        self.script(|s| s.market_data_notices.push((code, message.to_string())));
+    }
+
+    pub fn set_order_behavior(&self, behavior: OrderBehavior) {
+        self.script(|s| s.order_behavior = behavior);
+    }
+
+    pub fn set_what_if_preview(&self, preview: OrderPreview) {
+        self.script(|s| s.what_if_preview = preview);
+    }
+
+    /// Delay every reply (not the handshake) by `delay`
+    pub fn set_reply_delay(&self, delay: Duration) {
+        self.script(|s| s.reply_delay = delay);
+    }
+
+    /// Send an unsolicited message to every connected client
+    pub fn push(&self, message: IncomingMessage) {
+        let _ = self.control.send(Control::Push(Box::new(message)));
+    }
+
+    /// Drop every live session, as if the gateway restarted
+    pub fn disconnect_all(&self) {
+        let _ = self.control.send(Control::Disconnect);
+    }
+
+    /// Messages received from clients since start, handshakes included
+    pub fn received(&self) -> Vec<OutgoingMessage> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Orders currently working at the gateway
+    pub fn open_orders(&self) -> Vec<FakeOrder> {
+        self.state.lock().unwrap().open_orders.clone()
+    }
+
+    /// Fill a working order completely at `price`, as if the market traded
+    /// through it; other members of its OCA group are cancelled or reduced.
+    /// Returns false if the order is not working.
+    pub fn fill_order(&self, order_id: i32, price: f64, commission: f64) -> bool {
+        let replies = {
+            let mut state = self.state.lock().unwrap();
+            let Some(index) = state
+                .open_orders
+                .iter()
+                .position(|o| o.order_id == order_id)
+            else {
+                return false;
+            };
+            let order = state.open_orders.remove(index);
+            fill(&mut state, &order, price, commission)
+        };
+        for reply in replies {
+            self.push(reply);
+        }
+        true
+    }
+
+    /// Number of sessions that completed `startApi`
+    pub fn connection_count(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    /// Wait until `predicate` holds for the received messages
+    pub async fn wait_for(
+        &self,
+        timeout: Duration,
+        predicate: impl Fn(&[OutgoingMessage]) -> bool,
+    ) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if predicate(&self.state.lock().unwrap().received) {
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for FakeGateway {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+        let _ = self.control.send(Control::Disconnect);
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    state: Arc<Mutex<GatewayState>>,
+    control: broadcast::Sender<Control>,
+) {
+    while let Ok((socket, peer)) = listener.accept().await {
+        debug!("Fake gateway accepted {}", peer);
+        tokio::spawn(session(socket, Arc::clone(&state), control.subscribe()));
+    }
+}
+
+async fn session(
+    socket: TcpStream,
+    state: Arc<Mutex<GatewayState>>,
+    mut control: broadcast::Receiver<Control>,
+) {
+    let mut framed = Framed::new(socket, GatewayCodec::new());
+
+    loop {
+        tokio::select! {
+            request = framed.next() => {
+                let Some(Ok(request)) = request else { break };
+                let (delay, replies) = {
+                    let mut state = state.lock().unwrap();
+                    state.received.push(request.clone());
+                    let delay = match request {
+                        OutgoingMessage::Handshake { .. } => Duration::ZERO,
+                        _ => state.script.reply_delay,
+                    };
+                    (delay, respond(&mut state, request))
+                };
+
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                for reply in replies {
+                    if framed.send(reply).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            command = control.recv() => match command {
+                Ok(Control::Push(message)) => {
+                    if framed.send(*message).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(Control::Disconnect) | Err(broadcast::error::RecvError::Closed) => break,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+            },
+        }
+    }
+}
+
+fn respond(state: &mut GatewayState, request: OutgoingMessage) -> Vec<IncomingMessage> {
+    let script = &state.script;
+
+    match request {
+        OutgoingMessage::Handshake { .. } => vec![IncomingMessage::ServerVersion {
+            version: script.server_version,
+            connection_time: chrono::Utc::now().format("%Y%m%d %H:%M:%S UTC").to_string(),
+        }],
+        OutgoingMessage::StartApi { .. } => {
+            state.connections += 1;
+            vec![
+                IncomingMessage::ManagedAccounts {
+                    accounts: script.accounts.clone(),
+                },
+                IncomingMessage::NextValidId {
+                    order_id: state.next_valid_id(),
+                },
+                IncomingMessage::Error {
+                    id: -1,
+                    code: 2104,
+                    message: "Market data farm connection is OK:usfarm".into(),
+                },
+            ]
+        }
+        OutgoingMessage::ReqIds { .. } => vec![IncomingMessage::NextValidId {
+            order_id: state.next_valid_id(),
+        }],
+        OutgoingMessage::ReqCurrentTime => vec![IncomingMessage::CurrentTime {
+            time: chrono::Utc::now().timestamp(),
+        }],
+        OutgoingMessage::ReqAccountSummary { req_id, .. } => {
+            let mut replies: Vec<_> = script
+                .account_values
+                .iter()
+                .map(
+                    |(account, tag, value, currency)| IncomingMessage::AccountSummary {
+                        req_id,
+                        account: account.clone(),
+                        tag: tag.clone(),
+                        value: value.clone(),
+                        currency: currency.clone(),
+                    },
+                )
+                .collect();
+            replies.push(IncomingMessage::AccountSummaryEnd { req_id });
+            replies
+        }
+        OutgoingMessage::ReqPositions => {
+            let mut replies: Vec<_> = script
+                .positions
+                .iter()
+                .map(
+                    |(account, contract, position, avg_cost)| IncomingMessage::Position {
+                        account: account.clone(),
+                        contract: contract.clone(),
+                        position: *position,
+                        avg_cost: *avg_cost,
+                    },
+                )
+                .collect();
+            replies.push(IncomingMessage::PositionEnd);
+            replies
+        }
+        OutgoingMessage::ReqMktData {
+            req_id,
+            contract,
+            snapshot,
+            ..
+        } => match script.quotes.get(&contract.symbol) {
+            Some(quote) => {
+                let mut replies = market_data_notices(script, req_id);
+                replies.extend([
+                    tick_price(req_id, tick::BID, quote.bid),
+                    tick_price(req_id, tick::ASK, quote.ask),
+                    tick_price(req_id, tick::LAST, quote.last),
+                    IncomingMessage::TickSize {
+                        req_id,
+                        tick_type: tick::VOLUME,
+                        size: quote.volume,
+                    },
+                ]);
+                if snapshot {
+                    replies.push(IncomingMessage::TickSnapshotEnd { req_id });
+                }
+                replies
+            }
+            None => vec![IncomingMessage::Error {
+                id: req_id,
+                code: 200,
+                message: "No security definition has been found for the request".into(),
+            }],
+        },
+        OutgoingMessage::ReqHistoricalData { req_id, .. } => {
+            let mut replies = market_data_notices(script, req_id);
+            replies.push(IncomingMessage::HistoricalData {
+                req_id,
+                start: String::new(),
+                end: String::new(),
+                bars: script.historical_bars.clone(),
+            });
+            replies
+        }
+        OutgoingMessage::PlaceOrder {
+            order_id,
+            contract,
+            order,
+        } => place_order(state, order_id, contract, *order),
+        OutgoingMessage::CancelOrder { order_id } => {
+            match state
+                .open_orders
+                .iter()
+                .position(|o| o.order_id == order_id)
+            {
+                Some(index) => {
+                    // Cancelling a parent cancels its attached orders too
+                    let cancelled = state.open_orders.remove(index);
+                    let (children, working): (Vec<_>, Vec<_>) =
+                        std::mem::take(&mut state.open_orders)
+                            .into_iter()
+                            .partition(|o| o.order.parent_id == Some(order_id));
+                    state.open_orders = working;
+                    std::iter::once(cancelled)
+                        .chain(children)
+                        .map(|o| complete(state, o, "Cancelled"))
+                        .collect()
+                }
+                None => vec![IncomingMessage::Error {
+                    id: order_id,
+                    code: 10147,
+                    message: format!(
+                        "OrderId {} that needs to be cancelled is not found.",
+                        order_id
+                    ),
+                }],
+            }
+        }
+        OutgoingMessage::ReqGlobalCancel => std::mem::take(&mut state.open_orders)
+            .into_iter()
+            .map(|o| complete(state, o, "Cancelled"))
+            .collect(),
+        OutgoingMessage::ReqOpenOrders | OutgoingMessage::ReqAllOpenOrders => {
+            let mut replies: Vec<_> = state
+                .open_orders
+                .iter()
+                .flat_map(|o| [open_order(o), order_status(o, &o.status, 0.0, 0.0)])
+                .collect();
+            replies.push(IncomingMessage::OpenOrderEnd);
+            replies
+        }
+        OutgoingMessage::ReqExecutions { req_id } => {
+            let mut replies: Vec<_> = state
+                .executions
+                .iter()
+                .map(
+                    |(order_id, contract, execution)| IncomingMessage::ExecutionData {
+                        req_id,
+                        order_id: *order_id,
+                        contract: contract.clone(),
+                        execution: execution.clone(),
+                    },
+                )
+                .collect();
+            replies.push(IncomingMessage::ExecutionDataEnd { req_id });
+            replies
+        }
+        OutgoingMessage::ReqCompletedOrders { .. } => {
+            let mut replies: Vec<_> = state
+                .completed_orders
+                .iter()
+                .map(|o| IncomingMessage::CompletedOrder {
+                    contract: o.contract.clone(),
+                    order: o.order.clone(),
+                    perm_id: o.order_id,
+                    status: o.status.clone(),
+                })
+                .collect();
+            replies.push(IncomingMessage::CompletedOrdersEnd);
+            replies
+        }
+        OutgoingMessage::CancelMktData { .. }
+        | OutgoingMessage::CancelAccountSummary { .. }
+        | OutgoingMessage::CancelPositions => Vec::new(),
+    }
+}
+
+fn place_order(
+    state: &mut GatewayState,
+    order_id: i32,
+    contract: Contract,
+    order: Order,
+) -> Vec<IncomingMessage> {
+    let is_modification = state.open_orders.iter().any(|o| o.order_id == order_id);
+    if !is_modification && order_id < state.next_valid_id() {
+        return vec![IncomingMessage::Error {
+            id: order_id,
+            code: 103,
+            message: "Duplicate order id".into(),
+        }];
+    }
+    state.max_order_id = state.max_order_id.max(order_id);
+
+    if let OrderBehavior::Reject { code, message } = &state.script.order_behavior {
+        return vec![IncomingMessage::Error {
+            id: order_id,
+            code: *code,
+            message: message.clone(),
+        }];
+    }
+
+    // What-if orders are only priced, never worked
+    if order.what_if {
+        return vec![IncomingMessage::OpenOrder {
+            order_id,
+            contract,
+            order,
+            state: OrderState {
+                status: "PreSubmitted".to_string(),
+                preview: Box::new(state.script.what_if_preview.clone()),
+            },
+        }];
+    }
+
+    // Attached orders wait for their parent to fill
+    let working_status = if order.parent_id.is_some() {
+        "PreSubmitted"
+    } else {
+        "Submitted"
+    };
+    let transmit = order.transmit;
+    let parent_id = order.parent_id;
+    let mut fake = FakeOrder {
+        order_id,
+        contract,
+        order,
+        status: working_status.to_string(),
+    };
+    state.open_orders.retain(|o| o.order_id != order_id);
+
+    // Untransmitted orders are held silently until a later order in the
+    // same group is transmitted
+    if !transmit {
+        fake.status = "PendingSubmit".to_string();
+        state.open_orders.push(fake);
+        return Vec::new();
+    }
+
+    let mut replies = Vec::new();
+    if let Some(parent_id) = parent_id {
+        for held in state.open_orders.iter_mut().filter(|o| {
+            o.status == "PendingSubmit"
+                && (o.order_id == parent_id || o.order.parent_id == Some(parent_id))
+        }) {
+            held.status = if held.order.parent_id.is_some() {
+                "PreSubmitted".to_string()
+            } else {
+                "Submitted".to_string()
+            };
+            replies.push(open_order(held));
+            replies.push(order_status(held, &held.status, 0.0, 0.0));
+        }
+    }
+    replies.push(open_order(&fake));
+    replies.push(order_status(&fake, working_status, 0.0, 0.0));
+
+    match state.script.order_behavior.clone() {
+        OrderBehavior::Fill { price, commission } if parent_id.is_none() => {
+            replies.extend(fill(state, &fake, price, commission));
+        }
+        _ => state.open_orders.push(fake),
+    }
+
+    replies
+}
+
+/// Execute `order` completely and settle the rest of its OCA group
+fn fill(
+    state: &mut GatewayState,
+    order: &FakeOrder,
+    price: f64,
+    commission: f64,
+) -> Vec<IncomingMessage> {
+    state.exec_seq += 1;
+    let exec_id = format!("0000e0d5.{:08x}.01.01", state.exec_seq);
+    let quantity = order.order.total_quantity;
+    let execution = Execution {
+        exec_id: exec_id.clone(),
+        time: chrono::Utc::now().format("%Y%m%d %H:%M:%S").to_string(),
+        account: order
+            .order
+            .account
+            .clone()
+            .or_else(|| state.script.accounts.first().cloned())
+            .unwrap_or_default(),
+        exchange: "ISLAND".into(),
+        side: match order.order.action {
+            OrderAction::Buy => "BOT".into(),
+            OrderAction::Sell => "SLD".into(),
+        },
+        shares: quantity,
+        price,
+        perm_id: order.order_id,
+        client_id: 0,
+        cum_qty: quantity,
+        avg_price: price,
+        order_ref: order.order.order_ref.clone().unwrap_or_default(),
+    };
+    state
+        .executions
+        .push((order.order_id, order.contract.clone(), execution.clone()));
+    state.completed_orders.push(FakeOrder {
+        status: "Filled".to_string(),
+        ..order.clone()
+    });
+    let mut replies = vec![
+        IncomingMessage::ExecutionData {
+            req_id: -1,
+            order_id: order.order_id,
+            contract: order.contract.clone(),
+            execution,
+        },
+        IncomingMessage::CommissionReport(CommissionReport {
+            exec_id,
+            commission,
+            currency: order.contract.currency.clone(),
+            realized_pnl: None,
+        }),
+        order_status(order, "Filled", quantity, price),
+    ];
+
+    let Some(group) = order.order.oca_group.as_deref() else {
+        return replies;
+    };
+    let reduce = matches!(
+        order.order.oca_type,
+        Some(OcaType::ReduceWithBlock | OcaType::ReduceNonBlock)
+    );
+    let mut working = Vec::new();
+    for mut sibling in std::mem::take(&mut state.open_orders) {
+        if sibling.order.oca_group.as_deref() != Some(group) {
+            working.push(sibling);
+            continue;
+        }
+        if reduce && sibling.order.total_quantity > quantity {
+            sibling.order.total_quantity -= quantity;
+            replies.push(open_order(&sibling));
+            replies.push(order_status(&sibling, &sibling.status, 0.0, 0.0));
+            working.push(sibling);
+        } else {
+            replies.push(complete(state, sibling, "Cancelled"));
+        }
+    }
+    state.open_orders = working;
+    replies
+}
+
+// Move an order to the completed orders, returning its final status
+fn complete(state: &mut GatewayState, mut order: FakeOrder, status: &str) -> IncomingMessage {
+    let update = order_status(&order, status, 0.0, 0.0);
+    order.status = status.to_string();
+    state.completed_orders.push(order);
+    update
+}
+
+fn open_order(order: &FakeOrder) -> IncomingMessage {
+    IncomingMessage::OpenOrder {
+        order_id: order.order_id,
+        contract: order.contract.clone(),
+        order: order.order.clone(),
+        state: OrderState {
+            status: order.status.clone(),
+            ..Default::default()
+        },
+    }
+}
+
+fn tick_price(req_id: i32, tick_type: i32, price: f64) -> IncomingMessage {
+    IncomingMessage::TickPrice {
+        req_id,
+        tick_type,
+        price,
+        size: 0.0,
+        attr_mask: 0,
+    }
+}
+
+fn market_data_notices(script: &GatewayScript, req_id: i32) -> Vec<IncomingMessage> {
+    script
+        .market_data_notices
+        .iter()
+        .map(|(code, message)| IncomingMessage::Error {
+            id: req_id,
+            code: *code,
+            message: message.clone(),
+        })
+        .collect()
+}
+
+fn order_status(order: &FakeOrder, status: &str, filled: f64, avg_price: f64) -> IncomingMessage {
+    IncomingMessage::OrderStatus(OrderStatusUpdate {
+        order_id: order.order_id,
+        status: status.to_string(),
+        filled,
+        remaining: order.order.total_quantity - filled,
+        avg_fill_price: avg_price,
+        perm_id: order.order_id,
+        parent_id: 0,
+        last_fill_price: avg_price,
+        client_id: 0,
+        why_held: String::new(),
+        mkt_cap_price: 0.0,
+    })
+}
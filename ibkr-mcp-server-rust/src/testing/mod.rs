@@ -0,0 +1,4 @@
+/// Test utilities, enabled with the `test-util` feature
+pub mod fake_gateway;
+
+pub use fake_gateway::{FakeGateway, FakeOrder, GatewayScript, OrderBehavior, Quote};
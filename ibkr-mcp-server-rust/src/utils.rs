@@ -2,7 +2,7 @@
 use crate::error::Result;
 
 /// Validate configuration parameters
-pub fn validate_config(config: &crate::config::Settings) -> Result<()> {
+pub fn validate_config(_config: &crate::config::Settings) -> Result<()> {
     // Add validation logic
     Ok(())
 }
@@ -1,4 +1,98 @@
-use ibkr_mcp_server::{IBKRClient, Result, Settings};
+use std::time::Duration;
+
+use futures::future::{BoxFuture, FutureExt};
+use ibkr_mcp_server::config::{BackendKind, RiskConfig};
+use ibkr_mcp_server::ibkr::codec::{IncomingMessage, OutgoingMessage};
+use ibkr_mcp_server::ibkr::connection::{Connection, ConnectionManager, ConnectionState};
+use ibkr_mcp_server::ibkr::{OrderFilter, TrackedOrder};
+use ibkr_mcp_server::models::{
+    AdaptivePriority, AlgoParams, AlgoStrategy, BracketOrder, Contract, OcaType, Order,
+    OrderAction, OrderChanges, OrderPreview, OrderStatus, OrderType, SecType, TimeInForce,
+};
+use ibkr_mcp_server::risk::RiskRule;
+use ibkr_mcp_server::testing::{FakeGateway, OrderBehavior, Quote};
+use ibkr_mcp_server::{IBKRClient, IBKRMCPError, MCPServer, Result, Settings};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+/// Collect messages until `done` matches one, failing after a timeout
+async fn collect_until(
+    rx: &mut broadcast::Receiver<IncomingMessage>,
+    done: impl Fn(&IncomingMessage) -> bool,
+) -> Vec<IncomingMessage> {
+    let mut messages = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let message = rx.recv().await.unwrap();
+            let finished = done(&message);
+            messages.push(message);
+            if finished {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for gateway reply");
+    messages
+}
+
+/// Serve `settings` over HTTP on a free port. Returns the address and a
+/// function calling a tool through `/mcp/tools`.
+async fn spawn_server(
+    settings: Settings,
+) -> (
+    SocketAddr,
+    impl Fn(&'static str, serde_json::Value) -> BoxFuture<'static, serde_json::Value>,
+) {
+    let app = MCPServer::new(settings).router().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let http = reqwest::Client::new();
+    let call = move |tool: &'static str, parameters: serde_json::Value| {
+        let request = http
+            .post(format!("http://{}/mcp/tools", addr))
+            .json(&serde_json::json!({ "tool": tool, "parameters": parameters }));
+        async move {
+            let response = request.send().await.unwrap();
+            response.json().await.unwrap()
+        }
+        .boxed()
+    };
+    (addr, call)
+}
+
+/// Gateway scripted with the paper account used throughout these tests
+async fn scripted_gateway() -> FakeGateway {
+    let gateway = FakeGateway::start().await;
+    gateway.script(|s| s.next_valid_id = 1000);
+    gateway.add_account_value("DU123456", "NetLiquidation", "150000.00", "USD");
+    gateway.add_account_value("DU123456", "TotalCashValue", "50000.00", "USD");
+    gateway.add_account_value("DU123456", "GrossPositionValue", "100000.00", "USD");
+    gateway.add_position(
+        "DU123456",
+        Contract::new("AAPL", SecType::Stock),
+        100.0,
+        150.25,
+    );
+    gateway.add_position(
+        "DU123456",
+        Contract::new("MSFT", SecType::Stock),
+        50.0,
+        350.0,
+    );
+    gateway.set_quote("AAPL", Quote::new(175.0, 175.1, 175.05, 1_000_000.0));
+    gateway
+}
+
+fn memory_client() -> IBKRClient {
+    let mut config = Settings::new().unwrap().ibkr;
+    config.backend = BackendKind::Memory;
+    IBKRClient::new(config)
+}
 
 #[tokio::test]
 async fn test_settings_loading() {
@@ -21,20 +115,27 @@ async fn test_ibkr_client_creation() {
 
 #[tokio::test]
 async fn test_ibkr_client_connect() -> Result<()> {
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    let gateway = FakeGateway::start().await;
+    let client = IBKRClient::new(gateway.config());
 
-    // Test mock connection
     client.connect().await?;
     assert!(client.is_connected().await);
+    assert_eq!(
+        client.managed_accounts().await,
+        vec!["DU123456".to_string()]
+    );
+    assert_eq!(client.next_valid_id().await, Some(1));
+
+    client.disconnect().await?;
+    assert!(!client.is_connected().await);
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_get_account_summary() -> Result<()> {
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
     client.connect().await?;
     let summary = client.get_account_summary().await?;
@@ -47,8 +148,8 @@ async fn test_get_account_summary() -> Result<()> {
 
 #[tokio::test]
 async fn test_get_positions() -> Result<()> {
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
     client.connect().await?;
     let positions = client.get_positions().await?;
@@ -61,11 +162,53 @@ async fn test_get_positions() -> Result<()> {
 }
 
 #[tokio::test]
-async fn test_place_order() -> Result<()> {
-    use ibkr_mcp_server::models::{Contract, Order, OrderAction, OrderType, SecType};
+async fn test_concurrent_position_requests() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    client.connect().await?;
+    // reqPositions has no request ID: every caller sees every reply, so a
+    // second request may only go out once the first one has finished
+    gateway.set_reply_delay(Duration::from_millis(100));
+    let (first, second) = tokio::join!(client.get_positions(), client.get_positions());
+    let (first, second) = (first?, second?);
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(
+        serde_json::to_value(&first).unwrap(),
+        serde_json::to_value(&second).unwrap()
+    );
+    let positions_requests = |received: &[OutgoingMessage]| -> Vec<OutgoingMessage> {
+        received
+            .iter()
+            .filter(|m| {
+                matches!(
+                    m,
+                    OutgoingMessage::ReqPositions | OutgoingMessage::CancelPositions
+                )
+            })
+            .cloned()
+            .collect()
+    };
+    let sent = |r: &[OutgoingMessage]| positions_requests(r).len() == 4;
+    assert!(gateway.wait_for(Duration::from_secs(1), sent).await);
+    assert!(matches!(
+        positions_requests(&gateway.received())[..],
+        [
+            OutgoingMessage::ReqPositions,
+            OutgoingMessage::CancelPositions,
+            OutgoingMessage::ReqPositions,
+            OutgoingMessage::CancelPositions
+        ]
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_place_order() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
     client.connect().await?;
 
@@ -80,31 +223,67 @@ async fn test_place_order() -> Result<()> {
 
 #[tokio::test]
 async fn test_cancel_order() -> Result<()> {
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
     client.connect().await?;
 
-    let result = client.cancel_order(1001).await?;
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(170.0);
+    let order_id = client.place_order(&contract, &order).await?;
+    assert_eq!(gateway.open_orders().len(), 1);
+
+    let result = client.cancel_order(order_id).await?;
     assert!(result);
+    assert!(gateway.open_orders().is_empty());
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_get_market_data() -> Result<()> {
-    use ibkr_mcp_server::models::{Contract, SecType};
-
-    let settings = Settings::new().unwrap();
-    let client = IBKRClient::new(settings.ibkr);
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
 
     client.connect().await?;
 
     let contract = Contract::new("AAPL", SecType::Stock);
     let data = client.get_market_data(&contract).await?;
 
-    assert!(data["symbol"].as_str().unwrap() == "AAPL");
-    assert!(data["last"].as_f64().is_some());
+    assert_eq!(data.symbol, "AAPL");
+    assert!(data.last.is_some());
+    assert_eq!(data.bid, Some(175.0));
+
+    let unknown = client
+        .get_market_data(&Contract::new("NOPE", SecType::Stock))
+        .await;
+    assert!(matches!(
+        unknown,
+        Err(ibkr_mcp_server::IBKRMCPError::MarketData(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_market_data_notices_are_not_errors() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    gateway.add_market_data_notice(
+        10167,
+        "Requested market data is not subscribed. Displaying delayed market data.",
+    );
+    gateway.add_market_data_notice(2104, "Market data farm connection is OK:usfarm");
+    let client = IBKRClient::new(gateway.config());
+
+    client.connect().await?;
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let data = client.get_market_data(&contract).await?;
+    assert_eq!(data.bid, Some(175.0));
+
+    let bars = client
+        .get_historical_data(&contract, "1 D", "1 min", "TRADES")
+        .await?;
+    assert!(bars.is_empty());
 
     Ok(())
 }
@@ -126,3 +305,2148 @@ async fn test_not_connected_error() {
         panic!("Expected NotConnected error");
     }
 }
+
+#[tokio::test]
+async fn test_connect_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut config = Settings::new().unwrap().ibkr;
+    config.port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let client = IBKRClient::new(config);
+    assert!(client.connect().await.is_err());
+    assert!(!client.is_connected().await);
+}
+
+#[tokio::test]
+async fn test_place_order_rejected() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    gateway.set_order_behavior(OrderBehavior::Reject {
+        code: 201,
+        message: "Order rejected - reason: insufficient margin".into(),
+    });
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 100.0, OrderType::Market);
+    assert!(matches!(
+        client.place_order(&contract, &order).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_historical_data() -> Result<()> {
+    use chrono::TimeZone;
+    use ibkr_mcp_server::models::BarData;
+
+    let gateway = scripted_gateway().await;
+    let bar = BarData {
+        date: chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        open: 1.0,
+        high: 2.0,
+        low: 0.5,
+        close: 1.5,
+        volume: 1000,
+        wap: Some(1.2),
+        count: Some(10),
+    };
+    gateway.set_historical_bars(vec![bar.clone()]);
+
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+    let bars = client
+        .get_historical_data(
+            &Contract::new("AAPL", SecType::Stock),
+            "1 D",
+            "1 min",
+            "TRADES",
+        )
+        .await?;
+    assert_eq!(bars, vec![bar]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_backend_selection() {
+    let settings = Settings::new().unwrap();
+    assert_eq!(settings.ibkr.backend, BackendKind::Live);
+    assert_eq!(
+        IBKRClient::new(settings.ibkr).backend_kind(),
+        BackendKind::Live
+    );
+    assert_eq!(memory_client().backend_kind(), BackendKind::Memory);
+}
+
+#[tokio::test]
+async fn test_memory_backend() -> Result<()> {
+    let client = memory_client();
+    assert!(matches!(
+        client.get_positions().await,
+        Err(ibkr_mcp_server::IBKRMCPError::NotConnected)
+    ));
+
+    client.connect().await?;
+    assert_eq!(client.get_account_summary().await?.len(), 3);
+    assert_eq!(client.get_positions().await?[0].contract.symbol, "AAPL");
+
+    // Quotes and bars are deterministic
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let first = client.get_market_data(&contract).await?;
+    let second = client.get_market_data(&contract).await?;
+    assert_eq!(first.last, second.last);
+    assert_eq!(first.last, Some(175.0));
+    let bars = client
+        .get_historical_data(&contract, "1 D", "1 min", "TRADES")
+        .await?;
+    assert_eq!(bars.len(), 10);
+    assert_eq!(bars[0].open, 175.0);
+
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let order_id = client.place_order(&contract, &order).await?;
+    assert!(order_id >= 1000);
+    assert_eq!(client.get_open_orders().await?.len(), 1);
+    assert!(client.cancel_order(order_id).await?);
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_gateway_handshake() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    gateway.script(|s| {
+        s.accounts = vec!["DU111".into(), "DU222".into()];
+        s.next_valid_id = 500;
+    });
+
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    assert_eq!(gateway.connection_count(), 1);
+    assert_eq!(client.next_valid_id().await, Some(500));
+    assert_eq!(
+        client.managed_accounts().await,
+        vec!["DU111".to_string(), "DU222".to_string()]
+    );
+    assert!(matches!(
+        gateway.received()[1],
+        OutgoingMessage::StartApi { client_id: 1, .. }
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_gateway_scripted_replies() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    gateway.add_account_value("DU123456", "NetLiquidation", "150000.00", "USD");
+    gateway.add_position(
+        "DU123456",
+        Contract::new("AAPL", SecType::Stock),
+        100.0,
+        150.25,
+    );
+    gateway.set_quote("AAPL", Quote::new(175.0, 175.1, 175.05, 1_000_000.0));
+
+    let connection = Connection::connect(&gateway.config()).await?;
+    let mut rx = connection.subscribe();
+
+    connection
+        .send(OutgoingMessage::ReqAccountSummary {
+            req_id: 1,
+            group: "All".into(),
+            tags: "NetLiquidation".into(),
+        })
+        .await?;
+    let replies = collect_until(&mut rx, |m| {
+        matches!(m, IncomingMessage::AccountSummaryEnd { req_id: 1 })
+    })
+    .await;
+    assert!(matches!(
+        &replies[0],
+        IncomingMessage::AccountSummary { tag, value, .. } if tag == "NetLiquidation" && value == "150000.00"
+    ));
+
+    connection.send(OutgoingMessage::ReqPositions).await?;
+    let replies = collect_until(&mut rx, |m| matches!(m, IncomingMessage::PositionEnd)).await;
+    assert!(matches!(
+        &replies[0],
+        IncomingMessage::Position { contract, position, .. } if contract.symbol == "AAPL" && *position == 100.0
+    ));
+
+    connection
+        .send(OutgoingMessage::ReqMktData {
+            req_id: 2,
+            contract: Contract::new("AAPL", SecType::Stock),
+            generic_ticks: String::new(),
+            snapshot: true,
+        })
+        .await?;
+    let replies = collect_until(&mut rx, |m| {
+        matches!(m, IncomingMessage::TickSnapshotEnd { req_id: 2 })
+    })
+    .await;
+    assert!(replies.contains(&IncomingMessage::TickPrice {
+        req_id: 2,
+        tick_type: 4,
+        price: 175.05,
+        size: 0.0,
+        attr_mask: 0,
+    }));
+
+    connection
+        .send(OutgoingMessage::ReqMktData {
+            req_id: 3,
+            contract: Contract::new("NOPE", SecType::Stock),
+            generic_ticks: String::new(),
+            snapshot: true,
+        })
+        .await?;
+    let replies = collect_until(&mut rx, |m| {
+        matches!(m, IncomingMessage::Error { id: 3, .. })
+    })
+    .await;
+    assert!(matches!(
+        replies.last(),
+        Some(IncomingMessage::Error { code: 200, .. })
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_gateway_order_lifecycle() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let connection = Connection::connect(&gateway.config()).await?;
+    let mut rx = connection.subscribe();
+    let contract = Contract::new("AAPL", SecType::Stock);
+
+    connection
+        .send(OutgoingMessage::PlaceOrder {
+            order_id: 1,
+            contract: contract.clone(),
+            order: Box::new(
+                Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0),
+            ),
+        })
+        .await?;
+    collect_until(
+        &mut rx,
+        |m| matches!(m, IncomingMessage::OrderStatus(s) if s.order_id == 1),
+    )
+    .await;
+    assert_eq!(gateway.open_orders().len(), 1);
+
+    connection
+        .send(OutgoingMessage::CancelOrder { order_id: 1 })
+        .await?;
+    let replies = collect_until(&mut rx, |m| matches!(m, IncomingMessage::OrderStatus(_))).await;
+    assert!(matches!(&replies[0], IncomingMessage::OrderStatus(s) if s.status == "Cancelled"));
+    assert!(gateway.open_orders().is_empty());
+
+    gateway.set_order_behavior(OrderBehavior::Fill {
+        price: 171.0,
+        commission: 1.0,
+    });
+    connection
+        .send(OutgoingMessage::PlaceOrder {
+            order_id: 2,
+            contract,
+            order: Box::new(Order::new(OrderAction::Buy, 10.0, OrderType::Market)),
+        })
+        .await?;
+    let replies = collect_until(
+        &mut rx,
+        |m| matches!(m, IncomingMessage::OrderStatus(s) if s.status == "Filled"),
+    )
+    .await;
+    assert!(replies
+        .iter()
+        .any(|m| matches!(m, IncomingMessage::ExecutionData { order_id: 2, .. })));
+    assert!(replies
+        .iter()
+        .any(|m| matches!(m, IncomingMessage::CommissionReport(c) if c.commission == 1.0)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_gateway_disconnect() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let mut config = gateway.config();
+    config.reconnect.enabled = false;
+    let client = IBKRClient::new(config);
+    client.connect().await?;
+    assert!(client.is_connected().await);
+
+    gateway.disconnect_all();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.is_connected().await {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("client did not notice the disconnect");
+
+    client.connect().await?;
+    assert!(client.is_connected().await);
+    assert_eq!(gateway.connection_count(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_gateway_delayed_replies() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let connection = Connection::connect(&gateway.config()).await?;
+    let mut rx = connection.subscribe();
+
+    gateway.set_reply_delay(Duration::from_millis(300));
+    let started = tokio::time::Instant::now();
+    connection.send(OutgoingMessage::ReqCurrentTime).await?;
+    collect_until(&mut rx, |m| {
+        matches!(m, IncomingMessage::CurrentTime { .. })
+    })
+    .await;
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_times_out_on_silent_gateway() {
+    let gateway = FakeGateway::start().await;
+    gateway.set_reply_delay(Duration::from_secs(10));
+
+    let mut config = gateway.config();
+    config.timeout = 1;
+    let client = IBKRClient::new(config);
+
+    assert!(matches!(
+        client.connect().await,
+        Err(ibkr_mcp_server::IBKRMCPError::Timeout)
+    ));
+}
+
+async fn wait_for_state(manager: &ConnectionManager, state: ConnectionState) {
+    let mut states = manager.watch_state();
+    tokio::time::timeout(Duration::from_secs(5), states.wait_for(|s| *s == state))
+        .await
+        .unwrap_or_else(|_| panic!("connection never became {:?}", state))
+        .unwrap();
+}
+
+fn count_received(gateway: &FakeGateway, predicate: impl Fn(&OutgoingMessage) -> bool) -> usize {
+    gateway.received().iter().filter(|m| predicate(m)).count()
+}
+
+#[tokio::test]
+async fn test_auto_reconnect_after_socket_loss() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let manager = Arc::new(ConnectionManager::new(gateway.config()));
+    manager.start().await?;
+    assert_eq!(manager.state(), ConnectionState::Connected);
+    let open_orders_requests = |count: usize| {
+        let gateway = &gateway;
+        async move {
+            gateway
+                .wait_for(Duration::from_secs(5), |received| {
+                    received
+                        .iter()
+                        .filter(|m| matches!(m, OutgoingMessage::ReqAllOpenOrders))
+                        .count()
+                        == count
+                })
+                .await
+        }
+    };
+    assert!(open_orders_requests(1).await);
+
+    let mut states = manager.watch_state();
+    gateway.disconnect_all();
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        states.wait_for(|s| *s == ConnectionState::Connecting),
+    )
+    .await
+    .expect("socket loss not detected")
+    .unwrap();
+    wait_for_state(&manager, ConnectionState::Connected).await;
+
+    assert_eq!(manager.reconnects(), 1);
+    assert_eq!(gateway.connection_count(), 2);
+    assert!(manager.current().await.is_ok());
+
+    // Open orders are requested again on the new socket
+    assert!(open_orders_requests(2).await);
+
+    manager.stop().await;
+    assert_eq!(manager.state(), ConnectionState::Disconnected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connectivity_notices_degrade_state() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let manager = Arc::new(ConnectionManager::new(gateway.config()));
+    manager.start().await?;
+
+    gateway.push(IncomingMessage::Error {
+        id: -1,
+        code: 1100,
+        message: "Connectivity between IB and Trader Workstation has been lost.".into(),
+    });
+    wait_for_state(&manager, ConnectionState::Degraded).await;
+    assert!(manager.current().await.is_ok());
+
+    gateway.push(IncomingMessage::Error {
+        id: -1,
+        code: 1101,
+        message: "Connectivity between IB and TWS has been restored- data lost.".into(),
+    });
+    wait_for_state(&manager, ConnectionState::Connected).await;
+    assert!(
+        gateway
+            .wait_for(Duration::from_secs(5), |received| {
+                received
+                    .iter()
+                    .filter(|m| matches!(m, OutgoingMessage::ReqAllOpenOrders))
+                    .count()
+                    == 2
+            })
+            .await
+    );
+    assert_eq!(manager.reconnects(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missed_heartbeats_trigger_reconnect() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let mut config = gateway.config();
+    config.reconnect.heartbeat_interval_ms = 50;
+    config.reconnect.max_missed_heartbeats = 2;
+    let manager = Arc::new(ConnectionManager::new(config));
+    manager.start().await?;
+
+    // Heartbeats are answered while the gateway is responsive
+    assert!(
+        gateway
+            .wait_for(Duration::from_secs(5), |received| {
+                received
+                    .iter()
+                    .filter(|m| matches!(m, OutgoingMessage::ReqCurrentTime))
+                    .count()
+                    >= 3
+            })
+            .await
+    );
+    assert_eq!(manager.state(), ConnectionState::Connected);
+
+    gateway.set_reply_delay(Duration::from_secs(1));
+    wait_for_state(&manager, ConnectionState::Degraded).await;
+    gateway.set_reply_delay(Duration::ZERO);
+
+    wait_for_state(&manager, ConnectionState::Connected).await;
+    assert!(manager.reconnects() >= 1);
+    assert!(gateway.connection_count() >= 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_reports_connection_state() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let client = IBKRClient::new(gateway.config());
+    assert_eq!(
+        client.connection_state().await,
+        ConnectionState::Disconnected
+    );
+
+    client.connect().await?;
+    assert_eq!(client.connection_state().await, ConnectionState::Connected);
+
+    client.reconnect().await?;
+    assert_eq!(client.connection_state().await, ConnectionState::Connected);
+    assert_eq!(gateway.connection_count(), 2);
+
+    client.disconnect().await?;
+    assert_eq!(
+        client.connection_state().await,
+        ConnectionState::Disconnected
+    );
+    assert_eq!(
+        memory_client().connection_state().await,
+        ConnectionState::Disconnected
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_ids_persist_across_restarts() -> Result<()> {
+    let state_dir = tempfile::tempdir()?;
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 1.0, OrderType::Limit).with_limit_price(1.0);
+
+    let gateway = FakeGateway::start().await;
+    let mut config = gateway.config();
+    config.state_dir = Some(state_dir.path().to_string_lossy().into_owned());
+    let client = IBKRClient::new(config.clone());
+    client.connect().await?;
+    assert_eq!(client.place_order(&contract, &order).await?, 1);
+    assert_eq!(client.place_order(&contract, &order).await?, 2);
+    client.disconnect().await?;
+
+    // A fresh gateway reports nextValidId 1 again; the persisted mark wins
+    let gateway = FakeGateway::start().await;
+    config.port = gateway.port();
+    let client = IBKRClient::new(config);
+    client.connect().await?;
+    assert_eq!(client.next_valid_id().await, Some(3));
+    assert_eq!(client.place_order(&contract, &order).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_duplicate_order_id_resyncs_with_req_ids() -> Result<()> {
+    let gateway = FakeGateway::start().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+    assert_eq!(client.next_valid_id().await, Some(1));
+
+    // Another session on the same client ID consumes IDs behind our back
+    let other = Connection::connect(&gateway.config()).await?;
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 1.0, OrderType::Limit).with_limit_price(1.0);
+    other
+        .send(OutgoingMessage::PlaceOrder {
+            order_id: 10,
+            contract: contract.clone(),
+            order: Box::new(order.clone()),
+        })
+        .await?;
+    while gateway.open_orders().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert_eq!(client.place_order(&contract, &order).await?, 11);
+    assert_eq!(client.next_valid_id().await, Some(12));
+    assert_eq!(
+        count_received(&gateway, |m| matches!(m, OutgoingMessage::ReqIds { .. })),
+        1
+    );
+
+    Ok(())
+}
+
+async fn wait_for_order(
+    client: &IBKRClient,
+    order_id: i32,
+    done: impl Fn(&TrackedOrder) -> bool,
+) -> TrackedOrder {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(order) = client.get_order_status(order_id).await {
+                if done(&order) {
+                    return order;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("order never reached the expected state")
+}
+
+#[tokio::test]
+async fn test_order_book_tracks_fills() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    gateway.set_order_behavior(OrderBehavior::Fill {
+        price: 175.25,
+        commission: 1.05,
+    });
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 100.0, OrderType::Market);
+    let order_id = client.place_order(&contract, &order).await?;
+
+    let tracked = wait_for_order(&client, order_id, |o| o.commission > 0.0).await;
+    assert_eq!(tracked.status, OrderStatus::Filled);
+    assert_eq!(tracked.filled, 100.0);
+    assert_eq!(tracked.remaining, 0.0);
+    assert_eq!(tracked.avg_fill_price, 175.25);
+    assert_eq!(tracked.commission, 1.05);
+    assert_eq!(tracked.fills.len(), 1);
+    assert!(client.get_open_orders().await?.is_empty());
+
+    assert!(matches!(
+        client.get_order_status(424242).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_book_tracks_cancel_and_foreign_orders() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let order_id = client.place_order(&contract, &order).await?;
+    let tracked = wait_for_order(&client, order_id, |o| o.status == OrderStatus::Submitted).await;
+    assert_eq!(tracked.order.lmt_price, Some(170.0));
+
+    // An order placed by another session shows up after a refresh
+    let other = Connection::connect(&gateway.config()).await?;
+    other
+        .send(OutgoingMessage::PlaceOrder {
+            order_id: 5000,
+            contract: Contract::new("MSFT", SecType::Stock),
+            order: Box::new(
+                Order::new(OrderAction::Sell, 5.0, OrderType::Limit).with_limit_price(400.0),
+            ),
+        })
+        .await?;
+    while gateway.open_orders().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let open: Vec<i32> = client
+        .get_open_orders()
+        .await?
+        .iter()
+        .map(|o| o.order_id)
+        .collect();
+    assert_eq!(open, vec![order_id, 5000]);
+
+    assert!(client.cancel_order(order_id).await?);
+    let tracked = wait_for_order(&client, order_id, |o| !o.is_open()).await;
+    assert_eq!(tracked.status, OrderStatus::Cancelled);
+    let open = client.get_open_orders().await?;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].contract.symbol, "MSFT");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_modify_order() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let order_id = client.place_order(&contract, &order).await?;
+
+    let changes = OrderChanges {
+        quantity: Some(20.0),
+        limit_price: Some(171.5),
+        time_in_force: Some(TimeInForce::Gtc),
+        ..Default::default()
+    };
+    let modified = client.modify_order(order_id, &changes).await?;
+    assert_eq!(modified.order_id, order_id);
+    assert_eq!(modified.order.total_quantity, 20.0);
+    assert_eq!(modified.order.lmt_price, Some(171.5));
+    assert_eq!(modified.order.time_in_force, TimeInForce::Gtc);
+
+    // Same ID at the gateway, so queue priority is kept
+    let working = gateway.open_orders();
+    assert_eq!(working.len(), 1);
+    assert_eq!(working[0].order_id, order_id);
+    assert_eq!(working[0].order.lmt_price, Some(171.5));
+
+    assert!(matches!(
+        client
+            .modify_order(order_id, &OrderChanges::default())
+            .await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        client
+            .modify_order(
+                order_id,
+                &OrderChanges {
+                    time_in_force: Some(TimeInForce::Gtd),
+                    ..Default::default()
+                }
+            )
+            .await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+
+    client.cancel_order(order_id).await?;
+    wait_for_order(&client, order_id, |o| !o.is_open()).await;
+    assert!(matches!(
+        client.modify_order(order_id, &changes).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+    assert!(matches!(
+        client.modify_order(9999, &changes).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_modify_order_memory_backend() -> Result<()> {
+    let client = memory_client();
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let order_id = client.place_order(&contract, &order).await?;
+    let modified = client
+        .modify_order(
+            order_id,
+            &OrderChanges {
+                limit_price: Some(172.0),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(modified.order.lmt_price, Some(172.0));
+    assert_eq!(client.get_open_orders().await?.len(), 1);
+
+    client.cancel_order(order_id).await?;
+    let err = client
+        .modify_order(
+            order_id,
+            &OrderChanges {
+                limit_price: Some(173.0),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Cancelled"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bracket_order() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let entry = Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(175.0);
+    let bracket = BracketOrder::new(entry, 185.0, 170.0)?;
+    let ids = client.place_bracket_order(&contract, bracket).await?;
+    assert_eq!(ids.take_profit_order_id, ids.parent_order_id + 1);
+    assert_eq!(ids.stop_loss_order_id, ids.parent_order_id + 2);
+
+    // Held legs are released together when the stop-loss is transmitted
+    let working = gateway.open_orders();
+    let statuses: Vec<_> = working.iter().map(|o| o.status.as_str()).collect();
+    assert_eq!(statuses, vec!["Submitted", "PreSubmitted", "PreSubmitted"]);
+    assert!(!working[0].order.transmit);
+    assert_eq!(working[1].order.parent_id, Some(ids.parent_order_id));
+    assert_eq!(working[2].order.aux_price, Some(170.0));
+
+    let parent = client.get_order_status(ids.parent_order_id).await?;
+    assert_eq!(parent.status, OrderStatus::Submitted);
+    let stop = client.get_order_status(ids.stop_loss_order_id).await?;
+    assert_eq!(stop.status, OrderStatus::PreSubmitted);
+    assert_eq!(stop.order.parent_id, Some(ids.parent_order_id));
+
+    // Cancelling the parent takes the exits with it
+    assert!(client.cancel_order(ids.parent_order_id).await?);
+    let take_profit = wait_for_order(&client, ids.take_profit_order_id, |o| !o.is_open()).await;
+    assert_eq!(take_profit.status, OrderStatus::Cancelled);
+    assert!(gateway.open_orders().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rejected_bracket_leaves_nothing_working() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    gateway.set_order_behavior(OrderBehavior::Reject {
+        code: 201,
+        message: "Order rejected - reason: insufficient margin".into(),
+    });
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let entry = Order::new(OrderAction::Sell, 10.0, OrderType::Market);
+    let bracket = BracketOrder::new(entry, 160.0, 190.0)?;
+    assert!(matches!(
+        client.place_bracket_order(&contract, bracket).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+    assert!(gateway.open_orders().is_empty());
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bracket_order_memory_backend() -> Result<()> {
+    let client = memory_client();
+    client.connect().await?;
+
+    let contract = Contract::new("MSFT", SecType::Stock);
+    let entry = Order::new(OrderAction::Buy, 5.0, OrderType::Market);
+    let ids = client
+        .place_bracket_order(&contract, BracketOrder::new(entry, 400.0, 350.0)?)
+        .await?;
+    assert_eq!(client.get_open_orders().await?.len(), 3);
+
+    client.cancel_order(ids.parent_order_id).await?;
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+fn limit_exits() -> Vec<(Contract, Order)> {
+    let contract = Contract::new("AAPL", SecType::Stock);
+    vec![
+        (
+            contract.clone(),
+            Order::new(OrderAction::Sell, 100.0, OrderType::Limit).with_limit_price(185.0),
+        ),
+        (
+            contract,
+            Order::new(OrderAction::Sell, 100.0, OrderType::Limit).with_limit_price(190.0),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_oca_fill_cancels_siblings() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let ids = client
+        .place_oca_group("exits", OcaType::CancelWithBlock, limit_exits())
+        .await?;
+    assert_eq!(ids.len(), 2);
+    let working = gateway.open_orders();
+    assert!(working
+        .iter()
+        .all(|o| o.order.oca_group.as_deref() == Some("exits")
+            && o.order.oca_type == Some(OcaType::CancelWithBlock)));
+
+    let open = client.get_open_orders().await?;
+    assert_eq!(open.len(), 2);
+    assert!(open
+        .iter()
+        .all(|o| o.order.oca_group.as_deref() == Some("exits")));
+
+    assert!(gateway.fill_order(ids[0], 185.0, 1.0));
+    let filled = wait_for_order(&client, ids[0], |o| o.status == OrderStatus::Filled).await;
+    assert_eq!(filled.filled, 100.0);
+    let sibling = wait_for_order(&client, ids[1], |o| !o.is_open()).await;
+    assert_eq!(sibling.status, OrderStatus::Cancelled);
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_oca_group() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let other = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(160.0);
+    let other_id = client.place_order(&contract, &other).await?;
+    let ids = client
+        .place_oca_group("exits", OcaType::ReduceWithBlock, limit_exits())
+        .await?;
+
+    let mut cancelled = client.cancel_oca_group("exits").await?;
+    cancelled.sort();
+    assert_eq!(cancelled, ids);
+    let open = gateway.open_orders();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].order_id, other_id);
+
+    assert!(matches!(
+        client.cancel_oca_group("exits").await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+    assert!(matches!(
+        client
+            .place_oca_group(
+                "single",
+                OcaType::CancelWithBlock,
+                limit_exits()[..1].to_vec()
+            )
+            .await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oca_group_memory_backend() -> Result<()> {
+    let client = memory_client();
+    client.connect().await?;
+
+    client
+        .place_oca_group("exits", OcaType::CancelWithBlock, limit_exits())
+        .await?;
+    assert_eq!(client.get_open_orders().await?.len(), 2);
+    assert_eq!(client.cancel_oca_group("exits").await?.len(), 2);
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stop_limit_and_trailing_orders() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let stop_limit = Order::new(OrderAction::Sell, 10.0, OrderType::StopLimit)
+        .with_stop_price(170.0)
+        .with_limit_price(169.5);
+    let trail = Order::new(OrderAction::Sell, 10.0, OrderType::Trail)
+        .with_trailing_percent(2.5)
+        .with_trail_stop_price(171.0);
+    let mut trail_limit = Order::new(OrderAction::Sell, 10.0, OrderType::TrailLimit)
+        .with_trailing_amount(1.25)
+        .with_trail_stop_price(173.0);
+    trail_limit.lmt_price_offset = Some(0.1);
+
+    for order in [&stop_limit, &trail, &trail_limit] {
+        let order_id = client.place_order(&contract, order).await?;
+        let tracked = client.get_order_status(order_id).await?;
+        assert_eq!(&tracked.order.order_type, &order.order_type);
+    }
+    let working: Vec<Order> = gateway.open_orders().into_iter().map(|o| o.order).collect();
+    assert_eq!(working[0].aux_price, Some(170.0));
+    assert_eq!(working[0].lmt_price, Some(169.5));
+    assert_eq!(working[1].trailing_percent, Some(2.5));
+    assert_eq!(working[1].aux_price, None);
+    assert_eq!(working[1].trail_stop_price, Some(171.0));
+    assert_eq!(working[2].aux_price, Some(1.25));
+    assert_eq!(working[2].lmt_price_offset, Some(0.1));
+
+    // A trailing stop without a trailing distance never reaches the gateway
+    let placed = count_received(&gateway, |m| {
+        matches!(m, OutgoingMessage::PlaceOrder { .. })
+    });
+    let missing = Order::new(OrderAction::Sell, 10.0, OrderType::Trail);
+    assert!(matches!(
+        client.place_order(&contract, &missing).await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+    assert_eq!(
+        count_received(&gateway, |m| matches!(
+            m,
+            OutgoingMessage::PlaceOrder { .. }
+        )),
+        placed
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_timing_fields_reach_gateway() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let mut order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit)
+        .with_limit_price(170.0)
+        .with_tif(TimeInForce::Gtd);
+    order.good_till_date = Some("20260320 16:00:00 US/Eastern".into());
+    order.good_after_time = Some("20260319 09:30:00 US/Eastern".into());
+    order.outside_rth = true;
+    order.hidden = true;
+    let order_id = client.place_order(&contract, &order).await?;
+
+    let working = gateway.open_orders();
+    assert_eq!(working[0].order, order);
+    let tracked = client.get_order_status(order_id).await?;
+    assert_eq!(tracked.order.time_in_force, TimeInForce::Gtd);
+    assert_eq!(
+        tracked.order.good_till_date.as_deref(),
+        Some("20260320 16:00:00 US/Eastern")
+    );
+
+    let mut ioc = Order::new(OrderAction::Buy, 10.0, OrderType::Limit)
+        .with_limit_price(170.0)
+        .with_tif(TimeInForce::Ioc);
+    ioc.outside_rth = true;
+    assert!(matches!(
+        client.place_order(&contract, &ioc).await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+    assert_eq!(gateway.open_orders().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_algo_orders_reach_gateway() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let vwap = Order::new(OrderAction::Buy, 5000.0, OrderType::Limit)
+        .with_limit_price(175.0)
+        .with_algo(
+            AlgoStrategy::Vwap,
+            AlgoParams {
+                max_pct_vol: Some(0.1),
+                start_time: Some("09:45:00 US/Eastern".into()),
+                end_time: Some("15:30:00 US/Eastern".into()),
+                allow_past_end_time: Some(false),
+                ..Default::default()
+            },
+        );
+    let order_id = client.place_order(&contract, &vwap).await?;
+
+    assert_eq!(gateway.open_orders()[0].order, vwap);
+    let tracked = client.get_order_status(order_id).await?;
+    assert_eq!(tracked.order.algo_strategy, Some(AlgoStrategy::Vwap));
+    assert_eq!(tracked.order.algo_params.max_pct_vol, Some(0.1));
+
+    // Adaptive needs a priority and cannot ride on a stop order
+    let adaptive_stop = Order::new(OrderAction::Sell, 100.0, OrderType::Stop)
+        .with_stop_price(170.0)
+        .with_algo(
+            AlgoStrategy::Adaptive,
+            AlgoParams {
+                adaptive_priority: Some(AdaptivePriority::Urgent),
+                ..Default::default()
+            },
+        );
+    assert!(matches!(
+        client.place_order(&contract, &adaptive_stop).await,
+        Err(ibkr_mcp_server::IBKRMCPError::InvalidParameter(_))
+    ));
+    assert_eq!(gateway.open_orders().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_preview_order_never_transmits() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    gateway.set_what_if_preview(OrderPreview {
+        init_margin_before: Some(25_000.0),
+        init_margin_change: Some(5_250.0),
+        init_margin_after: Some(30_250.0),
+        maint_margin_change: Some(4_375.0),
+        equity_with_loan_after: Some(149_999.0),
+        commission: Some(1.0),
+        commission_currency: Some("USD".into()),
+        warning_text: Some("Order exceeds 10% of average daily volume".into()),
+        ..Default::default()
+    });
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 100.0, OrderType::Limit).with_limit_price(175.0);
+    let preview = client.preview_order(&contract, &order).await?;
+    assert_eq!(preview.init_margin_change, Some(5_250.0));
+    assert_eq!(preview.commission, Some(1.0));
+    assert_eq!(preview.maint_margin_before, None);
+    assert!(preview.warning_text.unwrap().contains("daily volume"));
+
+    let sent: Vec<Order> = gateway
+        .received()
+        .into_iter()
+        .filter_map(|m| match m {
+            OutgoingMessage::PlaceOrder { order, .. } => Some(*order),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].what_if);
+    assert!(gateway.open_orders().is_empty());
+    assert!(client.get_open_orders().await?.is_empty());
+
+    // Invalid orders are rejected before anything is sent
+    let unpriced = Order::new(OrderAction::Buy, 100.0, OrderType::Limit);
+    assert!(client.preview_order(&contract, &unpriced).await.is_err());
+
+    gateway.set_order_behavior(OrderBehavior::Reject {
+        code: 201,
+        message: "Order rejected - reason: no trading permissions".into(),
+    });
+    assert!(matches!(
+        client.preview_order(&contract, &order).await,
+        Err(ibkr_mcp_server::IBKRMCPError::Order(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_preview_order_memory_backend() -> Result<()> {
+    let client = memory_client();
+    client.connect().await?;
+
+    let contract = Contract::new("MSFT", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Market);
+    let preview = client.preview_order(&contract, &order).await?;
+    assert_eq!(preview.init_margin_change, Some(10.0 * 375.0 * 0.30));
+    assert_eq!(preview.commission, Some(1.0));
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_readonly_refuses_mutations() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let mut config = gateway.config();
+    config.readonly = true;
+    let client = IBKRClient::new(config);
+    client.connect().await?;
+    assert!(client.is_readonly());
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let readonly = |result: Result<_>| matches!(result, Err(IBKRMCPError::ReadOnly(_)));
+
+    assert!(readonly(
+        client.place_order(&contract, &order).await.map(|_| ())
+    ));
+    assert!(readonly(client.cancel_order(1000).await.map(|_| ())));
+    assert!(readonly(
+        client
+            .modify_order(
+                1000,
+                &OrderChanges {
+                    quantity: Some(5.0),
+                    ..Default::default()
+                }
+            )
+            .await
+            .map(|_| ())
+    ));
+    assert!(readonly(
+        client
+            .place_bracket_order(&contract, BracketOrder::new(order.clone(), 180.0, 160.0)?)
+            .await
+            .map(|_| ())
+    ));
+    assert!(readonly(
+        client
+            .place_oca_group("exits", OcaType::CancelWithBlock, limit_exits())
+            .await
+            .map(|_| ())
+    ));
+    assert!(readonly(client.cancel_oca_group("exits").await.map(|_| ())));
+    assert!(readonly(client.reconnect().await));
+    assert_eq!(
+        count_received(&gateway, |m| matches!(
+            m,
+            OutgoingMessage::PlaceOrder { .. } | OutgoingMessage::CancelOrder { .. }
+        )),
+        0
+    );
+    assert_eq!(gateway.connection_count(), 1);
+
+    // Reads and what-if previews still work
+    assert_eq!(client.get_positions().await?.len(), 2);
+    assert!(client.get_open_orders().await?.is_empty());
+    client.preview_order(&contract, &order).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_risk_limits_block_orders_before_transmission() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config()).with_risk_limits(RiskConfig {
+        max_position: Some(150.0),
+        max_daily_notional: Some(20_000.0),
+        allowed_symbols: vec!["AAPL".to_string(), "MSFT".to_string()],
+        price_collar_pct: Some(5.0),
+        ..RiskConfig::default()
+    });
+    client.connect().await?;
+
+    let aapl = Contract::new("AAPL", SecType::Stock);
+    let limit = |action, quantity, price| {
+        Order::new(action, quantity, OrderType::Limit).with_limit_price(price)
+    };
+    let rejected_by = |result: Result<i32>| match result {
+        Err(IBKRMCPError::RiskRejected(violations)) => {
+            violations.into_iter().map(|v| v.rule).collect::<Vec<_>>()
+        }
+        other => panic!("expected a risk rejection, got {:?}", other),
+    };
+
+    // 100 AAPL held, so buying 60 more breaks the position limit
+    assert_eq!(
+        rejected_by(
+            client
+                .place_order(&aapl, &limit(OrderAction::Buy, 60.0, 175.0))
+                .await
+        ),
+        vec![RiskRule::MaxPosition]
+    );
+    assert_eq!(
+        rejected_by(
+            client
+                .place_order(&aapl, &limit(OrderAction::Buy, 10.0, 200.0))
+                .await
+        ),
+        vec![RiskRule::PriceCollar]
+    );
+    assert_eq!(
+        rejected_by(
+            client
+                .place_order(
+                    &Contract::new("TSLA", SecType::Stock),
+                    &limit(OrderAction::Buy, 10.0, 180.0)
+                )
+                .await
+        ),
+        vec![RiskRule::SymbolNotAllowed, RiskRule::MarketPriceUnavailable]
+    );
+
+    client
+        .place_order(&aapl, &limit(OrderAction::Buy, 40.0, 175.0))
+        .await?;
+    assert_eq!(client.risk().usage().daily_notional, 7_000.0);
+    assert_eq!(
+        rejected_by(
+            client
+                .place_order(&aapl, &limit(OrderAction::Sell, 100.0, 175.0))
+                .await
+        ),
+        vec![RiskRule::MaxDailyNotional]
+    );
+    // The order still working counts towards the position: 100 held plus
+    // 40 working plus 20 more breaks the limit of 150
+    assert_eq!(
+        rejected_by(
+            client
+                .place_order(&aapl, &limit(OrderAction::Buy, 20.0, 175.0))
+                .await
+        ),
+        vec![RiskRule::MaxPosition]
+    );
+
+    assert_eq!(
+        count_received(&gateway, |m| matches!(
+            m,
+            OutgoingMessage::PlaceOrder { .. }
+        )),
+        1
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_orders_share_daily_notional() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = Arc::new(
+        IBKRClient::new(gateway.config()).with_risk_limits(RiskConfig {
+            max_daily_notional: Some(10_000.0),
+            ..RiskConfig::default()
+        }),
+    );
+    client.connect().await?;
+    gateway.set_reply_delay(Duration::from_millis(100));
+
+    // Each fits the remaining notional alone, not both together
+    let aapl = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 40.0, OrderType::Limit).with_limit_price(175.0);
+    let (first, second) = tokio::join!(
+        client.place_order(&aapl, &order),
+        client.place_order(&aapl, &order)
+    );
+    let rejected = [&first, &second]
+        .iter()
+        .filter(|result| matches!(result, Err(IBKRMCPError::RiskRejected(_))))
+        .count();
+    assert_eq!(rejected, 1, "{:?} {:?}", first, second);
+    assert_eq!(client.risk().usage().daily_notional, 7_000.0);
+
+    // A failed transmission gives its reservation back
+    gateway.set_reply_delay(Duration::ZERO);
+    client.disconnect().await?;
+    assert!(client.place_order(&aapl, &order).await.is_err());
+    assert_eq!(client.risk().usage().daily_notional, 7_000.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kill_switch_halts_cancels_and_flattens() -> Result<()> {
+    let state_dir = tempfile::tempdir()?;
+    let gateway = scripted_gateway().await;
+    let mut config = gateway.config();
+    config.state_dir = Some(state_dir.path().to_string_lossy().into_owned());
+    let client = IBKRClient::new(config.clone());
+    client.connect().await?;
+
+    let aapl = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit).with_limit_price(170.0);
+    let working = client.place_order(&aapl, &order).await?;
+
+    let report = client.halt_trading("runaway strategy", true, true).await?;
+    assert_eq!(report.halt.reason, "runaway strategy");
+    assert_eq!(report.cancelled_orders, vec![working]);
+    assert_eq!(report.flatten_orders.len(), 2);
+    assert!(report.errors.is_empty());
+    let flattened: Vec<_> = gateway
+        .received()
+        .into_iter()
+        .filter_map(|m| match m {
+            OutgoingMessage::PlaceOrder {
+                contract, order, ..
+            } if order.order_type == OrderType::Market => Some((
+                contract.symbol,
+                order.action,
+                order.total_quantity,
+                order.account,
+            )),
+            _ => None,
+        })
+        .collect();
+    let account = Some("DU123456".to_string());
+    assert_eq!(
+        flattened,
+        vec![
+            (
+                "AAPL".to_string(),
+                OrderAction::Sell,
+                100.0,
+                account.clone()
+            ),
+            ("MSFT".to_string(), OrderAction::Sell, 50.0, account)
+        ]
+    );
+    assert_eq!(
+        client.get_order_status(working).await?.status,
+        OrderStatus::Cancelled
+    );
+
+    // New orders are refused, and the halt survives a restart
+    assert!(matches!(
+        client.place_order(&aapl, &order).await,
+        Err(IBKRMCPError::TradingHalted(_))
+    ));
+    client.disconnect().await?;
+    let client = IBKRClient::new(config);
+    client.connect().await?;
+    assert_eq!(client.trading_halt(), Some(report.halt.clone()));
+    assert!(matches!(
+        client
+            .place_bracket_order(&aapl, BracketOrder::new(order.clone(), 180.0, 160.0)?)
+            .await,
+        Err(IBKRMCPError::TradingHalted(_))
+    ));
+
+    assert_eq!(client.resume_trading()?, Some(report.halt));
+    assert!(client.trading_halt().is_none());
+    client.place_order(&aapl, &order).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kill_switch_attempts_every_step() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let mut config = gateway.config();
+    config.state_dir = None;
+    let client = IBKRClient::new(config);
+    client.connect().await?;
+
+    // Every flatten leg is rejected; each one is still attempted
+    gateway.set_order_behavior(OrderBehavior::Reject {
+        code: 201,
+        message: "Order rejected - reason: no trading permissions".into(),
+    });
+    let report = client.halt_trading("broker outage", true, true).await?;
+    assert!(report.cancelled_orders.is_empty());
+    assert!(report.flatten_orders.is_empty());
+    assert_eq!(report.errors.len(), 2, "{:?}", report.errors);
+    assert!(report.errors[0].contains("AAPL"));
+    assert!(report.errors[1].contains("MSFT"));
+    let legs = gateway
+        .received()
+        .iter()
+        .filter(|m| matches!(m, OutgoingMessage::PlaceOrder { .. }))
+        .count();
+    assert_eq!(legs, 2);
+    assert!(client.trading_halt().is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_ref_tags_orders_at_gateway() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let contract = Contract::new("AAPL", SecType::Stock);
+    let order = Order::new(OrderAction::Buy, 10.0, OrderType::Limit)
+        .with_limit_price(170.0)
+        .with_order_ref("agent-retry-1");
+    let order_id = client.place_order(&contract, &order).await?;
+
+    assert!(gateway.received().iter().any(|m| matches!(
+        m,
+        OutgoingMessage::PlaceOrder { order, .. }
+            if order.order_ref.as_deref() == Some("agent-retry-1")
+    )));
+    // Open orders report the tag, so a retry can find the order it placed
+    let tagged = client
+        .get_open_orders()
+        .await?
+        .into_iter()
+        .find(|o| o.order.order_ref.as_deref() == Some("agent-retry-1"))
+        .expect("tagged order is open");
+    assert_eq!(tagged.order_id, order_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_orders_by_filter() -> Result<()> {
+    let gateway = scripted_gateway().await;
+    let client = IBKRClient::new(gateway.config());
+    client.connect().await?;
+
+    let aapl = Contract::new("AAPL", SecType::Stock);
+    let msft = Contract::new("MSFT", SecType::Stock);
+    let limit = |action, price| Order::new(action, 10.0, OrderType::Limit).with_limit_price(price);
+    let aapl_buy = client
+        .place_order(&aapl, &limit(OrderAction::Buy, 170.0))
+        .await?;
+    client
+        .place_order(&aapl, &limit(OrderAction::Sell, 180.0))
+        .await?;
+    client
+        .place_order(&msft, &limit(OrderAction::Buy, 340.0))
+        .await?;
+    let bracket = client
+        .place_bracket_order(
+            &msft,
+            BracketOrder::new(limit(OrderAction::Buy, 345.0), 360.0, 330.0)?,
+        )
+        .await?;
+
+    let outcomes = client
+        .cancel_orders(&OrderFilter {
+            symbol: Some("aapl".to_string()),
+            action: Some(OrderAction::Buy),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(outcomes[0].order_id, aapl_buy);
+    assert!(outcomes[0].cancelled);
+
+    let recent_only = OrderFilter {
+        older_than: Some(Duration::from_secs(3600)),
+        ..Default::default()
+    };
+    assert!(client.cancel_orders(&recent_only).await?.is_empty());
+
+    // Everything else, including bracket exits that go with their parent
+    let outcomes = client.cancel_orders(&OrderFilter::default()).await?;
+    assert_eq!(outcomes.len(), 5);
+    assert!(outcomes.iter().all(|o| o.cancelled && o.error.is_none()));
+    assert!(outcomes
+        .iter()
+        .any(|o| o.order_id == bracket.stop_loss_order_id));
+    assert!(client.get_open_orders().await?.is_empty());
+
+    Ok(())
+}
+
+// Run the server over stdio on `requests`, returning the lines it wrote
+fn run_stdio(requests: &[&str]) -> Vec<serde_json::Value> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let state_dir = tempfile::tempdir().unwrap();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_ibkr-mcp-server"))
+        .args(["--transport", "stdio"])
+        .env("IBKR__IBKR__BACKEND", "memory")
+        .env("IBKR__IBKR__STATE_DIR", state_dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = server.stdin.take().unwrap();
+    for request in requests {
+        writeln!(stdin, "{}", request).unwrap();
+    }
+    // Closing stdin ends the session
+    drop(stdin);
+    let output = server.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("stdout is JSON-RPC"))
+        .collect()
+}
+
+#[test]
+fn test_stdio_transport_writes_only_json_rpc() {
+    let lines = run_stdio(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+        r#"[{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"get_positions","arguments":{}}}]"#,
+    ]);
+
+    let mut ids = Vec::new();
+    for message in lines {
+        let messages = match message {
+            serde_json::Value::Array(batch) => batch,
+            single => vec![single],
+        };
+        for message in messages {
+            assert_eq!(message["jsonrpc"], "2.0");
+            assert!(message.get("error").is_none(), "{}", message);
+            ids.push(message["id"].as_i64().unwrap());
+        }
+    }
+    ids.sort();
+    assert_eq!(ids, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_stdio_transport_refuses_batches_on_2025_06_18() {
+    let lines = run_stdio(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"[{"jsonrpc":"2.0","id":2,"method":"tools/list"}]"#,
+    ]);
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(lines[1]["id"], serde_json::Value::Null);
+    assert_eq!(lines[1]["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_token() -> Result<()> {
+    use serde_json::{json, Value};
+
+    let serve = |settings| async { format!("http://{}", spawn_server(settings).await.0) };
+
+    let gateway = scripted_gateway().await;
+    let mut settings = Settings::new().unwrap();
+    settings.ibkr = gateway.config();
+    settings.ibkr.state_dir = None;
+    let http = reqwest::Client::new();
+    let halt = json!({ "reason": "test" });
+
+    // Refused outright without a configured token
+    let url = serve(settings.clone()).await;
+    let response = http
+        .post(format!("{}/admin/halt", url))
+        .bearer_auth("")
+        .json(&halt)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    settings.mcp.admin_token = Some("s3cret".into());
+    let url = serve(settings.clone()).await;
+    for token in [None, Some("wrong")] {
+        let mut request = http.post(format!("{}/admin/halt", url)).json(&halt);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        assert_eq!(request.send().await.unwrap().status(), 401);
+    }
+
+    // No CORS headers, so browsers block cross-origin calls
+    let response = http
+        .post(format!("{}/admin/halt", url))
+        .header("Origin", "http://evil.example")
+        .bearer_auth("s3cret")
+        .json(&halt)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"]["halt"]["reason"], "test");
+
+    // Read-only mode refuses resume like tools/call does
+    settings.ibkr.readonly = true;
+    let url = serve(settings).await;
+    let response = http
+        .post(format!("{}/admin/resume", url))
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], false);
+    assert!(body["error"].as_str().unwrap().contains("resume_trading"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tool_route_refuses_other_origins() -> Result<()> {
+    use serde_json::{json, Value};
+
+    let mut settings = Settings::new().unwrap();
+    settings.ibkr.backend = BackendKind::Memory;
+    settings.ibkr.state_dir = None;
+    let (addr, call) = spawn_server(settings).await;
+    let url = format!("http://{}/mcp/tools", addr);
+
+    let http = reqwest::Client::new();
+    let order = json!({
+        "tool": "place_order",
+        "parameters": { "symbol": "AAPL", "action": "BUY", "quantity": 10 }
+    });
+    let place = |origin: &'static str| http.post(&url).header("Origin", origin).json(&order);
+
+    // A page on another site can neither call tools nor read the answer
+    let refused = place("http://evil.example").send().await.unwrap();
+    assert_eq!(refused.status(), 403);
+    assert!(refused
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+    let preflight = http
+        .request(reqwest::Method::OPTIONS, &url)
+        .header("Origin", "http://evil.example")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert!(preflight
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+
+    let open = call("get_open_orders", json!({})).await;
+    assert!(open["data"].as_array().unwrap().is_empty());
+
+    // Local pages may
+    let local = place("http://localhost:3000").send().await.unwrap();
+    assert_eq!(local.status(), 200);
+    assert_eq!(
+        local.headers()["access-control-allow-origin"],
+        "http://localhost:3000"
+    );
+    let placed: Value = local.json().await.unwrap();
+    assert_eq!(placed["success"], true, "{}", placed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_confirmation_tickets_over_http() -> Result<()> {
+    use serde_json::json;
+
+    let mut settings = Settings::new().unwrap();
+    settings.ibkr.backend = BackendKind::Memory;
+    settings.ibkr.state_dir = None;
+    settings.mcp.confirmation.enabled = true;
+    let (_, call) = spawn_server(settings).await;
+    let order = json!({
+        "symbol": "AAPL",
+        "action": "BUY",
+        "quantity": 10,
+        "order_type": "LMT",
+        "limit_price": 150.0
+    });
+    let pending = || async {
+        call("list_pending_orders", json!({})).await["data"]
+            .as_array()
+            .unwrap()
+            .len()
+    };
+
+    // Placing only parks the order
+    let parked = call("place_order", order.clone()).await;
+    assert_eq!(parked["success"], true, "{}", parked);
+    assert_eq!(parked["data"]["status"], "pending_confirmation");
+    let ticket = parked["data"]["ticket"]["ticket_id"].clone();
+    assert!(call("get_open_orders", json!({})).await["data"]
+        .as_array()
+        .unwrap()
+        .is_empty());
+
+    // A confirmation that fails keeps the ticket
+    call("halt_trading", json!({ "reason": "test" })).await;
+    let refused = call("confirm_order", json!({ "ticket_id": ticket })).await;
+    assert_eq!(refused["success"], false);
+    assert_eq!(pending().await, 1);
+
+    call("resume_trading", json!({})).await;
+    let confirmed = call("confirm_order", json!({ "ticket_id": ticket })).await;
+    assert_eq!(confirmed["success"], true, "{}", confirmed);
+    assert_eq!(confirmed["data"]["ticket_id"], ticket);
+    let order_id = confirmed["data"]["order_id"].as_i64().unwrap();
+    let open = call("get_open_orders", json!({})).await;
+    assert_eq!(open["data"][0]["order_id"], order_id);
+    assert_eq!(pending().await, 0);
+
+    // A confirmed ticket is spent
+    let again = call("confirm_order", json!({ "ticket_id": ticket })).await;
+    assert_eq!(again["success"], false);
+
+    // Rejected tickets are never transmitted
+    let parked = call("place_order", order).await;
+    let ticket = parked["data"]["ticket"]["ticket_id"].clone();
+    let rejected = call("reject_order", json!({ "ticket_id": ticket })).await;
+    assert_eq!(rejected["data"]["rejected"], true);
+    assert_eq!(pending().await, 0);
+    let open = call("get_open_orders", json!({})).await;
+    assert_eq!(open["data"].as_array().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_confirmations_transmit_once() -> Result<()> {
+    use serde_json::json;
+
+    let gateway = scripted_gateway().await;
+    let mut settings = Settings::new().unwrap();
+    settings.ibkr = gateway.config();
+    settings.ibkr.state_dir = None;
+    settings.mcp.confirmation.enabled = true;
+    let (_, call) = spawn_server(settings).await;
+
+    let parked = call(
+        "place_order",
+        json!({ "symbol": "AAPL", "action": "BUY", "quantity": 10, "client_order_key": "k1" }),
+    )
+    .await;
+    let ticket = parked["data"]["ticket"]["ticket_id"].clone();
+
+    // The second confirmation arrives while the first is still in flight
+    gateway.set_reply_delay(Duration::from_millis(300));
+    let confirm = || call("confirm_order", json!({ "ticket_id": ticket }));
+    let (first, second) = tokio::join!(confirm(), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        confirm().await
+    });
+    assert_eq!(first["success"], true, "{}", first);
+    assert_eq!(second["success"], false, "{}", second);
+    assert!(second["error"]
+        .as_str()
+        .unwrap()
+        .contains("confirmation in progress"));
+    let placed = count_received(
+        &gateway,
+        |m| matches!(m, OutgoingMessage::PlaceOrder { order, .. } if !order.what_if),
+    );
+    assert_eq!(placed, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_order_keys_survive_restarts() -> Result<()> {
+    use serde_json::json;
+
+    let gateway = scripted_gateway().await;
+    gateway.set_order_behavior(OrderBehavior::Fill {
+        price: 170.0,
+        commission: 1.0,
+    });
+    // Each server starts with an empty key store, as after a restart
+    let start = |confirm: bool| {
+        let mut settings = Settings::new().unwrap();
+        settings.ibkr = gateway.config();
+        settings.mcp.confirmation.enabled = confirm;
+        async move { spawn_server(settings).await.1 }
+    };
+    let order = |key: &str| {
+        json!({
+            "symbol": "AAPL",
+            "action": "BUY",
+            "quantity": 10,
+            "order_type": "LMT",
+            "limit_price": 170.0,
+            "client_order_key": key
+        })
+    };
+    // Orders transmitted, not the what-if previews tickets are priced with
+    let placed = || {
+        count_received(
+            &gateway,
+            |m| matches!(m, OutgoingMessage::PlaceOrder { order, .. } if !order.what_if),
+        )
+    };
+
+    // A filled order is found through today's executions
+    let first = start(false).await;
+    let filled = first("place_order", order("fill-1")).await;
+    assert_eq!(filled["success"], true, "{}", filled);
+    assert_eq!(filled["data"]["duplicate"], false);
+
+    let restarted = start(false).await;
+    let retried = restarted("place_order", order("fill-1")).await;
+    assert_eq!(retried["success"], true, "{}", retried);
+    assert_eq!(retried["data"]["duplicate"], true);
+    assert_eq!(retried["data"]["order_id"], filled["data"]["order_id"]);
+    assert_eq!(placed(), 1);
+
+    // The key still belongs to the order it placed
+    let mut other = order("fill-1");
+    other["limit_price"] = json!(171.0);
+    let reused = restarted("place_order", other).await;
+    assert_eq!(reused["success"], false, "{}", reused);
+    assert_eq!(placed(), 1);
+
+    // A cancelled order of an earlier session is reported, not replaced
+    gateway.set_order_behavior(OrderBehavior::Accept);
+    let resting = restarted("place_order", order("cancel-1")).await;
+    let order_id = resting["data"]["order_id"].clone();
+    restarted("cancel_order", json!({ "order_id": order_id })).await;
+    let restarted = start(false).await;
+    let retried = restarted("place_order", order("cancel-1")).await;
+    assert_eq!(retried["success"], false, "{}", retried);
+    assert!(retried["error"]
+        .as_str()
+        .unwrap()
+        .contains("in an earlier session"));
+    assert_eq!(placed(), 2);
+
+    // With confirmation on, a retry gets the pending ticket back and a
+    // retry after confirming gets the order
+    let confirming = start(true).await;
+    let parked = confirming("place_order", order("ticket-1")).await;
+    let ticket = parked["data"]["ticket"]["ticket_id"].clone();
+    let again = confirming("place_order", order("ticket-1")).await;
+    assert_eq!(again["data"]["ticket"]["ticket_id"], ticket);
+    let confirmed = confirming("confirm_order", json!({ "ticket_id": ticket })).await;
+    assert_eq!(confirmed["success"], true, "{}", confirmed);
+    let again = confirming("place_order", order("ticket-1")).await;
+    assert_eq!(again["data"]["duplicate"], true, "{}", again);
+    assert_eq!(again["data"]["order_id"], confirmed["data"]["order_id"]);
+    assert_eq!(placed(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_streamable_http_sessions() -> Result<()> {
+    use serde_json::{json, Value};
+
+    let gateway = scripted_gateway().await;
+    let mut settings = Settings::new().unwrap();
+    settings.ibkr = gateway.config();
+    settings.ibkr.state_dir = None;
+    settings.mcp.sse_upgrade_after_ms = 100;
+    settings.mcp.allowed_origins = vec!["https://app.example.com".to_string()];
+    let (addr, _) = spawn_server(settings).await;
+    let url = format!("http://{}/mcp", addr);
+
+    let http = reqwest::Client::new();
+    let post = |body: Value| {
+        http.post(&url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(&body)
+    };
+    let call = |id: i32, tool: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": {} }
+        })
+    };
+
+    // Everything but initialize needs a session
+    let response = post(call(1, "get_positions")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = http
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .body("{not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], -32700);
+
+    let response = post(json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2025-06-18",
+            "clientInfo": { "name": "test", "version": "1" }
+        }
+    }))
+    .send()
+    .await
+    .unwrap();
+    let session = response.headers()["mcp-session-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(body["result"]["capabilities"]["tools"]["listChanged"], true);
+
+    // Only pings are answered until the client confirms initialization
+    let response = post(call(1, "get_positions"))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = post(json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" }))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = post(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    // Later requests may only repeat the negotiated version
+    let response = post(call(2, "get_positions"))
+        .header("Mcp-Session-Id", &session)
+        .header("MCP-Protocol-Version", "2025-03-26")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // A quick call is answered with plain JSON
+    let response = post(call(2, "get_positions"))
+        .header("Mcp-Session-Id", &session)
+        .header("MCP-Protocol-Version", "2025-06-18")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["id"], 2);
+    assert_eq!(body["result"]["isError"], false);
+    assert_eq!(body["result"]["structuredContent"]["success"], true);
+    assert_eq!(body["result"]["content"][0]["type"], "text");
+
+    // Pages on other sites are refused, local and configured ones are not
+    for (origin, status) in [
+        ("http://evil.example", 403),
+        ("null", 403),
+        ("http://localhost.evil.example", 403),
+        ("http://localhost:3000", 200),
+        ("http://[::1]", 200),
+        ("https://app.example.com", 200),
+    ] {
+        let response = post(call(2, "get_positions"))
+            .header("Mcp-Session-Id", &session)
+            .header("Origin", origin)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{}", origin);
+    }
+    let response = http
+        .get(&url)
+        .header("Accept", "text/event-stream")
+        .header("Mcp-Session-Id", &session)
+        .header("Origin", "http://evil.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    // Tool failures are results flagged as errors, not JSON-RPC errors
+    let response = post(json!({
+        "jsonrpc": "2.0",
+        "id": 5,
+        "method": "tools/call",
+        "params": { "name": "get_order_status", "arguments": { "order_id": 999 } }
+    }))
+    .header("Mcp-Session-Id", &session)
+    .send()
+    .await
+    .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert!(body.get("error").is_none());
+    assert_eq!(body["result"]["isError"], true);
+    assert_eq!(body["result"]["structuredContent"]["success"], false);
+
+    // 2025-06-18 dropped batches
+    let response = post(json!([call(4, "get_positions")]))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], -32600);
+
+    // Malformed calls get JSON-RPC errors echoing their IDs
+    let mut errors = Vec::new();
+    for malformed in [
+        json!({ "jsonrpc": "2.0", "id": "a", "method": "tools/call", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": "b", "method": "resources/subscribe" }),
+        json!({ "jsonrpc": "2.0", "id": "c" }),
+    ] {
+        let response = post(malformed)
+            .header("Mcp-Session-Id", &session)
+            .send()
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        errors.push((
+            body["id"].as_str().unwrap().to_string(),
+            body["error"]["code"].as_i64().unwrap(),
+        ));
+    }
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|(id, code)| (id.as_str(), *code))
+        .collect();
+    assert_eq!(errors, vec![("a", -32602), ("b", -32601), ("c", -32600)]);
+
+    // A slow one switches to an SSE stream carrying the response
+    gateway.set_reply_delay(Duration::from_millis(500));
+    let response = post(call(3, "get_account_summary"))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let stream = response.text().await.unwrap();
+    let data = stream
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .expect("no event in stream");
+    let body: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(body["id"], 3);
+    gateway.set_reply_delay(Duration::ZERO);
+
+    let response = http
+        .get(&url)
+        .header("Accept", "text/event-stream")
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut stream = response;
+
+    // Progress of calls that ask for it, and tool list changes, arrive on
+    // the session's stream
+    let response = post(json!({
+        "jsonrpc": "2.0",
+        "id": 6,
+        "method": "tools/call",
+        "params": {
+            "name": "halt_trading",
+            "arguments": { "reason": "test" },
+            "_meta": { "progressToken": "halt-1" }
+        }
+    }))
+    .header("Mcp-Session-Id", &session)
+    .send()
+    .await
+    .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"]["isError"], false);
+
+    let mut events = Vec::new();
+    while events.len() < 3 {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), stream.chunk())
+            .await
+            .expect("no event on the session stream")
+            .unwrap()
+            .expect("session stream ended");
+        let chunk = String::from_utf8_lossy(&chunk).to_string();
+        events.extend(
+            chunk.lines().filter_map(|line| {
+                serde_json::from_str::<Value>(line.strip_prefix("data: ")?).ok()
+            }),
+        );
+    }
+    let methods: Vec<_> = events.iter().map(|e| e["method"].clone()).collect();
+    assert_eq!(
+        methods,
+        vec![
+            json!("notifications/progress"),
+            json!("notifications/tools/list_changed"),
+            json!("notifications/progress")
+        ]
+    );
+    assert_eq!(events[0]["params"]["progressToken"], "halt-1");
+    assert_eq!(events[0]["params"]["progress"], 0);
+    assert_eq!(events[2]["params"]["progress"], 1);
+
+    // Halted, the tools entering orders are not offered
+    let response = post(json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/list" }))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    let tools = body["result"]["tools"].as_array().unwrap();
+    assert!(tools.iter().all(|t| t["name"] != "place_order"));
+    assert!(tools.iter().any(|t| t["name"] == "cancel_order"));
+
+    // Once deleted, the session is gone
+    let response = http
+        .delete(&url)
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let response = post(call(4, "get_positions"))
+        .header("Mcp-Session-Id", &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    Ok(())
+}